@@ -49,3 +49,32 @@ impl State32 {
         self.b = 0;
     }
 }
+
+impl super::Checksum for State32 {
+    fn update(&mut self, buf: &[u8]) {
+        self.feed(buf)
+    }
+
+    fn value(&self) -> u32 {
+        self.result()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::State32;
+    use super::super::Checksum;
+
+    #[test]
+    fn vectors() {
+        let mut state = State32::new();
+        assert_eq!(state.value(), 1);
+        state.update(b"Wikipedia");
+        assert_eq!(state.value(), 0x11E60398);
+        state.reset();
+        state.update(b"Wiki");
+        state.update(b"pedia");
+        assert_eq!(state.value(), 0x11E60398);
+    }
+}
@@ -0,0 +1,109 @@
+/*!
+
+CRC-32 checksum, using the reflected IEEE polynomial of zlib and PNG
+
+This implementation is based off the description found at
+http://en.wikipedia.org/wiki/Cyclic_redundancy_check.
+
+# Example
+
+```rust
+use compress::checksum::crc32;
+let mut state = crc32::State32::new();
+state.feed(b"abracadabra");
+let checksum = state.result();
+```
+
+*/
+
+/// The reversed representation of the polynomial 0x04C11DB7
+const POLYNOMIAL: u32 = 0xEDB88320;
+
+/// Byte -> remainder table
+static TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut rem = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            rem = if rem & 1 != 0 { (rem >> 1) ^ POLYNOMIAL } else { rem >> 1 };
+            k += 1;
+        }
+        table[i] = rem;
+        i += 1;
+    }
+    table
+}
+
+/// CRC state for 32 bits
+pub struct State32 {
+    rem: u32,
+}
+
+impl State32 {
+    /// Create a new state
+    pub fn new() -> State32 {
+        State32 { rem: !0 }
+    }
+
+    /// Mutate the state for given data
+    pub fn feed(&mut self, buf: &[u8]) {
+        for byte in buf.iter() {
+            let index = (self.rem as u8 ^ *byte) as usize;
+            self.rem = (self.rem >> 8) ^ TABLE[index];
+        }
+    }
+
+    /// Get checksum
+    pub fn result(&self) -> u32 {
+        !self.rem
+    }
+
+    /// Reset the state
+    pub fn reset(&mut self) {
+        self.rem = !0;
+    }
+}
+
+impl super::Checksum for State32 {
+    fn update(&mut self, buf: &[u8]) {
+        self.feed(buf)
+    }
+
+    fn value(&self) -> u32 {
+        self.result()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::State32;
+    use super::super::Checksum;
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut state = State32::new();
+        state.update(data);
+        state.value()
+    }
+
+    #[test]
+    fn vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414FA339);
+    }
+
+    #[test]
+    fn chunks() {
+        let mut state = State32::new();
+        state.feed(b"The quick brown ");
+        state.feed(b"fox jumps over the lazy dog");
+        assert_eq!(state.result(), 0x414FA339);
+        state.reset();
+        assert_eq!(state.result(), 0);
+    }
+}
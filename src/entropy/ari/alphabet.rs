@@ -0,0 +1,111 @@
+/*!
+
+Remapping of sparse byte alphabets for the arithmetic coder.
+The bytes found in the data get dense indices in `[0,k)`, so that
+the model only spends the probability on `k` values. The map is stored
+ahead of the code as a bitmap of the used bytes.
+
+# Example
+
+```rust
+use compress::entropy::ari;
+use compress::entropy::ari::alphabet::AlphabetMap;
+
+let text = b"abracadabra";
+let map = AlphabetMap::from_data(text);
+let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+let indices: Vec<u32> = text.iter().map(|&b| map.map(b).unwrap() as u32).collect();
+let mut model = ari::table::FreqModel::new(map.get_num_symbols(), 16, threshold);
+let encoded = ari::encode_bytes(&indices, &mut model).unwrap();
+
+let mut model = ari::table::FreqModel::new(map.get_num_symbols(), 16, threshold);
+let decoded: Vec<u8> = ari::decode_bytes(&encoded, &mut model, text.len()).unwrap()
+    .into_iter().map(|i| map.unmap(i as usize)).collect();
+assert_eq!(&decoded[..], &text[..]);
+```
+
+*/
+
+#[cfg(feature="io")]
+use std::io::{self, Read, Write};
+use super::{Symbol, SYMBOL_TOTAL};
+
+#[cfg(feature="io")]
+/// Number of bytes in the stored bitmap
+const BITMAP_BYTES: usize = SYMBOL_TOTAL / 8;
+
+/// A bijection between the used bytes and the dense indices,
+/// the indices following the byte order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlphabetMap {
+    /// the used bytes, by their index
+    symbols: Vec<Symbol>,
+    /// the index of every byte, if used
+    indices: Vec<Option<u8>>,
+}
+
+impl AlphabetMap {
+    /// Create a map of the bytes used in the data
+    pub fn from_data(data: &[u8]) -> AlphabetMap {
+        let mut used = [false; SYMBOL_TOTAL];
+        for &byte in data.iter() {
+            used[byte as usize] = true;
+        }
+        AlphabetMap::from_used(&used)
+    }
+
+    fn from_used(used: &[bool; SYMBOL_TOTAL]) -> AlphabetMap {
+        let symbols: Vec<Symbol> = (0..SYMBOL_TOTAL).filter(|&b| used[b])
+            .map(|b| b as Symbol).collect();
+        let mut indices = vec![None; SYMBOL_TOTAL];
+        for (i, &byte) in symbols.iter().enumerate() {
+            indices[byte as usize] = Some(i as u8);
+        }
+        AlphabetMap {
+            symbols: symbols,
+            indices: indices,
+        }
+    }
+
+    /// Return the number of used bytes, the size of the dense alphabet
+    pub fn get_num_symbols(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Return the used bytes in the order of their indices
+    pub fn get_symbols(&self) -> &[Symbol] {
+        &self.symbols[..]
+    }
+
+    /// Return the dense index of a byte, `None` if it's not used
+    pub fn map(&self, byte: Symbol) -> Option<usize> {
+        self.indices[byte as usize].map(|i| i as usize)
+    }
+
+    /// Return the byte of a dense index, which has to be in the alphabet
+    pub fn unmap(&self, index: usize) -> Symbol {
+        self.symbols[index]
+    }
+
+    #[cfg(feature="io")]
+    /// Serialize the map as a bitmap of the used bytes, the lowest bit first
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut bitmap = [0u8; BITMAP_BYTES];
+        for &byte in self.symbols.iter() {
+            bitmap[byte as usize >> 3] |= 1 << (byte & 7);
+        }
+        w.write_all(&bitmap)
+    }
+
+    #[cfg(feature="io")]
+    /// Deserialize the map written by `write_to`
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<AlphabetMap> {
+        let mut bitmap = [0u8; BITMAP_BYTES];
+        try!(r.read_exact(&mut bitmap));
+        let mut used = [false; SYMBOL_TOTAL];
+        for (byte, flag) in used.iter_mut().enumerate() {
+            *flag = bitmap[byte >> 3] & (1 << (byte & 7)) != 0;
+        }
+        Ok(AlphabetMap::from_used(&used))
+    }
+}
@@ -0,0 +1,110 @@
+/*!
+
+Throughput measurements of the arithmetic coder, for external benchmark runners.
+Requires `bench` feature.
+
+Each function codes the input once and returns the number of input bytes
+along with the nanoseconds spent. The canned corpora stress different paths:
+random bytes renormalize on almost every symbol, the text is the usual case,
+and the zeros keep the range shrinking slowly, running into threshold cuts.
+
+# Example
+
+```rust
+use compress::entropy::ari::bench;
+
+let input = bench::corpus(bench::Corpus::Text, 1<<12);
+let (bytes, nanos) = bench::byte_encode(&input);
+println!("Encoding at {} MB/s", bench::mb_per_sec(bytes, nanos));
+```
+
+*/
+
+use std::io::{Read, Write};
+use std::time::Instant;
+use super::table;
+
+static TEXT: &'static [u8] = include_bytes!("../../data/test.txt");
+
+/// A distribution shape of the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corpus {
+    /// Uniformly random bytes
+    Random,
+    /// English text
+    Text,
+    /// All zeros
+    Zeros,
+}
+
+/// Generate 'len' bytes of the given corpus, always the same
+pub fn corpus(kind: Corpus, len: usize) -> Vec<u8> {
+    match kind {
+        Corpus::Random => {
+            let mut seed = 1u32;
+            (0..len).map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            }).collect()
+        },
+        Corpus::Text => TEXT.iter().cloned().cycle().take(len).collect(),
+        Corpus::Zeros => vec![0; len],
+    }
+}
+
+/// Convert a measurement into megabytes per second
+pub fn mb_per_sec(bytes: u64, nanos: u64) -> f64 {
+    (bytes as f64) * 1.0e3 / (nanos.max(1) as f64)
+}
+
+fn elapsed_nanos(start: Instant) -> u64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64
+}
+
+fn encode(input: &[u8]) -> Vec<u8> {
+    let mut e = table::ByteEncoder::new(Vec::with_capacity(input.len()));
+    e.write_all(input).unwrap();
+    let (output, result) = e.finish();
+    result.unwrap();
+    output
+}
+
+/// Measure `ByteEncoder` on the input
+pub fn byte_encode(input: &[u8]) -> (u64, u64) {
+    let start = Instant::now();
+    encode(input);
+    (input.len() as u64, elapsed_nanos(start))
+}
+
+/// Measure `ByteDecoder` on the input, encoded beforehand
+pub fn byte_decode(input: &[u8]) -> (u64, u64) {
+    let encoded = encode(input);
+    let mut output = Vec::with_capacity(input.len());
+    let start = Instant::now();
+    table::ByteDecoder::new(&encoded[..]).read_to_end(&mut output).unwrap();
+    let nanos = elapsed_nanos(start);
+    assert_eq!(&output[..], input);
+    (input.len() as u64, nanos)
+}
+
+/// Measure the raw `RangeEncoder` on the input, coding it
+/// with a static model of its own histogram, discarding the output
+pub fn range_encode(input: &[u8]) -> (u64, u64) {
+    let mut counts = [0u32; 256];
+    for &byte in input.iter() {
+        counts[byte as usize] += 1;
+    }
+    counts[0] += 1;
+    let mut model = table::StaticModel::from_histogram(&counts);
+    let mut re = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    let mut num_symbols = 0;
+    let start = Instant::now();
+    for &byte in input.iter() {
+        num_symbols += super::encode_into(byte as usize, &mut model, &mut re, |_| ()).unwrap();
+    }
+    num_symbols += re.get_code_tail(|_| ());
+    let nanos = elapsed_nanos(start);
+    debug!("Range coded {} bytes into {} symbols", input.len(), num_symbols);
+    (input.len() as u64, nanos)
+}
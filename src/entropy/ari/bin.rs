@@ -3,6 +3,14 @@
 Binary models for the arithmetic coder.
 The simplicity of the domain allows for normalized updates in place using bit shifts.
 
+The adaptive model keeps the frequency `p` of zero under a constant total,
+starting at half of the total unless a custom percentage is given.
+Every coded bit moves it towards the target (total for zero, 0 for one)
+by `p += (target - p) >> rate`: small rates adapt fast to bursty data,
+large rates converge closer on stationary data.
+
+The `Apm` stage refines the probability of a primary model in a given context.
+
 # Links
 
 # Example
@@ -13,7 +21,8 @@ The simplicity of the domain allows for normalized updates in place using bit sh
 
 use super::Border;
 
-/// A binary value frequency model
+/// A binary value frequency model, adapting the probability of zero
+/// by the rule `p += (target - p) >> rate`
 pub struct Model {
     /// frequency of bit 0
     zero: Border,
@@ -33,6 +42,17 @@ impl Model {
         }
     }
 
+    /// Create a new flat instance under the default threshold,
+    /// adapting with the given shift, which has to be in `[1, 14)`:
+    /// zero would move all the probability to one bit value,
+    /// and the shifts past the threshold bits would never adapt
+    pub fn with_rate(shift: usize) -> Model {
+        let max_shift = super::RANGE_DEFAULT_THRESHOLD.trailing_zeros() as usize;
+        assert!(1 <= shift && shift < max_shift,
+            "The shift {} is out of [1, {})", shift, max_shift);
+        Model::new_flat(super::RANGE_DEFAULT_THRESHOLD, shift as Border)
+    }
+
     /// Create a new instance with a given percentage for zeroes
     pub fn new_custom(zero_percent: u8, threshold: Border, rate: Border) -> Model {
         assert!(threshold >= 100);
@@ -165,3 +185,113 @@ impl<'a> super::Model<bool> for SumProxy<'a> {
             (self.w_shift as usize)
     }
 }
+
+
+/// The total of the probabilities refined by `Apm`
+pub const APM_TOTAL: Border = 1<<12;
+/// Number of interpolation buckets per context, plus one for the upper end
+const APM_BUCKETS: usize = 33;
+
+/// Adaptive probability map, also known as secondary symbol estimation (SSE).
+/// Refines the probability of zero, given by a primary model, in a context.
+/// Each context keeps a map of the input probabilities quantized into buckets,
+/// starting as the identity. The output is interpolated between the two
+/// nearest buckets, which are adapted towards the bit once it's known.
+///
+/// The map is a model on its own, coding with the last refined probability,
+/// so the encoder and the decoder adapt it in `update` identically.
+pub struct Apm {
+    /// refined probabilities of zero: context * bucket -> probability
+    table: Vec<u16>,
+    /// learning rate, see `Model`
+    pub rate: Border,
+    /// index of the lower bucket used by the last refinement
+    index: usize,
+    /// the last refined probability of zero
+    zero: Border,
+}
+
+impl Apm {
+    /// Create a new identity map for a given number of contexts
+    pub fn new(num_contexts: usize, rate: Border) -> Apm {
+        let step = APM_TOTAL as usize / (APM_BUCKETS - 1);
+        let bucket: Vec<u16> = (0..APM_BUCKETS).map(|i|
+            (i * step).max(1).min(APM_TOTAL as usize - 1) as u16
+            ).collect();
+        let mut table = Vec::with_capacity(num_contexts * APM_BUCKETS);
+        for _ in 0..num_contexts {
+            table.extend_from_slice(&bucket);
+        }
+        Apm {
+            table: table,
+            rate: rate,
+            index: 0,
+            zero: APM_TOTAL>>1,
+        }
+    }
+
+    /// Refine the probability of zero 'p', given under `APM_TOTAL`, in a context.
+    /// The result is never 0 or `APM_TOTAL`, so both bits remain codable.
+    pub fn refine(&mut self, p: u16, ctx: usize) -> u16 {
+        assert!((p as Border) <= APM_TOTAL, "Probability {} is out of range", p);
+        let scaled = p as usize * (APM_BUCKETS - 1);
+        let total = APM_TOTAL as usize;
+        let (bucket, weight) = match scaled / total {
+            b if b == APM_BUCKETS - 1 => (b - 1, total),
+            b => (b, scaled % total),
+        };
+        self.index = ctx * APM_BUCKETS + bucket;
+        let (lo, hi) = (self.table[self.index] as usize, self.table[self.index+1] as usize);
+        let zero = (lo * (total - weight) + hi * weight) / total;
+        self.zero = zero.max(1).min(total - 1) as Border;
+        self.zero as u16
+    }
+
+    /// Return the last refined probability of zero
+    pub fn get_probability_zero(&self) -> Border {
+        self.zero
+    }
+
+    /// Adapt both buckets of the last refinement in favor of given 'value'
+    pub fn update(&mut self, value: bool) {
+        let rate = self.rate as usize;
+        for entry in self.table[self.index .. self.index+2].iter_mut() {
+            let p = *entry as Border;
+            let p = if value {
+                p - (p >> rate)
+            }else {
+                p + ((APM_TOTAL - p) >> rate)
+            };
+            *entry = p.max(1).min(APM_TOTAL - 1) as u16;
+        }
+    }
+}
+
+impl super::Model<bool> for Apm {
+    fn get_range(&self, value: bool) -> (Border,Border) {
+        if value {
+            (self.zero, APM_TOTAL)
+        }else {
+            (0, self.zero)
+        }
+    }
+
+    fn find_value(&self, offset: Border) -> (bool,Border,Border) {
+        assert!(offset < APM_TOTAL,
+            "Invalid frequency offset {} requested under total {}",
+            offset, APM_TOTAL);
+        if offset < self.zero {
+            (false, 0, self.zero)
+        }else {
+            (true, self.zero, APM_TOTAL)
+        }
+    }
+
+    fn get_denominator(&self) -> Border {
+        APM_TOTAL
+    }
+
+    fn update(&mut self, value: bool) {
+        Apm::update(self, value)
+    }
+}
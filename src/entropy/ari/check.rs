@@ -0,0 +1,119 @@
+/*!
+
+Randomized round-trip checks of the arithmetic coder, usable as a correctness
+oracle by the crate tests and downstream code alike. Requires `io` feature.
+
+Everything is derived from the seed: the model, the input and the coder
+configuration. A failed check panics with the seed in the message, and calling
+`roundtrip_random` with the same arguments reproduces the failure exactly.
+
+# Example
+
+```rust
+use compress::entropy::ari::check;
+
+for seed in 0..4 {
+    check::roundtrip_random(seed, 1000, 3);
+}
+```
+
+*/
+
+use super::{Border, Decoder, Encoder, Model, RangeEncoder, RANGE_DEFAULT_THRESHOLD};
+use super::table::{FreqModel, Frequency, StaticModel};
+
+/// A small deterministic generator (SplitMix64)
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Return a number in `[0, n)`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Draw a value from the model distribution
+fn draw<M: Model<u32>>(rng: &mut Random, model: &M) -> u32 {
+    let offset = rng.below(model.get_denominator() as u64) as Border;
+    model.find_value(offset).0
+}
+
+/// Code the values with a fresh model and range, check the decoded ones
+fn roundtrip<M, F, G>(seed: u64, values: &[u32], new_model: F, new_range: G)
+    where M: Model<u32>, F: Fn() -> M, G: Fn() -> RangeEncoder
+{
+    let mut encoder = Encoder::with_range(Vec::new(), new_range());
+    encoder.encode_all(values, &mut new_model()).unwrap_or_else(|e|
+        panic!("Encoding failed for seed {}: {}", seed, e));
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    let mut decoder = Decoder::with_range(&encoded[..], new_range());
+    let decoded = decoder.decode_n(values.len(), &mut new_model()).unwrap_or_else(|e|
+        panic!("Decoding failed for seed {}: {}", seed, e));
+    assert!(decoded == values, "Decoded values differ for seed {}", seed);
+}
+
+/// Generate a random model over 'alphabet' values and 'len' values drawn from it,
+/// then check that they survive coding, panicking with the seed otherwise.
+/// Both a static model, with the total up to the default range threshold,
+/// and an adaptive one get tested, in either renormalization mode.
+pub fn roundtrip_random(seed: u64, len: usize, alphabet: usize) {
+    let threshold = RANGE_DEFAULT_THRESHOLD;
+    assert!(alphabet >= 1 && (alphabet as Border) < threshold,
+        "Alphabet of {} values doesn't fit the range threshold {}", alphabet, threshold);
+    let mut rng = Random(seed);
+    // skewed counts, all positive
+    let counts: Vec<u32> = (0..alphabet).map(|_| {
+        let x = rng.below(1000) as u32;
+        1 + x*x / (1 + rng.below(100) as u32)
+    }).collect();
+    // the total is often right at the threshold
+    let total = match rng.below(3) {
+        0 => threshold,
+        1 => threshold - rng.below(4) as Border,
+        _ => alphabet as Border + 1 + rng.below((threshold - alphabet as Border) as u64) as Border,
+    };
+    let carryless = rng.below(2) == 0;
+    let new_range = || if carryless {
+        RangeEncoder::new_carryless(threshold)
+    } else {
+        RangeEncoder::new(threshold)
+    };
+    debug!("Checking seed {}: total {}, carryless {}", seed, total, carryless);
+
+    let static_model = StaticModel::from_histogram_exact(&counts, total);
+    let values: Vec<u32> = (0..len).map(|_| draw(&mut rng, &StaticU32(&static_model))).collect();
+    roundtrip(seed, &values, || StaticU32(&static_model), &new_range);
+
+    let frequencies: Vec<Frequency> = counts.iter().map(|&c| c.min(Frequency::max_value() as u32) as Frequency).collect();
+    let increment = 1 + rng.below(32) as Frequency;
+    let adaptive = || FreqModel::with_frequencies(&frequencies, increment, threshold >> 1);
+    let values: Vec<u32> = (0..len).map(|_| rng.below(alphabet as u64) as u32).collect();
+    roundtrip(seed, &values, adaptive, &new_range);
+}
+
+/// A static model coding `u32` values
+struct StaticU32<'a>(&'a StaticModel);
+
+impl<'a> Model<u32> for StaticU32<'a> {
+    fn get_range(&self, value: u32) -> (Border,Border) {
+        self.0.get_range(value as usize)
+    }
+
+    fn find_value(&self, offset: Border) -> (u32,Border,Border) {
+        let (value, lo, hi) = self.0.find_value(offset);
+        (value as u32, lo, hi)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.0.get_denominator()
+    }
+}
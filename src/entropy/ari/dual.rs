@@ -0,0 +1,143 @@
+/*!
+
+Two interleaved streams of values, coded by one range coder with two models.
+Requires `io` feature, enabled by default.
+
+Any number of models can share an `Encoder`, as long as the decoder
+uses the same models in the same order: every value is decoded with the state
+the range and its model had when it was encoded. The coders here formalize
+the common case of two strictly alternating streams, such as the significance
+bits and the values of the residuals, checking the order with debug assertions.
+
+# Example
+
+```rust
+use compress::entropy::ari::{self, dual};
+
+let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+let mut e = dual::DualModelEncoder::new(Vec::new(),
+    ari::bin::Model::new_flat(threshold, 4),
+    ari::table::AdaptiveByteModel::new(16, threshold));
+e.encode_pair(true, b'a').unwrap();
+e.encode_pair(false, b'b').unwrap();
+let (encoded, _) = e.finish();
+
+let mut d = dual::DualModelDecoder::new(&encoded[..],
+    ari::bin::Model::new_flat(threshold, 4),
+    ari::table::AdaptiveByteModel::new(16, threshold));
+assert_eq!(d.decode_pair().unwrap(), (true, b'a'));
+assert_eq!(d.decode_pair().unwrap(), (false, b'b'));
+```
+
+*/
+
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use super::{Decoder, Encoder, Model};
+
+/// An encoder of two alternating streams, the first model coding
+/// the values of even positions, and the second one the odd
+pub struct DualModelEncoder<W, A, B> {
+    encoder: Encoder<W>,
+    /// the model of the first stream
+    pub first: A,
+    /// the model of the second stream
+    pub second: B,
+    /// number of values coded so far
+    count: u64,
+}
+
+impl<W: Write, A, B> DualModelEncoder<W, A, B> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W, first: A, second: B) -> DualModelEncoder<W, A, B> {
+        DualModelEncoder {
+            encoder: Encoder::new(w),
+            first: first,
+            second: second,
+            count: 0,
+        }
+    }
+
+    /// Encode a value of the first stream, which has to come next
+    pub fn encode_first<V: Copy + Display>(&mut self, value: V) -> io::Result<usize> where A: Model<V> {
+        debug_assert!(self.count % 2 == 0, "The value {} of the first stream is out of order", self.count);
+        let ret = try!(self.encoder.encode(value, &mut self.first));
+        self.count += 1;
+        Ok(ret)
+    }
+
+    /// Encode a value of the second stream, which has to come next
+    pub fn encode_second<V: Copy + Display>(&mut self, value: V) -> io::Result<usize> where B: Model<V> {
+        debug_assert!(self.count % 2 == 1, "The value {} of the second stream is out of order", self.count);
+        let ret = try!(self.encoder.encode(value, &mut self.second));
+        self.count += 1;
+        Ok(ret)
+    }
+
+    /// Encode a value of each stream
+    pub fn encode_pair<U, V>(&mut self, first: U, second: V) -> io::Result<usize>
+        where U: Copy + Display, V: Copy + Display, A: Model<U>, B: Model<V>
+    {
+        let num = try!(self.encode_first(first));
+        Ok(num + try!(self.encode_second(second)))
+    }
+
+    /// Finish encoding, the last pair has to be complete
+    pub fn finish(self) -> (W, io::Result<()>) {
+        debug_assert!(self.count % 2 == 0, "The last value of the second stream is missing");
+        self.encoder.finish()
+    }
+}
+
+
+/// A decoder of two alternating streams, see `DualModelEncoder`
+pub struct DualModelDecoder<R, A, B> {
+    decoder: Decoder<R>,
+    /// the model of the first stream
+    pub first: A,
+    /// the model of the second stream
+    pub second: B,
+    /// number of values decoded so far
+    count: u64,
+}
+
+impl<R: Read, A, B> DualModelDecoder<R, A, B> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R, first: A, second: B) -> DualModelDecoder<R, A, B> {
+        DualModelDecoder {
+            decoder: Decoder::new(r),
+            first: first,
+            second: second,
+            count: 0,
+        }
+    }
+
+    /// Decode a value of the first stream, which has to come next
+    pub fn decode_first<V: Copy + Display>(&mut self) -> io::Result<V> where A: Model<V> {
+        debug_assert!(self.count % 2 == 0, "The value {} of the first stream is out of order", self.count);
+        let value = try!(self.decoder.decode(&mut self.first));
+        self.count += 1;
+        Ok(value)
+    }
+
+    /// Decode a value of the second stream, which has to come next
+    pub fn decode_second<V: Copy + Display>(&mut self) -> io::Result<V> where B: Model<V> {
+        debug_assert!(self.count % 2 == 1, "The value {} of the second stream is out of order", self.count);
+        let value = try!(self.decoder.decode(&mut self.second));
+        self.count += 1;
+        Ok(value)
+    }
+
+    /// Decode a value of each stream
+    pub fn decode_pair<U, V>(&mut self) -> io::Result<(U, V)>
+        where U: Copy + Display, V: Copy + Display, A: Model<U>, B: Model<V>
+    {
+        let first = try!(self.decode_first());
+        Ok((first, try!(self.decode_second())))
+    }
+
+    /// Finish decoding
+    pub fn finish(self) -> (R, io::Result<()>) {
+        self.decoder.finish()
+    }
+}
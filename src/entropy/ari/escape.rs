@@ -0,0 +1,199 @@
+/*!
+
+Escape modelling for the arithmetic coder.
+A model reserves a part of its denominator for the escape code, signalling
+that the value hasn't been seen yet and has to be coded by a fallback model.
+This is the basic building block of PPM-style coders.
+
+# Links
+
+http://en.wikipedia.org/wiki/Prediction_by_partial_matching
+
+# Example
+
+```rust
+use compress::entropy::ari;
+use compress::entropy::ari::escape;
+
+let text = b"abracadabra";
+let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+// Encode some text, falling back to a flat table for the new symbols
+let mut model = escape::EscapeModel::new(256, 16, threshold);
+let mut literal = ari::table::Model::new_flat(256, threshold);
+let mut e = ari::Encoder::new(Vec::new());
+for &byte in text.iter() {
+    escape::encode_with_fallback(&mut e, byte as usize, &mut model, &mut literal).unwrap();
+}
+let (encoded, _) = e.finish();
+
+// Decode the encoded text
+let mut model = escape::EscapeModel::new(256, 16, threshold);
+let mut d = ari::Decoder::new(&encoded[..]);
+for &byte in text.iter() {
+    let value = escape::decode_with_fallback(&mut d, &mut model, &mut literal).unwrap();
+    assert_eq!(value, byte as usize);
+}
+```
+
+# Credit
+
+This is an original implementation.
+
+*/
+
+#[cfg(feature="io")]
+use std::io::{self, Read, Write};
+use super::{Border, Model};
+#[cfg(feature="io")]
+use super::{Decoder, Encoder, RangeBorder};
+use super::table::{self, Frequency};
+
+/// An adaptive frequency table that knows only the values seen so far,
+/// coding the rest as the escape value.
+#[derive(Clone)]
+pub struct EscapeModel {
+    /// frequency table with the escape in the last slot
+    table: table::Model,
+    /// frequency to add on each update
+    pub increment: Frequency,
+}
+
+impl EscapeModel {
+    /// Create a new model for values in [0,num_values) with no values seen yet
+    pub fn new(num_values: usize, increment: Frequency, threshold: Border) -> EscapeModel {
+        EscapeModel {
+            table: table::Model::new_custom(num_values+1, threshold,
+                |i| if i == num_values {1} else {0}),
+            increment: increment,
+        }
+    }
+
+    /// Return the escape value
+    pub fn get_escape(&self) -> usize {
+        self.table.get_frequencies().len() - 1
+    }
+
+    /// Check if the value can be coded without escaping
+    pub fn is_known(&self, value: usize) -> bool {
+        self.table.get_frequencies()[value] != 0
+    }
+
+    /// Return the frequency table, the escape being in the last slot
+    pub fn get_table(&self) -> &table::Model {
+        &self.table
+    }
+
+    /// Add the values learned by another model, along with their escapes
+    /// (see `table::Model::merge`)
+    pub fn merge(&mut self, other: &EscapeModel) {
+        self.table.merge(&other.table);
+    }
+}
+
+impl Model<usize> for EscapeModel {
+    fn get_range(&self, value: usize) -> (Border,Border) {
+        if self.is_known(value) {
+            self.table.get_range(value)
+        }else {
+            self.table.get_range(self.get_escape())
+        }
+    }
+
+    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
+        self.table.find_value(offset)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.table.get_denominator()
+    }
+
+    /// Learn the value, the escape itself is ignored.
+    /// Every newly seen value also makes the escape more probable.
+    fn update(&mut self, value: usize) {
+        let escape = self.get_escape();
+        if value == escape {
+            return
+        }
+        if !self.is_known(value) {
+            self.table.add_frequency(escape, self.increment as Border);
+        }
+        self.table.add_frequency(value, self.increment as Border);
+    }
+}
+
+#[cfg(feature="io")]
+/// Encode a value under the escape model,
+/// coding it again with the fallback model if it wasn't seen before
+pub fn encode_with_fallback<W, B, F>(encoder: &mut Encoder<W, B>, value: usize,
+                            model: &mut EscapeModel, fallback: &mut F) -> io::Result<()>
+    where W: Write, B: RangeBorder, F: Model<usize>
+{
+    if value >= model.get_escape() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "value is out of the alphabet"))
+    }
+    let known = model.is_known(value);
+    try!(encoder.encode(value, model));
+    if !known {
+        try!(encoder.encode(value, fallback));
+    }
+    Ok(())
+}
+
+#[cfg(feature="io")]
+/// Decode a value under the escape model,
+/// routing to the fallback model on the escape
+pub fn decode_with_fallback<R, B, F>(decoder: &mut Decoder<R, B>,
+                            model: &mut EscapeModel, fallback: &mut F) -> io::Result<usize>
+    where R: Read, B: RangeBorder, F: Model<usize>
+{
+    let value = try!(decoder.decode(model));
+    if value != model.get_escape() {
+        return Ok(value)
+    }
+    let value = try!(decoder.decode(fallback));
+    model.update(value);
+    Ok(value)
+}
+
+/// An escape model followed by the literals: the values never seen
+/// by the primary model are escaped and coded uniformly over the alphabet,
+/// so that any value is representable. The minimal tail of a PPM coder.
+#[derive(Clone)]
+pub struct LiteralFallbackModel {
+    /// the primary model, learning the coded values
+    pub primary: EscapeModel,
+    literal: table::UniformModel,
+}
+
+impl LiteralFallbackModel {
+    /// Create a new model for values in [0,num_values) with no values seen yet,
+    /// see `EscapeModel::new`. The alphabet has to fit the range threshold.
+    pub fn new(num_values: usize, increment: Frequency, threshold: Border) -> LiteralFallbackModel {
+        assert!(num_values as Border <= super::RANGE_DEFAULT_THRESHOLD,
+            "The alphabet of {} values is too large for the literals", num_values);
+        LiteralFallbackModel {
+            primary: EscapeModel::new(num_values, increment, threshold),
+            literal: table::UniformModel::new(num_values),
+        }
+    }
+
+    /// Return the number of values in the alphabet
+    pub fn get_num_values(&self) -> usize {
+        self.literal.get_num_values()
+    }
+
+    #[cfg(feature="io")]
+    /// Encode a value, escaping to the literal if it's new
+    pub fn encode<W: Write, B: RangeBorder>(&mut self, encoder: &mut Encoder<W, B>, value: usize) -> io::Result<()> {
+        if value >= self.get_num_values() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "value is out of the alphabet"))
+        }
+        encode_with_fallback(encoder, value, &mut self.primary, &mut self.literal)
+    }
+
+    #[cfg(feature="io")]
+    /// Decode a value, reading the literal after the escape
+    pub fn decode<R: Read, B: RangeBorder>(&mut self, decoder: &mut Decoder<R, B>) -> io::Result<usize> {
+        decode_with_fallback(decoder, &mut self.primary, &mut self.literal)
+    }
+}
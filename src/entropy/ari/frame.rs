@@ -0,0 +1,191 @@
+/*!
+
+A self-describing frame around the arithmetic byte coder.
+Requires `io` and `checksum` features, enabled by default.
+
+The frame starts with the magic bytes and the id of the model the payload
+is coded with. The payload is the output of `ByteEncoder`, closed with
+a padded tail, followed by the number of the coded bytes (8 bytes)
+and their CRC-32 (4 bytes), both little endian. Frames can be concatenated,
+each one is read to its end by a separate `FrameReader`.
+
+# Example
+
+```rust
+use std::io::{Read, Write};
+use compress::entropy::ari::frame;
+
+let text = b"some text";
+let mut e = frame::FrameEncoder::new(Vec::new());
+e.write_all(text).unwrap();
+let (encoded, result) = e.finish();
+result.unwrap();
+
+let mut d = frame::FrameReader::new(&encoded[..]);
+let mut decoded = Vec::new();
+d.read_to_end(&mut decoded).unwrap();
+assert_eq!(&decoded[..], &text[..]);
+assert_eq!(d.decoded_len(), text.len() as u64);
+```
+
+*/
+
+use std::io::{self, Read, Write};
+use checksum::crc32;
+use super::{ByteDecoder, ByteEncoder};
+
+/// Magic bytes opening a frame
+pub const MAGIC: [u8; 4] = [0x52, 0x43, 0x41, 0x46];
+/// Model id of the adaptive byte table of `ByteEncoder`
+pub const MODEL_ADAPTIVE_BYTES: u8 = 1;
+/// Size of the trailer: the length and the checksum
+const TRAILER_SIZE: usize = 12;
+
+/// An encoder of a single frame.
+/// Implements `Write`, the header goes out with the first bytes.
+pub struct FrameEncoder<W> {
+    encoder: ByteEncoder<W>,
+    checksum: crc32::State32,
+    length: u64,
+    wrote_header: bool,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W) -> FrameEncoder<W> {
+        FrameEncoder {
+            encoder: ByteEncoder::new(w),
+            checksum: crc32::State32::new(),
+            length: 0,
+            wrote_header: false,
+        }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.wrote_header {
+            let w = self.encoder.encoder.get_mut();
+            try!(w.write_all(&MAGIC));
+            try!(w.write_all(&[MODEL_ADAPTIVE_BYTES]));
+            self.wrote_header = true;
+        }
+        Ok(())
+    }
+
+    /// Close the payload and write the trailer
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let ret = self.write_header();
+        let (length, checksum) = (self.length, self.checksum.result());
+        let (mut w, r2) = self.encoder.finish_padded();
+        let ret = ret.and(r2).and_then(|_| {
+            let mut trailer = [0u8; TRAILER_SIZE];
+            for i in 0..8 {
+                trailer[i] = (length >> (8*i)) as u8;
+            }
+            for i in 0..4 {
+                trailer[8+i] = (checksum >> (8*i)) as u8;
+            }
+            try!(w.write_all(&trailer));
+            w.flush()
+        });
+        (w, ret)
+    }
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.write_header());
+        let amount = try!(self.encoder.write(buf));
+        self.checksum.feed(&buf[..amount]);
+        self.length += amount as u64;
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+
+/// A reader of a single frame.
+/// Implements `Read`, validating the header on the first read
+/// and the trailer at the end of the payload, before reporting the end of file.
+pub struct FrameReader<R> {
+    decoder: ByteDecoder<R>,
+    checksum: crc32::State32,
+    length: u64,
+    read_header: bool,
+    is_eof: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Create a reader on top of a given Reader
+    pub fn new(r: R) -> FrameReader<R> {
+        FrameReader {
+            decoder: ByteDecoder::new(r),
+            checksum: crc32::State32::new(),
+            length: 0,
+            read_header: false,
+            is_eof: false,
+        }
+    }
+
+    /// Return the number of bytes decoded so far,
+    /// which is the verified frame length once the end is reached
+    pub fn decoded_len(&self) -> u64 {
+        self.length
+    }
+
+    /// Return the underlying Reader, positioned after the frame
+    /// if it has been read to the end
+    pub fn into_inner(self) -> R {
+        self.decoder.decoder.into_inner()
+    }
+
+    fn validate_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; 5];
+        try!(self.decoder.decoder.get_mut().read_exact(&mut header));
+        if header[..4] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid frame magic"))
+        }
+        if header[4] != MODEL_ADAPTIVE_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown frame model"))
+        }
+        Ok(())
+    }
+
+    fn validate_trailer(&mut self) -> io::Result<()> {
+        let mut trailer = [0u8; TRAILER_SIZE];
+        // consume the padded tail of the payload
+        try!(self.decoder.decoder.resync_align());
+        try!(self.decoder.decoder.get_mut().read_exact(&mut trailer));
+        let length = (0..8).fold(0u64, |n, i| n | (trailer[i] as u64) << (8*i));
+        let checksum = (0..4).fold(0u32, |n, i| n | (trailer[8+i] as u32) << (8*i));
+        if length != self.length {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "frame length mismatch"))
+        }
+        if checksum != self.checksum.result() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "checksum mismatch"))
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for FrameReader<R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        if self.is_eof || dst.is_empty() {
+            return Ok(0)
+        }
+        if !self.read_header {
+            try!(self.validate_header());
+            self.read_header = true;
+        }
+        let amount = try!(self.decoder.read(dst));
+        if amount == 0 {
+            try!(self.validate_trailer());
+            self.is_eof = true;
+        }
+        self.checksum.feed(&dst[..amount]);
+        self.length += amount as u64;
+        Ok(amount)
+    }
+}
@@ -0,0 +1,137 @@
+/*!
+
+Static mixing of several models into one.
+Each model predicts the probability of every symbol, and the mix is
+their weighted average, spread over a common denominator.
+
+# Normalization
+
+For the weights `w_i` summing up to `W`, and the model `i` giving the symbol `v`
+an interval `[lo_i, hi_i)` of the total `t_i`, the mixed frequency is
+
+```text
+f(v) = 1 + sum_i floor(w_i * (hi_i - lo_i) * S / (t_i * W))
+```
+
+where `S = MIX_TOTAL - 256` is the share of the denominator left after giving
+each symbol the frequency of 1, so that no symbol becomes unencodable.
+Every term is computed with integers, so the encoder and the decoder
+get the same table as long as their models are in the same state.
+The floors only lose some precision, and the sum never exceeds `MIX_TOTAL`.
+
+The table is rebuilt on each `update`, which updates every inner model.
+
+# Example
+
+```rust
+use compress::entropy::ari;
+use compress::entropy::ari::mix::MixModel;
+use compress::entropy::ari::table::{AdaptiveByteModel, Order1Model};
+
+fn new_model() -> MixModel {
+    let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+    MixModel::new(vec![
+        Box::new(AdaptiveByteModel::new(16, threshold)),
+        Box::new(Order1Model::new(16, threshold)),
+    ], vec![1, 1])
+}
+
+let text = b"some text";
+let encoded = ari::encode_bytes(text, &mut new_model()).unwrap();
+let decoded = ari::decode_bytes(&encoded, &mut new_model(), text.len()).unwrap();
+assert_eq!(&decoded[..], &text[..]);
+```
+
+# Credit
+
+This is an original implementation.
+
+*/
+
+use super::{Border, Model, Symbol, RANGE_DEFAULT_THRESHOLD, SYMBOL_TOTAL};
+
+/// The denominator of the mixed table
+pub const MIX_TOTAL: Border = RANGE_DEFAULT_THRESHOLD;
+
+/// A weighted mix of byte models.
+/// The weights are static, only their ratio matters.
+pub struct MixModel {
+    /// inner models
+    models: Vec<Box<dyn Model<Symbol>>>,
+    /// weight of each model
+    weights: Vec<Border>,
+    /// mixed cumulative frequencies: symbol -> lower border, plus the total
+    cumulative: Vec<Border>,
+}
+
+impl MixModel {
+    /// Create a new mix of the models with given weights
+    pub fn new(models: Vec<Box<dyn Model<Symbol>>>, weights: Vec<Border>) -> MixModel {
+        assert!(!models.is_empty(), "Nothing to mix");
+        assert_eq!(models.len(), weights.len());
+        assert!(weights.iter().any(|&w| w != 0), "All the weights are zero");
+        let mut mix = MixModel {
+            models: models,
+            weights: weights,
+            cumulative: Vec::with_capacity(SYMBOL_TOTAL + 1),
+        };
+        mix.build_table();
+        mix
+    }
+
+    /// Return the weights of the inner models
+    pub fn get_weights(&self) -> &[Border] {
+        &self.weights
+    }
+
+    /// Mix the current predictions of the models into the cumulative table
+    fn build_table(&mut self) {
+        let weight_total = self.weights.iter().fold(0u64, |s, &w| s + w as u64);
+        let share = (MIX_TOTAL as u64) - (SYMBOL_TOTAL as u64);
+        let totals: Vec<u64> = self.models.iter()
+            .map(|m| m.get_denominator() as u64 * weight_total).collect();
+        self.cumulative.truncate(0);
+        let mut lo = 0;
+        for value in 0..SYMBOL_TOTAL {
+            self.cumulative.push(lo);
+            let mut freq = 1;
+            for ((model, &weight), &total) in self.models.iter().zip(self.weights.iter()).zip(totals.iter()) {
+                let (from, to) = model.get_range(value as Symbol);
+                freq += (weight as u64 * (to - from) as u64 * share / total) as Border;
+            }
+            lo += freq;
+        }
+        debug_assert!(lo <= MIX_TOTAL);
+        self.cumulative.push(lo);
+    }
+}
+
+impl Model<Symbol> for MixModel {
+    fn get_range(&self, value: Symbol) -> (Border,Border) {
+        let value = value as usize;
+        (self.cumulative[value], self.cumulative[value+1])
+    }
+
+    fn find_value(&self, offset: Border) -> (Symbol,Border,Border) {
+        assert!(offset < self.get_denominator(),
+            "Invalid frequency offset {} requested under total {}",
+            offset, self.get_denominator());
+        // the last value with the lower border not exceeding the offset
+        let value = match self.cumulative.binary_search(&offset) {
+            Ok(v) => v,
+            Err(v) => v - 1,
+        };
+        (value as Symbol, self.cumulative[value], self.cumulative[value+1])
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.cumulative[SYMBOL_TOTAL]
+    }
+
+    fn update(&mut self, value: Symbol) {
+        for model in self.models.iter_mut() {
+            model.update(value);
+        }
+        self.build_table();
+    }
+}
@@ -3,6 +3,10 @@
 Arithmetic encoder/decoder using the Range encoder underneath. Requires `entropy` feature, enabled by default
 Can be used in a general case of entropy coding stage. Supposed to be fast.
 
+The stream helpers (`Encoder`, `Decoder` and the byte coders) require `io` feature,
+enabled by default. Without it, the module provides the primitive `RangeEncoder`
+and the models only, passing the code symbols through closures (see `encode_into`).
+
 # Links
 
 http://en.wikipedia.org/wiki/Arithmetic_coding
@@ -10,21 +14,40 @@ http://en.wikipedia.org/wiki/Range_encoding
 
 # Example
 ```rust
-# #![allow(unused_must_use)]
-use std::io::{BufWriter, BufReader, Read, Write};
+use std::io::{self, BufWriter, BufReader, Read, Write};
 use compress::entropy::ari;
 
+# fn run() -> io::Result<()> {
 // Encode some text
 let text = "some text";
 let mut e = ari::ByteEncoder::new(BufWriter::new(Vec::new()));
-e.write_all(text.as_bytes()).unwrap();
-let (encoded, _) = e.finish();
-let inner = encoded.into_inner().unwrap();
+try!(e.write_all(text.as_bytes()));
+let (encoded, result) = e.finish();
+try!(result);
+let inner = try!(encoded.into_inner());
 
 // Decode the encoded text
 let mut d = ari::ByteDecoder::new(BufReader::new(&inner[..]));
 let mut decoded = Vec::new();
-d.read_to_end(&mut decoded).unwrap();
+try!(d.read_to_end(&mut decoded));
+# assert_eq!(&decoded[..], text.as_bytes());
+# Ok(())
+# }
+# run().unwrap();
+```
+
+The same with plain slices, using a custom model:
+```rust
+use compress::entropy::ari;
+
+let text = "some text";
+let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+let encoded = ari::encode_bytes(text.as_bytes(), &mut model).unwrap();
+
+let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+let decoded = ari::decode_bytes(&encoded, &mut model, text.len()).unwrap();
+# assert_eq!(&decoded[..], text.as_bytes());
 ```
 # Credit
 
@@ -34,114 +57,472 @@ This is an original implementation.
 
 #![allow(missing_docs)]
 
-use std::fmt::Display;
+extern crate num;
+
+use std::error;
+use std::fmt::{self, Debug, Display};
+#[cfg(feature="io")]
+use std::collections::VecDeque;
+#[cfg(feature="io")]
+use std::marker::PhantomData;
+#[cfg(feature="io")]
 use std::io::{self, Read, Write};
 
-use super::super::byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
-use super::super::byteorder_err_to_io;
+use self::num::traits::{PrimInt, WrappingAdd, WrappingSub};
 
+#[cfg(feature="io")]
 pub use self::table::{ByteDecoder, ByteEncoder};
 
+pub mod alphabet;
 pub mod apm;
+#[cfg(feature="bench")]
+pub mod bench;
 pub mod bin;
+#[cfg(feature="io")]
+pub mod check;
+#[cfg(feature="io")]
+pub mod dual;
+pub mod escape;
+#[cfg(all(feature="io", feature="checksum"))]
+pub mod frame;
+pub mod mix;
+#[cfg(feature="io")]
+pub mod preset;
 pub mod table;
-#[cfg(test)]
+#[cfg(all(test, feature="io"))]
 mod test;
 
 pub type Symbol = u8;
 const SYMBOL_BITS: usize = 8;
 const SYMBOL_TOTAL: usize = 1<<SYMBOL_BITS;
+/// Number of bits in a code symbol of the nibble mode
+const NIBBLE_BITS: usize = 4;
 
 pub type Border = u32;
-const BORDER_BYTES: usize = 4;
-const BORDER_BITS: usize = BORDER_BYTES * 8;
-const BORDER_EXCESS: usize = BORDER_BITS-SYMBOL_BITS;
-const BORDER_SYMBOL_MASK: u32 = ((SYMBOL_TOTAL-1) << BORDER_EXCESS) as u32;
+/// The all-ones value of `Border`, the initial upper end of the range
+pub const BORDER_MAX: Border = Border::MAX;
+#[cfg(feature="io")]
+const BORDER_BYTES_MAX: usize = 8;
+#[cfg(feature="io")]
+/// Number of symbols gathered by `Encoder::encode_iter` before writing them out
+const ITER_CHUNK_SIZE: usize = 1<<12;
+#[cfg(feature="io")]
+/// Number of interrupted reads in a row after which `Decoder` gives up
+const READ_RETRIES_MAX: usize = 64;
 
 pub const RANGE_DEFAULT_THRESHOLD: Border = 1<<14;
+/// Ratio between the range threshold and the largest model total
+const THRESHOLD_FACTOR: Border = 16;
+
 
+/// Range coding error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The current range width (second) is too narrow to split into the given total (first)
+    RangeTooNarrow(Border, Border),
+    /// The interval [from,to) is empty or doesn't fit into the total
+    BadInterval(Border, Border, Border),
+    /// The code doesn't belong to the current range
+    CodeOutOfRange,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::RangeTooNarrow(total, width) =>
+                write!(f, "total {} exceeds the current range width {}", total, width),
+            Error::BadInterval(from, to, total) =>
+                write!(f, "bad interval [{}-{}) for the total {}", from, to, total),
+            Error::CodeOutOfRange =>
+                write!(f, "code is out of the current range"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(feature="io")]
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+
+/// An unsigned integer type holding the range coder state.
+/// Wider types leave more precision after the division by the model total,
+/// allowing for larger denominators.
+pub trait RangeBorder: PrimInt + WrappingAdd + WrappingSub + Display + Debug {
+    /// Number of bytes in the code word
+    const BYTES: usize;
+    /// Convert a model border into the coder width
+    fn from_border(b: Border) -> Self;
+    /// Convert a value known to fit into a model border
+    fn to_border(self) -> Border;
+    /// Extract the lowest symbol
+    fn to_symbol(self) -> Symbol;
+}
+
+impl RangeBorder for u32 {
+    const BYTES: usize = 4;
+    #[inline]
+    fn from_border(b: Border) -> u32 { b }
+    #[inline]
+    fn to_border(self) -> Border { self }
+    #[inline]
+    fn to_symbol(self) -> Symbol { self as Symbol }
+}
+
+impl RangeBorder for u64 {
+    const BYTES: usize = 8;
+    #[inline]
+    fn from_border(b: Border) -> u64 { b as u64 }
+    #[inline]
+    fn to_border(self) -> Border { self as Border }
+    #[inline]
+    fn to_symbol(self) -> Symbol { self as Symbol }
+}
+
+#[inline]
+fn border_bits<B: RangeBorder>() -> usize {
+    B::BYTES * 8
+}
+
+#[inline]
+fn border_excess<B: RangeBorder>() -> usize {
+    border_bits::<B>() - SYMBOL_BITS
+}
+
+
+/// A snapshot of the range encoder state,
+/// taken by `RangeEncoder::checkpoint`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeState<B = Border> {
+    low: B,
+    hai: B,
+    cache: Option<Symbol>,
+    num_pending: usize,
+}
 
 /// Range Encoder basic primitive
 /// Gets probability ranges on the input, produces whole bytes of code on the output,
 /// where the code is an arbitrary fixed-ppoint value inside the resulting probability range.
-pub struct RangeEncoder {
-    low: Border,
-    hai: Border,
+/// The state is kept in `B`, which is `u32` by default.
+///
+/// There are two modes of renormalization. The default one cuts the range
+/// on a symbol boundary whenever it gets too narrow, losing some bits on the way.
+/// The carryless mode (see `new_carryless`) never cuts and propagates the carry
+/// into the pending output instead.
+pub struct RangeEncoder<B = Border> {
+    low: B,
+    hai: B,
     /// The minimum distance between low and hai to keep at all times,
     /// has to be at least the largest incoming 'total',
     /// and optimally many times larger
-    pub threshold: Border,
-    /// Tuning parameters
+    pub threshold: B,
+    /// Carry propagation mode
+    carryless: bool,
+    /// Number of bits in a code symbol
+    symbol_bits: usize,
+    /// The last shifted symbol, still subject to a carry
+    cache: Option<Symbol>,
+    /// Number of 0xFF symbols following the cache
+    num_pending: usize,
+    /// Tuning parameters, only counted when tracking is enabled
+    tracking: bool,
+    bits_ideal: f32,
     bits_lost_on_threshold_cut: f32,
     bits_lost_on_division: f32,
 }
 
-impl RangeEncoder {
+impl<B: RangeBorder> RangeEncoder<B> {
     /// Create a new instance
     /// will keep the active range below 'max_range'
-    pub fn new(max_range: Border) -> RangeEncoder {
-        debug_assert!(max_range > (SYMBOL_TOTAL as Border));
+    pub fn new(max_range: B) -> RangeEncoder<B> {
+        debug_assert!(max_range > B::from_border(SYMBOL_TOTAL as Border));
         RangeEncoder {
-            low: 0,
-            hai: !0,
+            low: B::zero(),
+            hai: B::max_value(),
             threshold: max_range,
+            carryless: false,
+            symbol_bits: SYMBOL_BITS,
+            cache: None,
+            num_pending: 0,
+            tracking: cfg!(tune),
+            bits_ideal: 0.0,
             bits_lost_on_threshold_cut: 0.0,
             bits_lost_on_division: 0.0,
         }
     }
 
+    /// Create a new instance for models with denominators up to 'max_total'.
+    /// The threshold has to be at least the largest total, otherwise
+    /// the range may get too narrow to split. Every division by the total
+    /// loses the fraction of the range below it, so the threshold is
+    /// picked `THRESHOLD_FACTOR` times larger to keep the loss small.
+    pub fn with_model_bounds(max_total: Border) -> RangeEncoder<B> {
+        let threshold = B::from_border(max_total).checked_mul(&B::from_border(THRESHOLD_FACTOR));
+        match threshold {
+            Some(t) if t <= B::one() << border_excess::<B>() =>
+                RangeEncoder::new(t.max(B::from_border(RANGE_DEFAULT_THRESHOLD))),
+            _ => panic!("Model total {} is too large for the border width", max_total),
+        }
+    }
+
+    /// Create a new instance propagating the carry instead of cutting the range
+    /// the active range is kept above `1<<(BITS-8)`, which also has to be
+    /// larger than 'max_range'
+    pub fn new_carryless(max_range: B) -> RangeEncoder<B> {
+        debug_assert!(max_range <= B::one() << border_excess::<B>());
+        RangeEncoder {
+            carryless: true,
+            .. RangeEncoder::new(max_range)
+        }
+    }
+
+    /// Check if the carry propagation mode is used
+    pub fn is_carryless(&self) -> bool {
+        self.carryless
+    }
+
+    /// Set the number of bits in a code symbol, either 8 (bytes, the default)
+    /// or 4 (nibbles). The nibbles renormalize with a finer granularity and
+    /// may shorten the code tail by half a byte, passing the values up to 15
+    /// into 'fn_shift'. `Encoder` and `Decoder` pack them two per byte,
+    /// the high one first. Has to be set before coding, the same on both ends.
+    pub fn set_symbol_bits(&mut self, bits: usize) {
+        assert!(bits == SYMBOL_BITS || bits == NIBBLE_BITS,
+            "Code symbols of {} bits are not supported", bits);
+        self.symbol_bits = bits;
+    }
+
+    /// Return the number of bits in a code symbol
+    pub fn get_symbol_bits(&self) -> usize {
+        self.symbol_bits
+    }
+
+    /// Number of code symbols in the code word
+    fn word_symbols(&self) -> usize {
+        border_bits::<B>() / self.symbol_bits
+    }
+
+    /// Number of bits below the highest code symbol
+    fn excess(&self) -> usize {
+        border_bits::<B>() - self.symbol_bits
+    }
+
+    /// The code symbol with all bits set
+    fn symbol_max(&self) -> Symbol {
+        ((1usize << self.symbol_bits) - 1) as Symbol
+    }
+
     /// Reset the current range
     pub fn reset(&mut self) {
-        self.low = 0;
-        self.hai = !0;
+        self.low = B::zero();
+        self.hai = B::max_value();
+        self.cache = None;
+        self.num_pending = 0;
     }
 
-    #[cfg(tune)]
-    fn count_bits(range: Border, total: Border) -> f32 {
-        -((range as f32) / (total as f32)).log2()
+    /// Capture the current range state
+    pub fn checkpoint(&self) -> RangeState<B> {
+        RangeState {
+            low: self.low,
+            hai: self.hai,
+            cache: self.cache,
+            num_pending: self.num_pending,
+        }
     }
 
-    #[cfg(not(tune))]
-    fn count_bits(_range: Border, _total: Border) -> f32 {
-        0.0
+    /// Return to the captured range state
+    pub fn restore(&mut self, state: RangeState<B>) {
+        self.low = state.low;
+        self.hai = state.hai;
+        self.cache = state.cache;
+        self.num_pending = state.num_pending;
     }
 
-    /// Return the number of bits lost due to threshold cuts and integer operations
-    #[cfg(tune)]
+    /// Return the current interval as a `(low, hai)` pair, for diagnostics
+    pub fn bounds(&self) -> (B, B) {
+        (self.low, self.hai)
+    }
+
+    /// Return the width of the current range
+    pub fn range_width(&self) -> B {
+        self.hai.wrapping_sub(&self.low)
+    }
+
+    /// Return the largest total the next `process` can split the range into
+    /// without failing with `RangeTooNarrow`. Each unit of the total gets
+    /// at least one unit of the range, so precision drops as it gets closer.
+    pub fn max_total(&self) -> Border {
+        let max = B::from_border(Border::max_value());
+        self.range_width().min(max).to_border()
+    }
+
+    /// Enable or disable tracking of the coding losses,
+    /// it is enabled by default under `cfg(tune)` only
+    pub fn set_tracking(&mut self, enable: bool) {
+        self.tracking = enable;
+    }
+
+    fn count_bits(range: B, total: B) -> f32 {
+        -(range.to_f32().unwrap() / total.to_f32().unwrap()).log2()
+    }
+
+    /// Return the number of bits lost due to threshold cuts and integer operations,
+    /// zero unless tracking is enabled
     pub fn get_bits_lost(&self) -> (f32, f32) {
         (self.bits_lost_on_threshold_cut, self.bits_lost_on_division)
     }
 
+    /// Return the number of bits the processed intervals take ideally,
+    /// by their probabilities, zero unless tracking is enabled
+    pub fn get_bits_ideal(&self) -> f32 {
+        self.bits_ideal
+    }
+
     /// Process a given interval [from/total,to/total) into the current range
-    /// write into the output slice, and return the number of symbols produced
-    pub fn process(&mut self, total: Border, from: Border, to: Border, output: &mut [Symbol]) -> usize {
-        debug_assert!(from<to && to<=total);
-        let old_range = self.hai - self.low;
-        let range = old_range / total;
-        debug_assert!(range>0, "RangeCoder range is too narrow [{}-{}) for the total {}",
-            self.low, self.hai, total);
+    /// pass the produced symbols into 'fn_shift', and return the number of shifts done.
+    ///
+    /// The interval is scaled by `range = (hai-low)/total`, and the products
+    /// `range*from` and `range*to` stay within `hai-low` as long as
+    /// `from < to <= total`, which is checked upfront. Renormalization keeps
+    /// `hai-low` above the `threshold`, so any `total` up to the threshold
+    /// gets a non-zero `range`, while larger ones are refused as `RangeTooNarrow`
+    /// once the range gets narrow. With debug assertions, the products and
+    /// the new borders are computed with overflow checks, panicking
+    /// with the offending numbers.
+    pub fn process<F: FnMut(Symbol)>(&mut self, total: Border, from: Border, to: Border, mut fn_shift: F)
+                   -> Result<usize, Error> {
+        if !(from<to && to<=total) {
+            return Err(Error::BadInterval(from, to, total))
+        }
+        let old_range = self.hai.wrapping_sub(&self.low);
+        let range = try!(self.split(total));
         debug!("\t\tProcessing [{}-{})/{} with range {}", from, to, total, range);
-        let mut lo = self.low + range*from;
-        let mut hi = self.low + range*to;
-        self.bits_lost_on_division += RangeEncoder::count_bits(range*total, old_range);
+        let (total, from, to) = (B::from_border(total), B::from_border(from), B::from_border(to));
+        if self.tracking {
+            self.bits_ideal += RangeEncoder::count_bits(to-from, total);
+            self.bits_lost_on_division += RangeEncoder::count_bits(range*total, old_range);
+        }
+        Ok(if self.carryless {
+            let offset = RangeEncoder::scale(range, from, (from, to, total));
+            let width = RangeEncoder::scale(range, to-from, (from, to, total));
+            self.process_carry(offset, width, &mut fn_shift)
+        }else {
+            let lo = self.shift_by(RangeEncoder::scale(range, from, (from, to, total)), (from, to, total));
+            let hi = self.shift_by(RangeEncoder::scale(range, to, (from, to, total)), (from, to, total));
+            self.process_cut(lo, hi, &mut fn_shift)
+        })
+    }
+
+    /// Multiply the range by a model border,
+    /// checking for an overflow with debug assertions
+    #[inline]
+    fn scale(range: B, border: B, (from, to, total): (B, B, B)) -> B {
+        if cfg!(debug_assertions) {
+            match range.checked_mul(&border) {
+                Some(product) => product,
+                None => panic!("Range {} times the border {} overflows, interval [{}-{}) of total {}",
+                    range, border, from, to, total),
+            }
+        }else {
+            range * border
+        }
+    }
+
+    /// Offset 'low' by a scaled border in the cut mode,
+    /// checking for an overflow with debug assertions
+    #[inline]
+    fn shift_by(&self, offset: B, (from, to, total): (B, B, B)) -> B {
+        if cfg!(debug_assertions) {
+            match self.low.checked_add(&offset) {
+                Some(border) => border,
+                None => panic!("Low {} plus the offset {} overflows, interval [{}-{}) of total {}",
+                    self.low, offset, from, to, total),
+            }
+        }else {
+            self.low + offset
+        }
+    }
+
+    /// Process a given interval like `process` does in the cut mode,
+    /// passing the produced symbols into 'fn_shift' as long as it accepts them.
+    /// Once it returns `false`, the symbol is kept and the renormalization
+    /// pauses, to be continued by `resume_chunked` after the sink is drained.
+    /// Return the number of shifts done. No interval can be processed,
+    /// nor the tail produced, while `has_pending_shifts` is true.
+    /// Panics in the carry mode, where a carry releases many symbols at once.
+    pub fn process_chunked<F: FnMut(Symbol) -> bool>(&mut self, total: Border, from: Border, to: Border,
+                           mut fn_shift: F) -> Result<usize, Error> {
+        assert!(!self.carryless, "Chunked processing requires the cut mode");
+        assert!(!self.has_pending_shifts(), "The previous renormalization is not complete");
+        if !(from<to && to<=total) {
+            return Err(Error::BadInterval(from, to, total))
+        }
+        let old_range = self.hai.wrapping_sub(&self.low);
+        let range = try!(self.split(total));
+        let (total, from, to) = (B::from_border(total), B::from_border(from), B::from_border(to));
+        if self.tracking {
+            self.bits_ideal += RangeEncoder::count_bits(to-from, total);
+            self.bits_lost_on_division += RangeEncoder::count_bits(range*total, old_range);
+        }
+        let lo = self.shift_by(RangeEncoder::scale(range, from, (from, to, total)), (from, to, total));
+        let hi = self.shift_by(RangeEncoder::scale(range, to, (from, to, total)), (from, to, total));
+        Ok(self.renormalize_cut(lo, hi, &mut fn_shift))
+    }
+
+    /// Continue the renormalization paused by `process_chunked`,
+    /// return the number of shifts done
+    pub fn resume_chunked<F: FnMut(Symbol) -> bool>(&mut self, mut fn_shift: F) -> usize {
+        let (lo, hi) = (self.low, self.hai);
+        self.renormalize_cut(lo, hi, &mut fn_shift)
+    }
+
+    /// Check if the renormalization paused by `process_chunked`
+    /// still has symbols to shift out
+    pub fn has_pending_shifts(&self) -> bool {
+        let mask = B::from_border(self.symbol_max() as Border) << self.excess();
+        !self.carryless && ((self.low ^ self.hai) & mask == B::zero() ||
+            self.hai - self.low <= self.threshold)
+    }
+
+    fn process_cut<F: FnMut(Symbol)>(&mut self, lo: B, hi: B, fn_shift: &mut F) -> usize {
+        self.renormalize_cut(lo, hi, &mut |symbol| {
+            fn_shift(symbol);
+            true
+        })
+    }
+
+    /// Shift the symbols out of [lo,hi) until the range is normalized
+    /// or 'fn_shift' refuses a symbol
+    fn renormalize_cut<F: FnMut(Symbol) -> bool>(&mut self, mut lo: B, mut hi: B, fn_shift: &mut F) -> usize {
+        let excess = self.excess();
+        let mask = B::from_border(self.symbol_max() as Border) << excess;
+        let bits = self.symbol_bits;
         let mut num_shift = 0;
         loop {
-            if (lo^hi) & BORDER_SYMBOL_MASK != 0 {
+            if (lo^hi) & mask != B::zero() {
                 if hi-lo > self.threshold {
                     break
                 }
                 let old_range = hi-lo;
-                let lim = hi & BORDER_SYMBOL_MASK;
+                let lim = hi & mask;
                 if hi-lim >= lim-lo {lo=lim}
-                else {hi=lim-1};
+                else {hi=lim-B::one()};
                 debug_assert!(lo < hi);
-                self.bits_lost_on_threshold_cut += RangeEncoder::count_bits(hi-lo, old_range);
+                if self.tracking {
+                    self.bits_lost_on_threshold_cut += RangeEncoder::count_bits(hi-lo, old_range);
+                }
             }
 
-            debug!("\t\tShifting on [{}-{}) to symbol {}", lo, hi, lo>>BORDER_EXCESS);
-            output[num_shift] = (lo>>BORDER_EXCESS) as Symbol;
+            debug!("\t\tShifting on [{}-{}) to symbol {}", lo, hi, lo>>excess);
+            if !fn_shift((lo>>excess).to_symbol()) {
+                break
+            }
             num_shift += 1;
-            lo<<=SYMBOL_BITS; hi<<=SYMBOL_BITS;
+            lo = lo<<bits; hi = hi<<bits;
             debug_assert!(lo < hi);
         }
         self.low = lo;
@@ -149,22 +530,135 @@ impl RangeEncoder {
         num_shift
     }
 
+    fn process_carry<F: FnMut(Symbol)>(&mut self, offset: B, mut width: B, fn_shift: &mut F) -> usize {
+        let top = B::one() << self.excess();
+        let lo = self.low.wrapping_add(&offset);
+        if lo < self.low {
+            self.propagate_carry(fn_shift);
+        }
+        self.low = lo;
+        let mut num_shift = 0;
+        while width < top {
+            self.shift_low(fn_shift);
+            width = width<<self.symbol_bits;
+            num_shift += 1;
+        }
+        self.hai = self.low.wrapping_add(&width);
+        num_shift
+    }
+
+    /// Move the highest symbol of 'low' into the cache,
+    /// releasing the previous cache if it can't be affected by a carry any more
+    fn shift_low<F: FnMut(Symbol)>(&mut self, fn_shift: &mut F) {
+        let symbol = (self.low >> self.excess()).to_symbol();
+        debug!("\t\tShifting low {} to symbol {}", self.low, symbol);
+        self.low = self.low << self.symbol_bits;
+        let symbol_max = self.symbol_max();
+        match self.cache {
+            Some(_) if symbol == symbol_max => {
+                self.num_pending += 1;
+            },
+            Some(cache) => {
+                fn_shift(cache);
+                for _ in 0..self.num_pending {
+                    fn_shift(symbol_max);
+                }
+                self.num_pending = 0;
+                self.cache = Some(symbol);
+            },
+            None => {
+                self.cache = Some(symbol);
+            },
+        }
+    }
+
+    /// Add the overflown bit to the cache and release the pending symbols
+    fn propagate_carry<F: FnMut(Symbol)>(&mut self, fn_shift: &mut F) {
+        debug!("\t\tPropagating carry over {} pending symbols", self.num_pending);
+        let cache = self.cache.take().expect("RangeCoder carry has nowhere to go");
+        fn_shift(cache + 1);
+        for _ in 0..self.num_pending {
+            fn_shift(0);
+        }
+        self.num_pending = 0;
+    }
+
+    /// Divide the current range by the total, the precondition shared
+    /// by `process` and `query`, so that encoding and decoding fail alike.
+    /// The total has to be positive and not exceed the range width.
+    fn split(&self, total: Border) -> Result<B, Error> {
+        let width = self.hai.wrapping_sub(&self.low);
+        if total == 0 {
+            return Err(Error::BadInterval(0, 0, 0))
+        }
+        let range = width / B::from_border(total);
+        if range == B::zero() {
+            debug!("\t\tRange [{}-{}) is too narrow for the total {}", self.low, self.hai, total);
+            // the width is below the total, so it fits the border
+            return Err(Error::RangeTooNarrow(total, width.to_border()))
+        }
+        Ok(range)
+    }
+
     /// Query the value encoded by 'code' in range [0,total)
-    pub fn query(&self, total: Border, code: Border) -> Border {
+    pub fn query(&self, total: Border, code: B) -> Result<Border, Error> {
         debug!("\t\tQuerying code {} of total {} under range [{}-{})",
             code, total, self.low, self.hai);
-        debug_assert!(self.low <= code && code < self.hai);
-        let range = (self.hai - self.low) / total;
-        (code - self.low) / range
+        if !self.carryless && !(self.low <= code && code < self.hai) {
+            return Err(Error::CodeOutOfRange)
+        }
+        let range = try!(self.split(total));
+        let offset = code.wrapping_sub(&self.low) / range;
+        if offset >= B::from_border(total) {
+            return Err(Error::CodeOutOfRange)
+        }
+        Ok(offset.to_border())
     }
 
     /// Get the code tail and close the range
-    /// used at the end of encoding
-    pub fn get_code_tail(&mut self) -> Border {
-        let tail = self.low;
-        self.low = 0;
-        self.hai = 0;
-        tail
+    /// used at the end of encoding. The tail is the shortest sequence of symbols
+    /// that keeps the code inside the range regardless of the following bytes.
+    /// Returns the number of tail symbols, which doesn't exceed the code word size
+    pub fn get_code_tail<F: FnMut(Symbol)>(&mut self, mut fn_shift: F) -> usize {
+        let width = self.hai.wrapping_sub(&self.low);
+        let bits = self.symbol_bits;
+        let mut num_tail = self.word_symbols();
+        let mut code = self.low;
+        for k in 1..self.word_symbols() {
+            let mask = (B::one() << (border_bits::<B>() - k*bits)) - B::one();
+            let v = self.low.wrapping_add(&mask) & !mask;
+            let delta = v.wrapping_sub(&self.low);
+            if width > delta && width - delta > mask && (self.carryless || v >= self.low) {
+                num_tail = k;
+                code = v;
+                break
+            }
+        }
+        debug!("\t\tCode tail {} of {} symbols for range [{}-{})",
+            code, num_tail, self.low, self.hai);
+        if self.carryless {
+            if code < self.low {
+                self.propagate_carry(&mut fn_shift);
+            }
+            self.low = code;
+            for _ in 0..num_tail {
+                self.shift_low(&mut fn_shift);
+            }
+            if let Some(cache) = self.cache.take() {
+                fn_shift(cache);
+            }
+            for _ in 0..self.num_pending {
+                fn_shift(self.symbol_max());
+            }
+        }else {
+            let excess = self.excess();
+            for i in 0..num_tail {
+                fn_shift((code >> (excess - i*bits)).to_symbol() & self.symbol_max());
+            }
+        }
+        self.reset();
+        self.hai = B::zero();
+        num_tail
     }
 }
 
@@ -178,111 +672,1241 @@ pub trait Model<V: Copy + Display> {
     fn find_value(&self, offset: Border) -> (V,Border,Border);
     /// Get the sum of all probabilities
     fn get_denominator(&self) -> Border;
+    /// Adapt to a value that has just been coded, does nothing by default
+    fn update(&mut self, _value: V) {}
+    /// Get the current probability of a value, without coding it
+    fn probability(&self, value: V) -> f64 {
+        let (lo, hi) = self.get_range(value);
+        (hi - lo) as f64 / self.get_denominator() as f64
+    }
+    /// Get the probability ranges of several values at once into 'out',
+    /// which has to be as long as 'values'. Loops over `get_range` by default,
+    /// the tables may override it to share the lookup work.
+    fn get_ranges(&self, values: &[V], out: &mut [(Border,Border)]) {
+        assert_eq!(values.len(), out.len());
+        for (range, &value) in out.iter_mut().zip(values.iter()) {
+            *range = self.get_range(value);
+        }
+    }
 
     /// Encode a value using a range encoder
-    /// return the number of symbols written
-    fn encode(&self, value: V, re: &mut RangeEncoder, out: &mut [Symbol]) -> usize {
+    /// return the number of shifts done
+    fn encode<B: RangeBorder, F: FnMut(Symbol)>(&self, value: V, re: &mut RangeEncoder<B>, fn_shift: F)
+              -> Result<usize, Error> where Self: Sized {
         let (lo, hi) = self.get_range(value);
         let total = self.get_denominator();
         debug!("\tEncoding value {} of range [{}-{}) with total {}", value, lo, hi, total);
-        re.process(total, lo, hi, out)
+        re.process(total, lo, hi, fn_shift)
     }
 
     /// Decode a value using given 'code' on the range encoder
     /// return a (value, num_symbols_to_shift) pair
-    fn decode(&self, code: Border, re: &mut RangeEncoder) -> (V, usize) {
+    fn decode<B: RangeBorder>(&self, code: B, re: &mut RangeEncoder<B>) -> Result<(V, usize), Error>
+              where Self: Sized {
         let total = self.get_denominator();
-        let offset = re.query(total, code);
+        let offset = try!(re.query(total, code));
         let (value, lo, hi) = self.find_value(offset);
         debug!("\tDecoding value {} of offset {} with total {}", value, offset, total);
-        let mut out = [0 as Symbol; BORDER_BYTES];
-        let shift = re.process(total, lo, hi, &mut out[..]);
-        debug_assert_eq!(if shift==0 {0} else {code>>(BORDER_BITS - shift*8)},
-            out[..shift].iter().fold(0 as Border, |u,&b| (u<<8)+(b as Border)));
-        (value, shift)
+        let mut check = B::zero();
+        let bits = re.get_symbol_bits();
+        let shift = try!(re.process(total, lo, hi, |s| check = (check<<bits) + B::from_border(s as Border)));
+        // shifted symbols must match the code, unless it was cut off the range
+        if !re.is_carryless() &&
+            check != if shift==0 {B::zero()} else {code>>(border_bits::<B>() - shift*bits)} {
+            return Err(Error::CodeOutOfRange)
+        }
+        Ok((value, shift))
     }
 }
 
+/// A boxed model, possibly a trait object picked at run time
+impl<V: Copy + Display, M: Model<V> + ?Sized> Model<V> for Box<M> {
+    fn get_range(&self, value: V) -> (Border,Border) {
+        (**self).get_range(value)
+    }
+    fn find_value(&self, offset: Border) -> (V,Border,Border) {
+        (**self).find_value(offset)
+    }
+    fn get_denominator(&self) -> Border {
+        (**self).get_denominator()
+    }
+    fn update(&mut self, value: V) {
+        (**self).update(value)
+    }
+    fn get_ranges(&self, values: &[V], out: &mut [(Border,Border)]) {
+        (**self).get_ranges(values, out)
+    }
+}
+
+/// A borrowed model, allowing to code with `&mut dyn Model`
+impl<'a, V: Copy + Display, M: Model<V> + ?Sized> Model<V> for &'a mut M {
+    fn get_range(&self, value: V) -> (Border,Border) {
+        (**self).get_range(value)
+    }
+    fn find_value(&self, offset: Border) -> (V,Border,Border) {
+        (**self).find_value(offset)
+    }
+    fn get_denominator(&self) -> Border {
+        (**self).get_denominator()
+    }
+    fn update(&mut self, value: V) {
+        (**self).update(value)
+    }
+    fn get_ranges(&self, values: &[V], out: &mut [(Border,Border)]) {
+        (**self).get_ranges(values, out)
+    }
+}
+
+/// Encode the value under the model and adapt it,
+/// passing the produced symbols straight into the 'sink', without any IO.
+/// Return the number of symbols produced.
+pub fn encode_into<V, M, B, F>(value: V, model: &mut M, re: &mut RangeEncoder<B>,
+                   sink: F) -> Result<usize, Error>
+    where V: Copy + Display, M: Model<V>, B: RangeBorder, F: FnMut(Symbol)
+{
+    let num = try!(model.encode(value, re, sink));
+    model.update(value);
+    Ok(num)
+}
+
+/// Encode the value under the model and adapt it,
+/// gathering the produced symbols into 'accum'
+pub fn encode<V, M, B>(value: V, model: &mut M, re: &mut RangeEncoder<B>,
+                 accum: &mut Vec<Symbol>) -> Result<usize, Error>
+    where V: Copy + Display, M: Model<V>, B: RangeBorder
+{
+    encode_into(value, model, re, |s| accum.push(s))
+}
+
+#[cfg(feature="io")]
+/// Encode the values with the model into a byte vector
+pub fn encode_bytes<V: Copy + Display, M: Model<V>>(values: &[V], model: &mut M) -> io::Result<Vec<u8>> {
+    let mut encoder = Encoder::new(Vec::new());
+    try!(encoder.encode_all(values, model));
+    let (output, result) = encoder.finish();
+    result.map(|_| output)
+}
+
+#[cfg(feature="io")]
+/// Decode 'count' values with the model from a byte slice
+pub fn decode_bytes<V: Copy + Display, M: Model<V>>(data: &[u8], model: &mut M, count: usize) -> io::Result<Vec<V>> {
+    Decoder::new(data).decode_n(count, model)
+}
 
+/// Estimate the number of bits needed to code the value under the model,
+/// ignoring the losses of the range encoder
+pub fn estimate_bits<V: Copy + Display, M: Model<V>>(value: V, model: &M) -> f64 {
+    let (lo, hi) = model.get_range(value);
+    -(((hi - lo) as f64) / (model.get_denominator() as f64)).log2()
+}
+
+/// Measure the coded size of the bytes under the model, adapting it on the way,
+/// without producing any output. The count includes the code tail and matches
+/// the length of the `Encoder` output closed by `finish`, so it's a cheap cost
+/// function for tuning a model.
+pub fn coded_len<M: Model<u8>>(data: &[u8], model: &mut M) -> Result<usize, Error> {
+    let mut range = RangeEncoder::new(RANGE_DEFAULT_THRESHOLD);
+    let mut count = 0;
+    for &byte in data.iter() {
+        try!(encode_into(byte, model, &mut range, |_| count += 1));
+    }
+    range.get_code_tail(|_| count += 1);
+    Ok(count)
+}
+
+
+#[cfg(feature="io")]
+/// A single precomputed interval [lo,hi) of the total,
+/// coded by `Encoder::encode_freq` and `Decoder::consume_freq`
+struct Interval {
+    total: Border,
+    lo: Border,
+    hi: Border,
+}
+
+#[cfg(feature="io")]
+impl Model<Border> for Interval {
+    fn get_range(&self, _value: Border) -> (Border,Border) {
+        (self.lo, self.hi)
+    }
+
+    fn find_value(&self, _offset: Border) -> (Border,Border,Border) {
+        (self.lo, self.lo, self.hi)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.total
+    }
+}
+
+#[cfg(feature="io")]
 /// An arithmetic encoder helper
-pub struct Encoder<W> {
+pub struct Encoder<W, B = Border> {
     stream: W,
-    range: RangeEncoder,
+    range: RangeEncoder<B>,
+    buffer: Vec<Symbol>,
+    written: u64,
+    /// the high nibble waiting for its pair in the nibble mode
+    nibble: Option<Symbol>,
+    /// set once the code tail is written by `finalize`
+    closed: bool,
+    /// the largest number of bytes allowed in the stream
+    limit: Option<u64>,
+    /// byte order of the code tail
+    endianness: Endianness,
+    /// the shadow decoder of `with_self_check`
+    check: Option<SelfCheck<B>>,
+}
+
+#[cfg(feature="io")]
+/// A decoder of the intervals coded by `Encoder`, fed by its symbols
+struct SelfCheck<B> {
+    range: RangeEncoder<B>,
+    code: B,
+    /// number of code symbols to read before decoding
+    symbols_pending: usize,
+    /// the produced symbols not read yet
+    symbols: VecDeque<Symbol>,
+    /// the coded intervals not decoded yet, as `(total, lo, hi)`
+    intervals: VecDeque<(Border, Border, Border)>,
+}
+
+#[cfg(feature="io")]
+impl<B: RangeBorder> SelfCheck<B> {
+    fn new(encoder: &RangeEncoder<B>) -> SelfCheck<B> {
+        let mut range = RangeEncoder::new(encoder.threshold);
+        range.carryless = encoder.is_carryless();
+        range.set_symbol_bits(encoder.get_symbol_bits());
+        SelfCheck {
+            symbols_pending: range.word_symbols(),
+            range: range,
+            code: B::zero(),
+            symbols: VecDeque::new(),
+            intervals: VecDeque::new(),
+        }
+    }
+
+    /// Decode the intervals the symbols are known for,
+    /// zero-filling the code past the tail once 'finished'
+    fn decode(&mut self, finished: bool) -> Result<(), String> {
+        let bits = self.range.get_symbol_bits();
+        while let Some(&(total, lo, hi)) = self.intervals.front() {
+            while self.symbols_pending != 0 {
+                let symbol = match self.symbols.pop_front() {
+                    Some(symbol) => symbol,
+                    None if finished => 0,
+                    None => return Ok(()),
+                };
+                self.code = (self.code<<bits) + B::from_border(symbol as Border);
+                self.symbols_pending -= 1;
+            }
+            let offset = try!(self.range.query(total, self.code).map_err(|e| e.to_string()));
+            if !(lo <= offset && offset < hi) {
+                return Err(format!("decoded offset {} is out of the coded interval [{}-{}) of total {}",
+                    offset, lo, hi, total))
+            }
+            self.symbols_pending = try!(self.range.process(total, lo, hi, |_| ()).map_err(|e| e.to_string()));
+            self.intervals.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Start over after a padded code tail
+    fn reset(&mut self) {
+        self.range.reset();
+        self.code = B::zero();
+        self.symbols_pending = self.range.word_symbols();
+        self.symbols.clear();
+        self.intervals.clear();
+    }
+}
+
+#[cfg(feature="io")]
+/// Report a failed self-check: a panic with debug assertions, an error otherwise
+fn self_check_failed(message: String) -> io::Error {
+    if cfg!(debug_assertions) {
+        panic!("Self-check failed: {}", message)
+    }
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+#[cfg(feature="io")]
+/// Byte order of the last code word, written by `Encoder::finish`.
+/// The big-endian tail is the natural continuation of the code and
+/// may be shortened. The little-endian one is always the whole word,
+/// reversed, and has to end the stream: the decoder reads a word ahead to find it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+#[cfg(feature="io")]
+impl Default for Endianness {
+    fn default() -> Endianness {
+        Endianness::Big
+    }
 }
 
+#[cfg(feature="io")]
 impl<W: Write> Encoder<W> {
     /// Create a new encoder on top of a given Writer
     pub fn new(w: W) -> Encoder<W> {
+        Encoder::with_range(w, RangeEncoder::new(RANGE_DEFAULT_THRESHOLD))
+    }
+
+    /// Create a new encoder for models with denominators up to 'max_total'
+    /// (see `RangeEncoder::with_model_bounds`)
+    pub fn with_model_bounds(w: W, max_total: Border) -> Encoder<W> {
+        Encoder::with_range(w, RangeEncoder::with_model_bounds(max_total))
+    }
+
+    /// Create a new encoder keeping the active range above 'threshold',
+    /// which bounds the model denominators. The decoder has to use
+    /// the same threshold (see `Decoder::with_threshold`).
+    pub fn with_threshold(w: W, threshold: Border) -> Encoder<W> {
+        assert!(threshold > SYMBOL_TOTAL as Border,
+            "The threshold {} has to exceed {}", threshold, SYMBOL_TOTAL);
+        Encoder::with_range(w, RangeEncoder::new(threshold))
+    }
+
+    /// Create a new encoder refusing to write more than 'max_bytes'
+    /// into the stream (see `Encoder::set_output_limit`)
+    pub fn with_output_limit(w: W, max_bytes: u64) -> Encoder<W> {
+        let mut e = Encoder::new(w);
+        e.set_output_limit(max_bytes);
+        e
+    }
+
+    /// Create a new encoder writing the code tail in the given byte order
+    /// (see `Encoder::set_endianness`)
+    pub fn with_endianness(w: W, endianness: Endianness) -> Encoder<W> {
+        let mut e = Encoder::new(w);
+        e.set_endianness(endianness);
+        e
+    }
+
+    /// Create a new encoder decoding its own output on the fly
+    /// (see `Encoder::set_self_check`)
+    pub fn with_self_check(w: W) -> Encoder<W> {
+        let mut e = Encoder::new(w);
+        e.set_self_check();
+        e
+    }
+}
+
+#[cfg(feature="io")]
+/// An encoder writing straight into an owned vector of symbols,
+/// `finish` returns the vector
+pub type VecEncoder = Encoder<Vec<Symbol>>;
+
+#[cfg(feature="io")]
+impl Encoder<Vec<Symbol>> {
+    /// Create a new encoder into a vector reserving 'capacity' symbols.
+    /// Reserving a bit more than the expected code length
+    /// avoids growing the vector while coding.
+    pub fn with_capacity(capacity: usize) -> VecEncoder {
+        Encoder::new(Vec::with_capacity(capacity))
+    }
+}
+
+#[cfg(feature="io")]
+impl<W: Write, B: RangeBorder> Encoder<W, B> {
+    /// Create a new encoder on top of a given Writer,
+    /// using a custom range encoder of any width
+    pub fn with_range(w: W, range: RangeEncoder<B>) -> Encoder<W, B> {
         Encoder {
             stream: w,
-            range: RangeEncoder::new(RANGE_DEFAULT_THRESHOLD),
+            range: range,
+            buffer: Vec::with_capacity(BORDER_BYTES_MAX),
+            written: 0,
+            nibble: None,
+            closed: false,
+            limit: None,
+            endianness: Endianness::Big,
+            check: None,
         }
     }
 
+    /// Refuse to write more than 'max_bytes' into the stream in total,
+    /// counting the bytes already written. The write that would exceed
+    /// the limit fails before reaching the Writer, and the code
+    /// can't be finished afterwards.
+    pub fn set_output_limit(&mut self, max_bytes: u64) {
+        self.limit = Some(max_bytes);
+    }
+
+    /// Decode the own output on the fly, to catch the coder bugs
+    /// as they happen. Every coded interval is checked against the model
+    /// and against a shadow decoder, fed by the produced symbols.
+    /// A mismatch panics with debug assertions, and fails the coding
+    /// otherwise. Expensive, meant for the development and the tests.
+    /// Has to be set before coding, after configuring the range.
+    pub fn set_self_check(&mut self) {
+        let check = SelfCheck::new(&self.range);
+        assert!(self.written == 0 && self.buffer.is_empty() &&
+            check.range.checkpoint() == self.range.checkpoint(),
+            "The self-check has to be set before coding");
+        self.check = Some(check);
+    }
+
+    /// Write the code tail in the given byte order, the decoder
+    /// has to use the same one (see `Decoder::set_endianness`).
+    /// The little-endian encoder can't close the blocks mid-stream.
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    /// Encode a value into the buffer, recording its interval for the self-check
+    fn encode_value<V: Copy + Display, M: Model<V>>(&mut self, value: V, model: &mut M) -> io::Result<()> {
+        if let Some(ref mut check) = self.check {
+            let (lo, hi) = model.get_range(value);
+            let total = model.get_denominator();
+            // the model has to find the value back by any offset of the interval
+            if lo < hi && hi <= total {
+                for &offset in [lo, hi - 1].iter() {
+                    let (_, found_lo, found_hi) = model.find_value(offset);
+                    if (found_lo, found_hi) != (lo, hi) {
+                        return Err(self_check_failed(format!(
+                            "value {} of range [{}-{}) is found as [{}-{}) by the offset {}",
+                            value, lo, hi, found_lo, found_hi, offset)))
+                    }
+                }
+            }
+            check.intervals.push_back((total, lo, hi));
+        }
+        let result = encode(value, model, &mut self.range, &mut self.buffer);
+        if result.is_err() {
+            if let Some(ref mut check) = self.check {
+                check.intervals.pop_back();
+            }
+        }
+        try!(result);
+        Ok(())
+    }
+
+    /// Pass the gathered symbols to the self-check and decode what it can
+    fn run_self_check(&mut self, finished: bool) -> io::Result<()> {
+        if let Some(ref mut check) = self.check {
+            check.symbols.extend(self.buffer.iter().cloned());
+            try!(check.decode(finished).map_err(self_check_failed));
+            if finished {
+                check.reset();
+            }
+        }
+        Ok(())
+    }
+
     /// Encode an abstract value under the given Model
-    pub fn encode<V: Copy + Display, M: Model<V>>(&mut self, value: V, model: &M) -> io::Result<()> {
-        let mut buf = [0 as Symbol; BORDER_BYTES];
-        let num = model.encode(value, &mut self.range, &mut buf[..]);
-        self.stream.write(&buf[..num]).map(|_| ()) 
+    /// the model is updated with the value afterwards.
+    /// With debug assertions, panics on a model denominator too large
+    /// for the current range, or an interval outside of it.
+    /// An empty interval is reported as `Error::BadInterval`, leaving the coder intact.
+    /// Return the number of bytes written for the value.
+    pub fn encode<V: Copy + Display, M: Model<V>>(&mut self, value: V, model: &mut M) -> io::Result<usize> {
+        try!(self.check_open());
+        if cfg!(debug_assertions) {
+            let total = model.get_denominator();
+            assert!(total <= self.range.max_total(),
+                "Model denominator {} exceeds the largest total {} of the range",
+                total, self.range.max_total());
+            let (lo, hi) = model.get_range(value);
+            assert!(lo <= hi && hi <= total,
+                "Model range [{}-{}) of value {} is not within the denominator {}",
+                lo, hi, value, total);
+        }
+        self.buffer.truncate(0);
+        try!(self.encode_value(value, model));
+        self.write_buffer()
     }
 
-    /// Finish encoding by writing the code tail word
+    /// Encode a slice of values under the given Model
+    /// the produced symbols are gathered and written at once.
+    /// Return the number of bytes written for all the values.
+    pub fn encode_all<V: Copy + Display, M: Model<V>>(&mut self, values: &[V], model: &mut M) -> io::Result<usize> {
+        try!(self.check_open());
+        self.buffer.truncate(0);
+        for &value in values.iter() {
+            try!(self.encode_value(value, model));
+        }
+        self.write_buffer()
+    }
+
+    /// Encode the values yielded by an iterator under the given Model,
+    /// gathering the produced symbols and writing them out in chunks.
+    /// Stops on the first error, return the number of bytes written otherwise.
+    ///
+    /// ```rust
+    /// use compress::entropy::ari;
+    ///
+    /// let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+    /// let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+    /// let mut e = ari::Encoder::new(Vec::new());
+    /// e.encode_iter("hello".bytes(), &mut model).unwrap();
+    /// let (encoded, _) = e.finish();
+    ///
+    /// let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+    /// let decoded = ari::decode_bytes(&encoded, &mut model, 5).unwrap();
+    /// assert_eq!(&decoded[..], b"hello");
+    /// ```
+    pub fn encode_iter<V, M, I>(&mut self, iter: I, model: &mut M) -> io::Result<usize>
+        where V: Copy + Display, M: Model<V>, I: Iterator<Item=V>
+    {
+        try!(self.check_open());
+        let mut written = 0;
+        self.buffer.truncate(0);
+        for value in iter {
+            try!(self.encode_value(value, model));
+            if self.buffer.len() >= ITER_CHUNK_SIZE {
+                written += try!(self.write_buffer());
+                self.buffer.truncate(0);
+            }
+        }
+        Ok(written + try!(self.write_buffer()))
+    }
+
+    /// Encode the interval [lo,hi) of the total directly, without a model.
+    /// The decoder gets the offset by `Decoder::decode_freq`
+    /// and passes the same interval into `Decoder::consume_freq`.
+    /// Return the number of bytes written.
+    pub fn encode_freq(&mut self, total: Border, lo: Border, hi: Border) -> io::Result<usize> {
+        self.encode(lo, &mut Interval { total: total, lo: lo, hi: hi })
+    }
+
+    /// Encode a single bit with the binary model, adapting it afterwards
+    pub fn encode_bit(&mut self, bit: bool, model: &mut bin::Model) -> io::Result<()> {
+        try!(self.encode(bit, model));
+        model.update(bit);
+        Ok(())
+    }
+
+    /// Capture the state of the coder, to continue the code
+    /// with `from_state` in place of this encoder
+    pub fn export_state(&self) -> EncoderState<B> {
+        EncoderState {
+            range: self.range.checkpoint(),
+            threshold: self.range.threshold,
+            carryless: self.range.is_carryless(),
+            symbol_bits: self.range.get_symbol_bits(),
+            nibble: self.nibble,
+            written: self.written,
+        }
+    }
+
+    /// Create an encoder continuing the code from the exported state
+    /// on top of a given Writer. The output joined to the one before
+    /// the export is the same as coded in one go.
+    pub fn from_state(w: W, state: EncoderState<B>) -> Encoder<W, B> {
+        let mut range = RangeEncoder::new(state.threshold);
+        range.carryless = state.carryless;
+        range.set_symbol_bits(state.symbol_bits);
+        range.restore(state.range);
+        let mut e = Encoder::with_range(w, range);
+        e.nibble = state.nibble;
+        e.written = state.written;
+        e
+    }
+
+    /// Refuse coding after `finalize`
+    fn check_open(&self) -> io::Result<()> {
+        if self.closed {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "the encoder is finalized"))
+        }else {
+            Ok(())
+        }
+    }
+
+    /// Refuse the mid-stream tails, which have to be big-endian
+    fn check_mid_stream(&self) -> io::Result<()> {
+        if self.endianness == Endianness::Little {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "the little-endian tail has to end the stream"))
+        }else {
+            self.check_open()
+        }
+    }
+
+    /// Pair the gathered nibbles into bytes in place,
+    /// keeping the odd one for the next time
+    fn pack_nibbles(&mut self) {
+        let mut num_packed = 0;
+        for i in 0..self.buffer.len() {
+            let symbol = self.buffer[i];
+            match self.nibble.take() {
+                Some(high) => {
+                    self.buffer[num_packed] = (high << NIBBLE_BITS) | symbol;
+                    num_packed += 1;
+                },
+                None => self.nibble = Some(symbol),
+            }
+        }
+        self.buffer.truncate(num_packed);
+    }
+
+    /// Write out the gathered symbols, return the number of bytes
+    fn write_buffer(&mut self) -> io::Result<usize> {
+        try!(self.run_self_check(false));
+        if self.range.get_symbol_bits() == NIBBLE_BITS {
+            self.pack_nibbles();
+        }
+        self.write_packed()
+    }
+
+    /// Write out the gathered bytes as they are
+    fn write_packed(&mut self) -> io::Result<usize> {
+        if let Some(limit) = self.limit {
+            if self.written + self.buffer.len() as u64 > limit {
+                return Err(io::Error::new(io::ErrorKind::Other, "output limit exceeded"))
+            }
+        }
+        try!(self.stream.write_all(&self.buffer[..]));
+        self.written += self.buffer.len() as u64;
+        Ok(self.buffer.len())
+    }
+
+    fn write_tail(&mut self, padded: bool) -> io::Result<()> {
+        let padded = padded || self.endianness == Endianness::Little;
+        self.buffer.truncate(0);
+        let num_tail = {
+            let buffer = &mut self.buffer;
+            self.range.get_code_tail(|s| buffer.push(s))
+        };
+        if padded {
+            for _ in num_tail..self.range.word_symbols() {
+                self.buffer.push(0);
+            }
+        }
+        // complete the odd nibble with zeroes
+        if self.range.get_symbol_bits() == NIBBLE_BITS &&
+            (self.buffer.len() % 2 == 1) != self.nibble.is_some() {
+            self.buffer.push(0);
+        }
+        try!(self.run_self_check(true));
+        if self.range.get_symbol_bits() == NIBBLE_BITS {
+            self.pack_nibbles();
+        }
+        if self.endianness == Endianness::Little {
+            // the padded tail spans the whole last word
+            let start = self.buffer.len() - B::BYTES;
+            self.buffer[start..].reverse();
+        }
+        try!(self.write_packed());
+        self.stream.flush()
+    }
+
+    /// Finish encoding by writing the shortest code tail like `finalize`,
+    /// return the underlying Writer
     pub fn finish(mut self) -> (W, io::Result<()>) {
-        debug_assert!(BORDER_BITS == 32);
-        let code = self.range.get_code_tail();
-        let result = self.stream.write_u32::<BigEndian>(code)
-                                .map_err(byteorder_err_to_io);
-        let result = result.and(self.stream.flush());
+        let result = self.finalize();
+        (self.stream, result)
+    }
+
+    /// Finish encoding like `finish`, return the underlying Writer
+    /// along with the tail length and the losses, the latter being zero
+    /// unless tracking is enabled on the range encoder
+    pub fn try_finish(mut self) -> io::Result<(W, FinishReport)> {
+        let before = self.written;
+        try!(self.finalize());
+        let (cut, division) = self.range.get_bits_lost();
+        let report = FinishReport {
+            tail_bytes: (self.written - before) as usize,
+            bits_lost_on_threshold_cut: cut,
+            bits_lost_on_division: division,
+        };
+        Ok((self.stream, report))
+    }
+
+    /// Finish encoding by writing the shortest code tail,
+    /// keeping the encoder in place. The decoder assumes zeroes past
+    /// the end of the stream. The tail takes at least one byte, even if
+    /// nothing was encoded. Any coding afterwards is an error,
+    /// until `reset`, while finalizing again does nothing.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(())
+        }
+        self.closed = true;
+        self.write_tail(false)
+    }
+
+    /// Finish encoding by writing the code tail padded to the whole word,
+    /// so that the decoder consumes exactly the encoded bytes.
+    /// Required for the streams followed by other data.
+    pub fn finish_padded(mut self) -> (W, io::Result<()>) {
+        let result = self.check_mid_stream().and_then(|_| self.write_tail(true));
         (self.stream, result)
     }
 
+    /// Return the underlying Writer as it is, without writing the code tail.
+    /// The coded data in it is incomplete and can't be decoded, unless
+    /// the last block was closed with `finish_block`.
+    pub fn into_inner(self) -> W {
+        self.stream
+    }
+
+    /// Reset the range coder, keeping the stream and the allocation.
+    /// The current block has to be closed with a padded tail before
+    /// (see `finish_block`), otherwise the decoder can't sync on the next one.
+    /// Reopens a finalized encoder.
+    pub fn reset(&mut self) {
+        self.range.reset();
+        self.buffer.truncate(0);
+        self.nibble = None;
+        self.closed = false;
+        if let Some(ref mut check) = self.check {
+            check.reset();
+        }
+    }
+
+    /// Close the current block with a padded code tail
+    /// and reset the range for the next block
+    pub fn finish_block(&mut self) -> io::Result<()> {
+        try!(self.check_mid_stream());
+        try!(self.write_tail(true));
+        self.reset();
+        Ok(())
+    }
+
+    /// Byte-align the code mid-stream, closing the current block
+    /// like `finish_block` does. The models are kept, and the encoding
+    /// can go on after some raw data is written into the stream
+    /// (see `get_mut`). The decoder skips the tail with `Decoder::resync_align`.
+    pub fn flush_align(&mut self) -> io::Result<()> {
+        self.finish_block()
+    }
+
+    /// Return a mutable reference to the underlying Writer.
+    /// Writing into it is only safe right after `flush_align`.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.stream
+    }
+
     /// Flush the output stream
     pub fn flush(&mut self) -> io::Result<()> {
         self.stream.flush()
     }
 
-    /// Return the number of bytes lost due to threshold cuts and integer operations
-    #[cfg(tune)]
+    /// Return the number of bytes written into the stream so far
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Return the number of bytes lost due to threshold cuts and integer operations,
+    /// zero unless tracking is enabled on the range encoder
     pub fn get_bytes_lost(&self) -> (f32, f32) {
         let (a,b) = self.range.get_bits_lost();
         (a/8.0, b/8.0)
     }
+
+    /// Compare the output so far against the ideal code length,
+    /// zero except for the actual bits unless tracking is enabled on the range encoder
+    pub fn efficiency_report(&self) -> EfficiencyReport {
+        let (cut, division) = self.range.get_bits_lost();
+        EfficiencyReport {
+            ideal_bits: self.range.get_bits_ideal(),
+            actual_bits: self.written * 8,
+            bits_lost_on_threshold_cut: cut,
+            bits_lost_on_division: division,
+        }
+    }
+}
+
+#[cfg(feature="io")]
+/// Coding efficiency of an `Encoder`, see `Encoder::efficiency_report`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EfficiencyReport {
+    /// Number of bits implied by the probabilities of the coded values
+    pub ideal_bits: f32,
+    /// Number of bits written into the stream so far, including the tails
+    /// written by `finalize` or `flush_align`, but not the pending code
+    pub actual_bits: u64,
+    /// Number of bits lost due to the range cuts on renormalization
+    pub bits_lost_on_threshold_cut: f32,
+    /// Number of bits lost due to the integer division by the model total
+    pub bits_lost_on_division: f32,
+}
+
+#[cfg(feature="io")]
+/// Summary of a finished `Encoder`, see `Encoder::try_finish`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FinishReport {
+    /// Number of bytes written for the code tail
+    pub tail_bytes: usize,
+    /// Number of bits lost due to the range cuts on renormalization
+    pub bits_lost_on_threshold_cut: f32,
+    /// Number of bits lost due to the integer division by the model total
+    pub bits_lost_on_division: f32,
+}
+
+#[cfg(feature="io")]
+/// The complete state of an `Encoder` between the coded values,
+/// taken by `Encoder::export_state`. Serialized by `to_bytes`,
+/// it allows continuing the code in another process, as if it was never
+/// interrupted. The models are not included and have to be kept separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncoderState<B = Border> {
+    range: RangeState<B>,
+    threshold: B,
+    carryless: bool,
+    symbol_bits: usize,
+    nibble: Option<Symbol>,
+    written: u64,
+}
+
+#[cfg(feature="io")]
+/// Flags of the serialized `EncoderState`
+const STATE_CARRYLESS: u8 = 1;
+#[cfg(feature="io")]
+const STATE_NIBBLES: u8 = 2;
+#[cfg(feature="io")]
+const STATE_CACHE: u8 = 4;
+#[cfg(feature="io")]
+const STATE_NIBBLE: u8 = 8;
+
+#[cfg(feature="io")]
+impl<B: RangeBorder> EncoderState<B> {
+    /// Serialize the state: the flags, the cache and the pending nibble,
+    /// then the borders, the threshold, the number of pending symbols
+    /// and of the bytes written, all little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.carryless { flags |= STATE_CARRYLESS }
+        if self.symbol_bits == NIBBLE_BITS { flags |= STATE_NIBBLES }
+        if self.range.cache.is_some() { flags |= STATE_CACHE }
+        if self.nibble.is_some() { flags |= STATE_NIBBLE }
+        let mut bytes = vec![flags, self.range.cache.unwrap_or(0), self.nibble.unwrap_or(0)];
+        for &border in [self.range.low, self.range.hai, self.threshold].iter() {
+            bytes.extend((0..B::BYTES).map(|i| (border >> (8*i)).to_symbol()));
+        }
+        for &number in [self.range.num_pending as u64, self.written].iter() {
+            bytes.extend((0..8).map(|i| (number >> (8*i)) as u8));
+        }
+        bytes
+    }
+
+    /// Deserialize the state written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<EncoderState<B>> {
+        if bytes.len() != 3 + 3*B::BYTES + 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid encoder state size"))
+        }
+        let flags = bytes[0];
+        if flags & !(STATE_CARRYLESS | STATE_NIBBLES | STATE_CACHE | STATE_NIBBLE) != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown encoder state flags"))
+        }
+        let border = |k: usize| bytes[3 + k*B::BYTES .. 3 + (k+1)*B::BYTES].iter().rev()
+            .fold(B::zero(), |u, &b| (u << 8) | B::from_border(b as Border));
+        let number = |k: usize| bytes[3 + 3*B::BYTES + 8*k .. 3 + 3*B::BYTES + 8*(k+1)].iter().rev()
+            .fold(0u64, |u, &b| (u << 8) | b as u64);
+        let (low, hai, threshold) = (border(0), border(1), border(2));
+        // the carryless coder wraps 'hai' around the top border
+        let carryless = flags & STATE_CARRYLESS != 0;
+        if threshold <= B::from_border(SYMBOL_TOTAL as Border) || (!carryless && low > hai) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid encoder state range"))
+        }
+        Ok(EncoderState {
+            range: RangeState {
+                low: low,
+                hai: hai,
+                cache: if flags & STATE_CACHE != 0 {Some(bytes[1])} else {None},
+                num_pending: number(0) as usize,
+            },
+            threshold: threshold,
+            carryless: carryless,
+            symbol_bits: if flags & STATE_NIBBLES != 0 {NIBBLE_BITS} else {SYMBOL_BITS},
+            nibble: if flags & STATE_NIBBLE != 0 {Some(bytes[2])} else {None},
+            written: number(1),
+        })
+    }
 }
 
+#[cfg(feature="io")]
+/// A snapshot of the decoder state, taken by `Decoder::checkpoint`.
+/// Restoring it on a stream positioned at the same offset
+/// resumes decoding from that point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecoderState<B = Border> {
+    range: RangeState<B>,
+    code: B,
+    bytes_pending: usize,
+    bytes_filled: usize,
+    nibble: Option<Symbol>,
+    remaining: Option<u64>,
+    ahead: Lookahead,
+}
+
+#[cfg(feature="io")]
+/// The bytes read ahead by the little-endian decoder,
+/// the last word reversed once the stream ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Lookahead {
+    bytes: [Symbol; BORDER_BYTES_MAX + 1],
+    len: usize,
+    at_tail: bool,
+}
+
+#[cfg(feature="io")]
+impl Lookahead {
+    fn new() -> Lookahead {
+        Lookahead {
+            bytes: [0; BORDER_BYTES_MAX + 1],
+            len: 0,
+            at_tail: false,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Symbol> {
+        if self.len == 0 {
+            return None
+        }
+        let byte = self.bytes[0];
+        self.bytes.copy_within(1..self.len, 0);
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+#[cfg(feature="io")]
 /// An arithmetic decoder helper
-pub struct Decoder<R> {
+pub struct Decoder<R, B = Border> {
     stream: R,
-    range: RangeEncoder,
-    code: Border,
+    range: RangeEncoder<B>,
+    code: B,
+    /// number of code symbols to read before decoding
     bytes_pending: usize,
+    /// number of zero bytes filled in past the end of the stream
+    bytes_filled: usize,
+    /// the low nibble of the last byte read in the nibble mode
+    nibble: Option<Symbol>,
+    /// number of values left to decode, if known
+    remaining: Option<u64>,
+    /// byte order of the code tail
+    endianness: Endianness,
+    ahead: Lookahead,
 }
 
+#[cfg(feature="io")]
 impl<R: Read> Decoder<R> {
     /// Create a decoder on top of a given Reader
     pub fn new(r: R) -> Decoder<R> {
+        Decoder::with_range(r, RangeEncoder::new(RANGE_DEFAULT_THRESHOLD))
+    }
+
+    /// Create a new decoder for models with denominators up to 'max_total',
+    /// has to match the encoder
+    pub fn with_model_bounds(r: R, max_total: Border) -> Decoder<R> {
+        Decoder::with_range(r, RangeEncoder::with_model_bounds(max_total))
+    }
+
+    /// Create a new decoder with a custom range threshold,
+    /// has to match the encoder (see `Encoder::with_threshold`)
+    pub fn with_threshold(r: R, threshold: Border) -> Decoder<R> {
+        assert!(threshold > SYMBOL_TOTAL as Border,
+            "The threshold {} has to exceed {}", threshold, SYMBOL_TOTAL);
+        Decoder::with_range(r, RangeEncoder::new(threshold))
+    }
+
+    /// Create a decoder of exactly 'num_values' values, known
+    /// from the frame header, say. Decoding past them reports the end
+    /// of the stream, regardless of the bytes left.
+    pub fn with_expected_len(r: R, num_values: u64) -> Decoder<R> {
+        let mut decoder = Decoder::new(r);
+        decoder.remaining = Some(num_values);
+        decoder
+    }
+
+    /// Create a decoder of the code tail in the given byte order
+    /// (see `Decoder::set_endianness`)
+    pub fn with_endianness(r: R, endianness: Endianness) -> Decoder<R> {
+        let mut decoder = Decoder::new(r);
+        decoder.set_endianness(endianness);
+        decoder
+    }
+}
+
+#[cfg(feature="io")]
+impl<R: Read, B: RangeBorder> Decoder<R, B> {
+    /// Create a decoder on top of a given Reader,
+    /// using a custom range encoder of any width
+    pub fn with_range(r: R, range: RangeEncoder<B>) -> Decoder<R, B> {
         Decoder {
             stream: r,
-            range: RangeEncoder::new(RANGE_DEFAULT_THRESHOLD),
-            code: 0,
-            bytes_pending: BORDER_BYTES,
+            code: B::zero(),
+            bytes_pending: range.word_symbols(),
+            bytes_filled: 0,
+            nibble: None,
+            remaining: None,
+            endianness: Endianness::Big,
+            ahead: Lookahead::new(),
+            range: range,
+        }
+    }
+
+    /// Read the code tail in the given byte order, has to match
+    /// the encoder (see `Encoder::set_endianness`) and to be set
+    /// before decoding. The little-endian decoder reads a word ahead,
+    /// so the stream has to end with the code, and `resync_align` is an error.
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        assert!(self.ahead.len == 0 && !self.ahead.at_tail,
+            "The byte order has to be set before decoding");
+        self.endianness = endianness;
+    }
+
+    /// Read a byte from the stream, `None` at the end.
+    /// A reader interrupted too many times in a row fails with its last error.
+    fn read_stream(&mut self) -> io::Result<Option<Symbol>> {
+        let mut buf = [0 as Symbol];
+        let mut retries = 0;
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                // a reader making no progress is an error, not a spin
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted &&
+                    retries < READ_RETRIES_MAX => retries += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read the next code byte, reversing the last word
+    /// of the stream in the little-endian mode
+    fn read_byte(&mut self) -> io::Result<Option<Symbol>> {
+        if self.endianness == Endianness::Big {
+            return self.read_stream()
+        }
+        while !self.ahead.at_tail && self.ahead.len <= B::BYTES {
+            match try!(self.read_stream()) {
+                Some(byte) => {
+                    self.ahead.bytes[self.ahead.len] = byte;
+                    self.ahead.len += 1;
+                },
+                None => {
+                    self.ahead.bytes[..self.ahead.len].reverse();
+                    self.ahead.at_tail = true;
+                },
+            }
         }
+        Ok(self.ahead.pop())
     }
 
+    /// Read the pending code symbols, zero-filling the short tail at the end of the stream
+    /// the tail is never shorter than a byte, so filling the whole word is an error.
     fn feed(&mut self) -> io::Result<()> {
+        let bits = self.range.get_symbol_bits();
         while self.bytes_pending != 0 {
-            let b = try!(self.stream.read_u8());
-            self.code = (self.code<<8) + (b as Border);
+            let symbol = match self.nibble.take() {
+                Some(low) => low,
+                None => {
+                    let byte = match try!(self.read_byte()) {
+                        Some(byte) => byte,
+                        None => {
+                            self.bytes_filled += 1;
+                            if self.bytes_filled >= B::BYTES {
+                                return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                    "unexpected end of the coded stream"))
+                            }
+                            0
+                        },
+                    };
+                    if bits == NIBBLE_BITS {
+                        self.nibble = Some(byte & 0xF);
+                        byte >> NIBBLE_BITS
+                    }else {
+                        byte
+                    }
+                },
+            };
+            self.code = (self.code<<bits) + B::from_border(symbol as Border);
             self.bytes_pending -= 1;
         }
         Ok(())
     }
 
     /// Decode an abstract value based on the given Model
-    pub fn decode<V: Copy + Display, M: Model<V>>(&mut self, model: &M) -> io::Result<V> {
-        self.feed().unwrap();
-        let (value, shift) = model.decode(self.code, &mut self.range);
+    /// the model is updated with the value afterwards
+    pub fn decode<V: Copy + Display, M: Model<V>>(&mut self, model: &mut M) -> io::Result<V> {
+        match try!(self.try_decode(model)) {
+            Some(value) => Ok(value),
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                "unexpected end of the coded stream")),
+        }
+    }
+
+    /// Decode an abstract value based on the given Model
+    /// return `None` if the stream has ended before the value
+    pub fn try_decode<V: Copy + Display, M: Model<V>>(&mut self, model: &mut M) -> io::Result<Option<V>> {
+        if self.remaining == Some(0) {
+            return Ok(None)
+        }
+        match self.feed() {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let (value, shift) = try!(model.decode(self.code, &mut self.range));
+        model.update(value);
         self.bytes_pending = shift;
-        Ok(value)
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= 1;
+        }
+        Ok(Some(value))
+    }
+
+    /// Skip the rest of the code tail written by `Encoder::flush_align`
+    /// and reset the range for the next block. The stream is left
+    /// right past the tail, so raw data can be read from it (see `get_mut`).
+    pub fn resync_align(&mut self) -> io::Result<()> {
+        if self.endianness == Endianness::Little {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "the little-endian tail has to end the stream"))
+        }
+        try!(self.feed());
+        self.reset_all();
+        Ok(())
+    }
+
+    /// Reset the range and the code, so that decoding restarts
+    /// from the current stream position, which has to be at the start
+    /// of a new frame. Allows decoding several frames from one Reader.
+    pub fn reset_all(&mut self) {
+        self.range.reset();
+        self.code = B::zero();
+        self.bytes_pending = self.range.word_symbols();
+        self.bytes_filled = 0;
+        self.nibble = None;
+    }
+
+    /// Return the number of values left to decode,
+    /// if the decoder was created by `with_expected_len`
+    pub fn get_remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    /// Return a mutable reference to the underlying Reader.
+    /// Reading from it is only safe right after `resync_align`.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+
+    /// Capture the current decoder state.
+    /// The pending code bytes are not yet read from the stream.
+    pub fn checkpoint(&self) -> DecoderState<B> {
+        DecoderState {
+            range: self.range.checkpoint(),
+            code: self.code,
+            bytes_pending: self.bytes_pending,
+            bytes_filled: self.bytes_filled,
+            nibble: self.nibble,
+            remaining: self.remaining,
+            ahead: self.ahead,
+        }
+    }
+
+    /// Return to the captured decoder state,
+    /// the stream is expected to be at the offset of the checkpoint
+    pub fn restore(&mut self, state: DecoderState<B>) {
+        self.range.restore(state.range);
+        self.code = state.code;
+        self.bytes_pending = state.bytes_pending;
+        self.bytes_filled = state.bytes_filled;
+        self.nibble = state.nibble;
+        self.remaining = state.remaining;
+        self.ahead = state.ahead;
+    }
+
+    /// Check that the stream decodes cleanly into 'expected_len' values
+    /// under the model, without keeping them: the values are in the range,
+    /// the code tail is consistent with it, and nothing follows the tail.
+    /// Cheaper than decoding into a buffer for integrity scans.
+    pub fn validate<V: Copy + Display, M: Model<V>>(&mut self, model: &mut M, expected_len: usize) -> io::Result<()> {
+        for _ in 0..expected_len {
+            try!(self.decode(model));
+        }
+        try!(self.feed());
+        let (low, hai) = self.range.bounds();
+        if !self.range.is_carryless() && !(low <= self.code && self.code < hai) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "code tail is out of the range"))
+        }
+        match try!(self.read_byte()) {
+            None => Ok(()),
+            Some(_) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                "unexpected data after the code tail")),
+        }
+    }
+
+    /// Decode the offset within the total, without a model,
+    /// for the caller to find the interval [lo,hi) it belongs to.
+    /// Nothing is consumed until `consume_freq` is called with the interval.
+    pub fn decode_freq(&mut self, total: Border) -> io::Result<Border> {
+        if self.remaining == Some(0) {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                "all the expected values are decoded"))
+        }
+        try!(self.feed());
+        Ok(try!(self.range.query(total, self.code)))
+    }
+
+    /// Consume the interval [lo,hi) of the total, which has to contain
+    /// the offset returned by `decode_freq`
+    pub fn consume_freq(&mut self, total: Border, lo: Border, hi: Border) -> io::Result<()> {
+        let offset = try!(self.decode_freq(total));
+        if !(lo <= offset && offset < hi) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "the interval doesn't contain the decoded offset"))
+        }
+        self.decode(&mut Interval { total: total, lo: lo, hi: hi }).map(|_| ())
+    }
+
+    /// Decode a single bit with the binary model, adapting it afterwards
+    pub fn decode_bit(&mut self, model: &mut bin::Model) -> io::Result<bool> {
+        let bit = try!(self.decode(model));
+        model.update(bit);
+        Ok(bit)
+    }
+
+    /// Iterate over the values decoded lazily with the given Model,
+    /// until the end of the stream. The short code tail may yield a few
+    /// extra values, so the stream length has to be known or terminated.
+    ///
+    /// ```rust
+    /// use compress::entropy::ari;
+    ///
+    /// let threshold = ari::RANGE_DEFAULT_THRESHOLD >> 2;
+    /// let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+    /// let mut e = ari::Encoder::new(Vec::new());
+    /// e.encode_all(b"abracadabra abracadabra", &mut model).unwrap();
+    /// let (encoded, _) = e.finish();
+    ///
+    /// let mut model = ari::table::AdaptiveByteModel::new(16, threshold);
+    /// let mut d = ari::Decoder::new(&encoded[..]);
+    /// let first: Vec<u8> = d.symbols(&mut model).take(10).map(|v| v.unwrap()).collect();
+    /// assert_eq!(&first[..], b"abracadabr");
+    /// ```
+    pub fn symbols<'a, V, M>(&'a mut self, model: &'a mut M) -> Symbols<'a, R, B, V, M>
+        where V: Copy + Display, M: Model<V>
+    {
+        Symbols {
+            decoder: self,
+            model: model,
+            is_done: false,
+            _value: PhantomData,
+        }
+    }
+
+    /// Decode exactly 'n' values based on the given Model,
+    /// nothing is read from the stream for zero values
+    pub fn decode_n<V: Copy + Display, M: Model<V>>(&mut self, n: usize, model: &mut M) -> io::Result<Vec<V>> {
+        let mut values = Vec::with_capacity(n);
+        for _ in 0..n {
+            values.push(try!(self.decode(model)));
+        }
+        Ok(values)
+    }
+
+    /// Return the underlying Reader as it is. The decoder reads ahead,
+    /// so the bytes of the current code word may be already consumed.
+    pub fn into_inner(self) -> R {
+        self.stream
     }
 
     /// Finish decoding
@@ -291,3 +1915,34 @@ impl<R: Read> Decoder<R> {
         (self.stream, err)
     }
 }
+
+#[cfg(feature="io")]
+/// An iterator over the decoded values, see `Decoder::symbols`
+pub struct Symbols<'a, R: 'a, B: 'a, V, M: 'a> {
+    decoder: &'a mut Decoder<R, B>,
+    model: &'a mut M,
+    is_done: bool,
+    _value: PhantomData<V>,
+}
+
+#[cfg(feature="io")]
+impl<'a, R: Read, B: RangeBorder, V: Copy + Display, M: Model<V>> Iterator for Symbols<'a, R, B, V, M> {
+    type Item = io::Result<V>;
+
+    fn next(&mut self) -> Option<io::Result<V>> {
+        if self.is_done {
+            return None
+        }
+        match self.decoder.try_decode(self.model) {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.is_done = true;
+                None
+            },
+            Err(e) => {
+                self.is_done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
@@ -0,0 +1,182 @@
+/*!
+
+Preset static models of bytes, picked per message by a selector byte.
+Requires `io` feature, enabled by default.
+
+The encoder estimates the size of the message under every preset,
+writes the index of the best one as a single byte and codes the message
+with it (see `table::DictByteEncoder`). The decoder loads the preset
+by the index, so many similar messages adapt cheaply, without sending
+a frequency table along.
+
+# Example
+
+```rust
+use compress::entropy::ari::preset;
+
+let registry = preset::PresetRegistry::new();
+let (encoded, selector) = registry.encode_best(Vec::new(), b"some text");
+assert_eq!(selector.unwrap(), preset::PRESET_TEXT);
+let decoded = registry.decode(&encoded[..]).unwrap();
+assert_eq!(&decoded[..], b"some text");
+```
+
+*/
+
+use std::io::{self, Read, Write};
+use super::{estimate_bits, SYMBOL_TOTAL};
+use super::table::{DictByteDecoder, DictByteEncoder, StaticModel};
+
+/// Selector of the built-in preset for English text
+pub const PRESET_TEXT: u8 = 0;
+/// Selector of the built-in preset for binary data
+pub const PRESET_BINARY: u8 = 1;
+/// Selector of the built-in preset for data dominated by zeroes
+pub const PRESET_SPARSE: u8 = 2;
+
+/// Letters of English by their frequency
+const LETTERS: &'static [u8] = b"etaoinshrdlcumwfgypbvkjxqz";
+
+/// Build a preset from the byte counts, giving every byte and the terminator
+/// a non-empty interval
+fn preset_from(byte_counts: &[u32]) -> StaticModel {
+    let mut counts = byte_counts.to_vec();
+    counts.push(1);
+    StaticModel::from_histogram_smoothed(&counts, 1)
+}
+
+fn preset_text() -> StaticModel {
+    let mut counts = vec![0u32; SYMBOL_TOTAL];
+    for (i, &c) in LETTERS.iter().enumerate() {
+        let count = 600 - 22 * i as u32;
+        counts[c as usize] = count;
+        counts[c.to_ascii_uppercase() as usize] = count / 8;
+    }
+    for c in 0x20..0x7F {
+        counts[c] = counts[c].max(4);
+    }
+    for &c in b"0123456789".iter() {
+        counts[c as usize] = 20;
+    }
+    counts[b' ' as usize] = 1200;
+    counts[b'\n' as usize] = 100;
+    counts[b',' as usize] = 60;
+    counts[b'.' as usize] = 60;
+    preset_from(&counts)
+}
+
+fn preset_binary() -> StaticModel {
+    let mut counts = vec![8u32; SYMBOL_TOTAL];
+    counts[0] = 256;
+    counts[0xFF] = 64;
+    preset_from(&counts)
+}
+
+fn preset_sparse() -> StaticModel {
+    let mut counts = vec![0u32; SYMBOL_TOTAL];
+    counts[0] = 4000;
+    for c in 1..16 {
+        counts[c] = 40;
+    }
+    preset_from(&counts)
+}
+
+/// A registry of up to 256 static models of bytes and the terminator,
+/// shared by the encoder and the decoder
+pub struct PresetRegistry {
+    presets: Vec<StaticModel>,
+}
+
+impl Default for PresetRegistry {
+    fn default() -> PresetRegistry {
+        PresetRegistry::new()
+    }
+}
+
+impl PresetRegistry {
+    /// Create a registry of the built-in presets
+    pub fn new() -> PresetRegistry {
+        PresetRegistry::with_presets(vec![preset_text(), preset_binary(), preset_sparse()])
+    }
+
+    /// Create a registry of custom presets, indexed by their position.
+    /// Every preset has to code the bytes and the terminator (see `DictByteEncoder`).
+    pub fn with_presets(presets: Vec<StaticModel>) -> PresetRegistry {
+        assert!(!presets.is_empty() && presets.len() <= SYMBOL_TOTAL,
+            "Unsupported number of presets {}", presets.len());
+        for preset in presets.iter() {
+            assert_eq!(preset.get_num_values(), SYMBOL_TOTAL+1,
+                "The preset doesn't fit the byte alphabet");
+        }
+        PresetRegistry {
+            presets: presets,
+        }
+    }
+
+    /// Return the number of presets
+    pub fn get_num_presets(&self) -> usize {
+        self.presets.len()
+    }
+
+    /// Return the preset of a given selector
+    pub fn get_preset(&self, selector: u8) -> Option<&StaticModel> {
+        self.presets.get(selector as usize)
+    }
+
+    /// Estimate the number of bits to code the data with the terminator
+    /// under the preset, see `estimate_bits`
+    pub fn estimate(&self, selector: u8, data: &[u8]) -> f64 {
+        let preset = &self.presets[selector as usize];
+        data.iter().fold(estimate_bits(SYMBOL_TOTAL, preset),
+            |sum, &b| sum + estimate_bits(b as usize, preset))
+    }
+
+    /// Return the selector of the preset giving the smallest estimate
+    pub fn select(&self, data: &[u8]) -> u8 {
+        let mut best = (0, self.estimate(0, data));
+        for selector in 1..self.presets.len() {
+            let bits = self.estimate(selector as u8, data);
+            if bits < best.1 {
+                best = (selector, bits);
+            }
+        }
+        best.0 as u8
+    }
+
+    /// Write the selector and the data coded with its preset
+    pub fn encode_with<W: Write>(&self, mut w: W, selector: u8, data: &[u8]) -> (W, io::Result<()>) {
+        let preset = match self.get_preset(selector) {
+            Some(preset) => preset,
+            None => return (w, Err(io::Error::new(io::ErrorKind::InvalidInput, "unknown preset"))),
+        };
+        if let Err(e) = w.write_all(&[selector]) {
+            return (w, Err(e))
+        }
+        let mut encoder = DictByteEncoder::new(w, preset);
+        let ret = encoder.write_all(data);
+        let (w, r2) = encoder.finish();
+        (w, ret.and(r2))
+    }
+
+    /// Write the data coded with the best preset (see `select`),
+    /// return the selector
+    pub fn encode_best<W: Write>(&self, w: W, data: &[u8]) -> (W, io::Result<u8>) {
+        let selector = self.select(data);
+        let (w, ret) = self.encode_with(w, selector, data);
+        (w, ret.map(|_| selector))
+    }
+
+    /// Read the selector and decode the data with its preset
+    pub fn decode<R: Read>(&self, mut r: R) -> io::Result<Vec<u8>> {
+        let mut selector = [0u8];
+        try!(r.read_exact(&mut selector));
+        let preset = match self.get_preset(selector[0]) {
+            Some(preset) => preset,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown preset")),
+        };
+        let mut decoder = DictByteDecoder::new(r, preset);
+        let mut output = Vec::new();
+        try!(decoder.read_to_end(&mut output));
+        Ok(output)
+    }
+}
@@ -11,17 +11,33 @@ The module also implements Reader/Writer using simple byte coding.
 
 */
 
+#[cfg(feature="io")]
 use std::io::{self, Read, Write};
+#[cfg(feature="io")]
+use std::thread;
+#[cfg(feature="io")]
+use ReadExact;
+#[cfg(all(feature="io", feature="checksum"))]
+use checksum::adler;
+use std::mem;
 use super::Border;
+use super::escape;
 
 pub type Frequency = u16;
 
 /// A simple table of frequencies.
+/// Cumulative frequencies are kept in a Fenwick tree,
+/// making both the lookup and the update logarithmic.
+/// Once the total reaches the threshold, all frequencies are halved,
+/// keeping the seen values at least 1.
+#[derive(Clone)]
 pub struct Model {
     /// sum of frequencies
     total: Border,
     /// main table: value -> Frequency
     table: Vec<Frequency>,
+    /// binary indexed tree of partial sums, 1-based
+    tree: Vec<Border>,
     /// maximum allowed sum of frequency,
     /// should be smaller than RangeEncoder::threshold
     cut_threshold: Border,
@@ -40,6 +56,7 @@ impl Model {
         let mut ft = Model {
             total: total,
             table: freq,
+            tree: Vec::with_capacity(num_values+1),
             cut_threshold: threshold,
             cut_shift: 1,
         };
@@ -47,80 +64,1180 @@ impl Model {
         while ft.total >= threshold {
             ft.downscale();
         }
+        ft.build_tree();
         ft
     }
 
+    /// Return the number of bytes taken by the frequencies and the tree
+    fn memory_size(&self) -> usize {
+        self.table.capacity() * mem::size_of::<Frequency>() +
+            self.tree.capacity() * mem::size_of::<Border>()
+    }
+
     /// Create a new tanle with all frequencies being equal
     pub fn new_flat(num_values: usize, threshold: Border) -> Model {
         Model::new_custom(num_values, threshold, |_| 1)
     }
 
-    /// Reset the table to the flat state
-    pub fn reset_flat(&mut self) {
-        for freq in self.table.iter_mut() {
-            *freq = 1;
-        }
-        self.total = self.table.len() as Border;
+    /// Reset the table to the flat state
+    pub fn reset_flat(&mut self) {
+        for freq in self.table.iter_mut() {
+            *freq = 1;
+        }
+        self.total = self.table.len() as Border;
+        self.build_tree();
+    }
+
+    /// Rebuild the cumulative tree from the frequency table
+    fn build_tree(&mut self) {
+        let n = self.table.len();
+        self.tree.truncate(0);
+        self.tree.push(0);
+        self.tree.extend(self.table.iter().map(|&f| f as Border));
+        for i in 1..n+1 {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= n {
+                self.tree[parent] += self.tree[i];
+            }
+        }
+    }
+
+    /// Add a given amount to the frequency of 'value'
+    fn add(&mut self, value: usize, add: Border) {
+        self.table[value] += add as Frequency;
+        self.total += add;
+        let mut i = value + 1;
+        while i < self.tree.len() {
+            self.tree[i] += add;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Return the sum of frequencies of all values below 'value'
+    fn get_cumulative(&self, value: usize) -> Border {
+        let mut sum = 0;
+        let mut i = value;
+        while i > 0 {
+            sum += self.tree[i];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Add a given amount to the frequency of 'value', rescaling the table
+    /// to keep the frequency representable and the total below the threshold.
+    /// Rescaling only depends on the table state, so the encoder and
+    /// the decoder do it at the same moment.
+    fn add_rescaled(&mut self, value: usize, add: Border) {
+        assert!(add <= Frequency::max_value() as Border);
+        while self.table[value] as Border + add > Frequency::max_value() as Border {
+            self.downscale();
+        }
+        self.add(value, add);
+        while self.total >= self.cut_threshold {
+            self.downscale();
+        }
+    }
+
+    /// Add a constant to the frequency of 'value',
+    /// downscaling the table once the threshold is reached
+    pub fn add_frequency(&mut self, value: usize, add: Border) {
+        debug!("\tAdding {} to value {}", add, value);
+        self.add_rescaled(value, add);
+    }
+
+    /// Adapt the table in favor of given 'value'
+    /// using 'add_log' and 'add_const' to produce the additive factor
+    /// the higher 'add_log' is, the more concervative is the adaptation
+    pub fn update(&mut self, value: usize, add_log: usize, add_const: Border) {
+        let add = (self.total>>add_log) + add_const;
+        assert!(add < 2*self.cut_threshold);
+        debug!("\tUpdating by adding {} to value {}", add, value);
+        self.add_rescaled(value, add);
+    }
+
+    /// Reduce frequencies by 'cut_iter' bits
+    pub fn downscale(&mut self) {
+        debug!("\tDownscaling frequencies");
+        let roundup: Border = (1<<self.cut_shift) - 1;
+        self.total = 0;
+        for freq in self.table.iter_mut() {
+            // preserve non-zero frequencies to remain positive
+            *freq = ((*freq as Border + roundup) >> self.cut_shift) as Frequency;
+            self.total += *freq as Border;
+        }
+        self.build_tree();
+    }
+
+    /// Add the frequencies of another table of the same size,
+    /// halving the sums while they don't fit the frequency type
+    /// or reach the threshold
+    pub fn merge(&mut self, other: &Model) {
+        assert_eq!(self.table.len(), other.table.len());
+        let mut sums: Vec<Border> = self.table.iter().zip(other.table.iter())
+            .map(|(&a, &b)| a as Border + b as Border).collect();
+        let roundup: Border = (1<<self.cut_shift) - 1;
+        while sums.iter().any(|&f| f > Frequency::max_value() as Border) ||
+            sums.iter().fold(0, |u, &f| u + f) >= self.cut_threshold {
+            for f in sums.iter_mut() {
+                *f = (*f + roundup) >> self.cut_shift;
+            }
+        }
+        self.total = 0;
+        for (freq, &sum) in self.table.iter_mut().zip(sums.iter()) {
+            *freq = sum as Frequency;
+            self.total += sum;
+        }
+        self.build_tree();
+    }
+
+    /// Return read-only frequencies slice
+    pub fn get_frequencies<'a>(&'a self) -> &'a [Frequency] {
+        &self.table[..]
+    }
+
+    /// List the interval [lo,hi) of every value, empty for the unseen ones
+    pub fn dump_intervals(&self) -> Vec<(usize, Border, Border)> {
+        let mut lo = 0;
+        self.table.iter().enumerate().map(|(value, &freq)| {
+            let hi = lo + freq as Border;
+            let interval = (value, lo, hi);
+            lo = hi;
+            interval
+        }).collect()
+    }
+}
+
+impl super::Model<usize> for Model {
+    fn get_range(&self, value: usize) -> (Border,Border) {
+        let lo = self.get_cumulative(value);
+        (lo, lo + (self.table[value] as Border))
+    }
+
+    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
+        assert!(offset < self.total,
+            "Invalid frequency offset {} requested under total {}",
+            offset, self.total);
+        // descend the tree, looking for the last node not exceeding the offset
+        let n = self.table.len();
+        let mut step = if n == 0 {0} else {1 << (63 - (n as u64).leading_zeros())};
+        let mut value = 0;
+        let mut lo = 0 as Border;
+        while step != 0 {
+            if value + step <= n && lo + self.tree[value+step] <= offset {
+                value += step;
+                lo += self.tree[value];
+            }
+            step >>= 1;
+        }
+        (value, lo, lo + (self.table[value] as Border))
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.total
+    }
+
+    fn get_ranges(&self, values: &[usize], out: &mut [(Border,Border)]) {
+        assert_eq!(values.len(), out.len());
+        if values.len() < self.table.len() >> 4 {
+            for (range, &value) in out.iter_mut().zip(values.iter()) {
+                *range = self.get_range(value);
+            }
+            return
+        }
+        // a long batch pays off a linear pass over the frequencies
+        let mut cumulative = Vec::with_capacity(self.table.len() + 1);
+        let mut lo = 0;
+        cumulative.push(lo);
+        for &freq in self.table.iter() {
+            lo += freq as Border;
+            cumulative.push(lo);
+        }
+        for (range, &value) in out.iter_mut().zip(values.iter()) {
+            *range = (cumulative[value], cumulative[value+1]);
+        }
+    }
+}
+
+
+/// A frozen frequency table, built from the value counts
+/// collected in the first pass over the data.
+/// Small tables look the values up directly by the offset.
+/// The default one is uniform over the bytes.
+#[derive(Clone)]
+pub struct StaticModel {
+    /// cumulative frequencies: value -> lower border, plus the total
+    cumulative: Vec<Border>,
+    /// offset -> value, only built for small totals
+    lookup: Vec<u32>,
+}
+
+/// The largest total to get a direct lookup of the values in `StaticModel`
+pub const LOOKUP_MAX_TOTAL: Border = 1<<12;
+
+impl Default for StaticModel {
+    fn default() -> StaticModel {
+        StaticModel::from_frequencies(&[1; super::SYMBOL_TOTAL])
+    }
+}
+
+impl StaticModel {
+    /// Create a new model by normalizing the histogram to fit below
+    /// the default range threshold. Every value with a positive count
+    /// is guaranteed to get a non-empty interval.
+    pub fn from_histogram(counts: &[u32]) -> StaticModel {
+        let frequencies = normalize(counts, super::RANGE_DEFAULT_THRESHOLD);
+        StaticModel::from_frequencies(&frequencies)
+    }
+
+    /// Create a new model like `from_histogram`, adding the 'pseudo_count'
+    /// to every count first (Laplace smoothing). Every value gets a non-empty
+    /// interval, so the data may contain values missing from the histogram.
+    pub fn from_histogram_smoothed(counts: &[u32], pseudo_count: u32) -> StaticModel {
+        assert!(pseudo_count > 0, "The pseudo-count has to be positive");
+        let smoothed: Vec<u32> = counts.iter().map(|&c| c.saturating_add(pseudo_count)).collect();
+        StaticModel::from_histogram(&smoothed)
+    }
+
+    /// Create a new model by normalizing the histogram to sum up
+    /// exactly to the 'total', giving the rounding leftover to the most
+    /// frequent value. Every value with a positive count is guaranteed
+    /// to get a non-empty interval.
+    pub fn from_histogram_exact(counts: &[u32], total: Border) -> StaticModel {
+        let mut frequencies = normalize(counts, total);
+        let sum = frequencies.iter().fold(0, |u,&f| u+f);
+        let mut top = 0;
+        for (i, &c) in counts.iter().enumerate() {
+            if c > counts[top] {
+                top = i;
+            }
+        }
+        frequencies[top] += total - sum;
+        StaticModel::from_frequencies(&frequencies)
+    }
+
+    fn from_frequencies(frequencies: &[Border]) -> StaticModel {
+        let mut cumulative = Vec::with_capacity(frequencies.len()+1);
+        let mut low = 0 as Border;
+        cumulative.push(low);
+        for &f in frequencies.iter() {
+            low += f;
+            cumulative.push(low);
+        }
+        StaticModel::from_cumulative(cumulative)
+    }
+
+    fn from_cumulative(cumulative: Vec<Border>) -> StaticModel {
+        let total = cumulative[cumulative.len() - 1];
+        let mut lookup = Vec::new();
+        if total <= LOOKUP_MAX_TOTAL {
+            lookup.reserve(total as usize);
+            for (value, pair) in cumulative.windows(2).enumerate() {
+                lookup.extend((pair[0]..pair[1]).map(|_| value as u32));
+            }
+        }
+        StaticModel {
+            cumulative: cumulative,
+            lookup: lookup,
+        }
+    }
+
+    /// Find the value by a given probability offset with the binary search,
+    /// bypassing the lookup table. Gives the same result as `find_value`.
+    pub fn search_value(&self, offset: Border) -> (usize,Border,Border) {
+        let total = self.cumulative[self.cumulative.len() - 1];
+        assert!(offset < total,
+            "Invalid frequency offset {} requested under total {}",
+            offset, total);
+        // binary search for the last value with the lower border not exceeding the offset
+        let (mut lo, mut hi) = (0, self.cumulative.len() - 1);
+        while hi - lo > 1 {
+            let mid = (lo + hi) >> 1;
+            if self.cumulative[mid] <= offset {
+                lo = mid;
+            }else {
+                hi = mid;
+            }
+        }
+        (lo, self.cumulative[lo], self.cumulative[lo+1])
+    }
+
+    /// Return the number of values in the model
+    pub fn get_num_values(&self) -> usize {
+        self.cumulative.len() - 1
+    }
+
+    /// List the interval [lo,hi) of every value, empty for the missing ones
+    pub fn dump_intervals(&self) -> Vec<(usize, Border, Border)> {
+        self.cumulative.windows(2).enumerate().map(|(value, w)| (value, w[0], w[1])).collect()
+    }
+
+    #[cfg(feature="io")]
+    /// Serialize the normalized frequencies as varints,
+    /// every zero is followed by the number of zeros repeating it
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_varint(w, self.get_num_values() as u64));
+        let frequencies: Vec<Border> = self.cumulative.windows(2)
+            .map(|pair| pair[1] - pair[0]).collect();
+        let mut i = 0;
+        while i < frequencies.len() {
+            try!(write_varint(w, frequencies[i] as u64));
+            i += 1;
+            if frequencies[i-1] == 0 {
+                let run = frequencies[i..].iter().take_while(|&&f| f == 0).count();
+                try!(write_varint(w, run as u64));
+                i += run;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature="io")]
+    /// Deserialize the model written by `write_to`, checking that
+    /// the frequencies sum up to a valid denominator
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<StaticModel> {
+        let threshold = super::RANGE_DEFAULT_THRESHOLD as u64;
+        let num_values = try!(read_varint(r));
+        if num_values >= threshold {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "Too many values in the static model"))
+        }
+        let mut cumulative = Vec::with_capacity(num_values as usize + 1);
+        let mut low = 0u64;
+        cumulative.push(low as Border);
+        while (cumulative.len() as u64) <= num_values {
+            let freq = try!(read_varint(r));
+            low += freq;
+            if low > threshold {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                    "Static model denominator exceeds the range threshold"))
+            }
+            cumulative.push(low as Border);
+            if freq == 0 {
+                let run = try!(read_varint(r));
+                if run > num_values + 1 - (cumulative.len() as u64) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        "Static model has too many zero frequencies"))
+                }
+                for _ in 0..run {
+                    cumulative.push(low as Border);
+                }
+            }
+        }
+        if low == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "Static model has an empty denominator"))
+        }
+        Ok(StaticModel::from_cumulative(cumulative))
+    }
+}
+
+/// Scale the counts to sum up to no more than the 'total',
+/// keeping positive counts positive
+fn normalize(counts: &[u32], total: Border) -> Vec<Border> {
+    let total = total as u64;
+    let num_values = counts.len() as u64;
+    let sum = counts.iter().fold(0u64, |u,&c| u+(c as u64));
+    assert!(sum > 0, "Empty histogram");
+    assert!(num_values < total,
+        "Too many values {} for the total {}", num_values, total);
+    // reserve one unit per value for rounding up
+    let scale = total - num_values;
+    counts.iter().map(|&c| {
+        if c != 0 {
+            ((c as u64 * scale / sum) + 1) as Border
+        }else {
+            0
+        }
+    }).collect()
+}
+
+#[cfg(feature="io")]
+/// Write an unsigned integer in LEB128 form
+fn write_varint<W: Write>(w: &mut W, mut value: u64) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut n = 0;
+    while value >= 0x80 {
+        buf[n] = (value as u8) | 0x80;
+        value >>= 7;
+        n += 1;
+    }
+    buf[n] = value as u8;
+    w.write_all(&buf[..n+1])
+}
+
+#[cfg(feature="io")]
+/// Read an unsigned integer in LEB128 form
+fn read_varint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..10).map(|i| i*7) {
+        let mut byte = [0u8; 1];
+        try!(r.read_exact(&mut byte));
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "Varint is too long"))
+}
+
+impl super::Model<usize> for StaticModel {
+    fn get_range(&self, value: usize) -> (Border,Border) {
+        (self.cumulative[value], self.cumulative[value+1])
+    }
+
+    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
+        match self.lookup.get(offset as usize) {
+            Some(&value) => {
+                let value = value as usize;
+                (value, self.cumulative[value], self.cumulative[value+1])
+            },
+            None => self.search_value(offset),
+        }
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.cumulative[self.cumulative.len() - 1]
+    }
+
+    fn get_ranges(&self, values: &[usize], out: &mut [(Border,Border)]) {
+        assert_eq!(values.len(), out.len());
+        let cumulative = &self.cumulative[..];
+        for (range, &value) in out.iter_mut().zip(values.iter()) {
+            *range = (cumulative[value], cumulative[value+1]);
+        }
+    }
+}
+
+/// A shared static model, which never adapts anyway
+impl<'a> super::Model<usize> for &'a StaticModel {
+    fn get_range(&self, value: usize) -> (Border,Border) {
+        (**self).get_range(value)
+    }
+
+    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
+        (**self).find_value(offset)
+    }
+
+    fn get_denominator(&self) -> Border {
+        (**self).get_denominator()
+    }
+
+    fn get_ranges(&self, values: &[usize], out: &mut [(Border,Border)]) {
+        (**self).get_ranges(values, out)
+    }
+}
+
+/// An adaptive byte model, starting flat and
+/// increasing the frequency of every coded symbol.
+/// Encoder and decoder have to call `update` with the same values.
+/// The default one uses `DEFAULT_INCREMENT` and `DEFAULT_THRESHOLD`.
+#[derive(Clone)]
+pub struct AdaptiveByteModel {
+    /// underlying frequency table, unused under `InitStrategy::Zero`
+    table: Model,
+    /// frequency to add on each update
+    pub increment: Frequency,
+    /// the seen bytes and the escape to the literals,
+    /// replacing the table under `InitStrategy::Zero`
+    escaped: Option<escape::LiteralFallbackModel>,
+}
+
+/// Initial frequencies of an adaptive model, which mostly affect
+/// the size of the short messages. The decoder has to start the same way.
+#[derive(Clone, Copy, Debug)]
+pub enum InitStrategy<'a> {
+    /// Every symbol starts with the frequency of 1
+    Uniform,
+    /// No symbol has an interval until it's seen: the unseen ones share
+    /// an escape, followed by a uniform literal (see `escape::LiteralFallbackModel`).
+    /// Such a model takes two coded values for a new byte, so it has to go
+    /// through `AdaptiveByteModel::encode` and `decode`.
+    Zero,
+    /// The frequencies follow the counts of some typical data,
+    /// scaled to a half of the threshold and keeping every symbol positive
+    Histogram(&'a [u32]),
+}
+
+/// Frequency increment of the default adaptive models
+pub const DEFAULT_INCREMENT: Frequency = 16;
+/// Downscaling threshold of the default adaptive models
+pub const DEFAULT_THRESHOLD: Border = super::RANGE_DEFAULT_THRESHOLD >> 2;
+
+impl Default for AdaptiveByteModel {
+    fn default() -> AdaptiveByteModel {
+        AdaptiveByteModel::new(DEFAULT_INCREMENT, DEFAULT_THRESHOLD)
+    }
+}
+
+impl AdaptiveByteModel {
+    /// Create a new flat model with a given increment,
+    /// downscaling the frequencies once their sum reaches the 'threshold'
+    pub fn new(increment: Frequency, threshold: Border) -> AdaptiveByteModel {
+        AdaptiveByteModel::with_init(InitStrategy::Uniform, increment, threshold)
+    }
+
+    /// Create a new model starting with the given frequencies,
+    /// see `AdaptiveByteModel::new`
+    pub fn with_init(init: InitStrategy, increment: Frequency, threshold: Border) -> AdaptiveByteModel {
+        assert!((increment as Border) < threshold);
+        let mut escaped = None;
+        let table = match init {
+            InitStrategy::Uniform => Model::new_flat(super::SYMBOL_TOTAL, threshold),
+            InitStrategy::Zero => {
+                escaped = Some(escape::LiteralFallbackModel::new(super::SYMBOL_TOTAL, increment, threshold));
+                Model::new_flat(super::SYMBOL_TOTAL, threshold)
+            },
+            InitStrategy::Histogram(counts) => {
+                assert_eq!(counts.len(), super::SYMBOL_TOTAL);
+                let frequencies = normalize(counts, threshold >> 1);
+                Model::new_custom(super::SYMBOL_TOTAL, threshold,
+                    |i| frequencies[i].max(1) as Frequency)
+            },
+        };
+        AdaptiveByteModel {
+            table: table,
+            increment: increment,
+            escaped: escaped,
+        }
+    }
+
+    /// Adapt the model in favor of a given symbol
+    pub fn update(&mut self, value: super::Symbol) {
+        match self.escaped {
+            Some(ref mut escaped) => {
+                escaped.primary.increment = self.increment;
+                super::Model::update(&mut escaped.primary, value as usize);
+            },
+            None => self.table.add_frequency(value as usize, self.increment as Border),
+        }
+    }
+
+    #[cfg(feature="io")]
+    /// Encode a byte under any initial strategy, escaping
+    /// to the literal if it's new under `InitStrategy::Zero`
+    pub fn encode<W: Write, B: super::RangeBorder>(&mut self, encoder: &mut super::Encoder<W, B>,
+                  value: super::Symbol) -> io::Result<()> {
+        if self.escaped.is_none() {
+            return encoder.encode(value, self).map(|_| ())
+        }
+        let escaped = self.escaped.as_mut().unwrap();
+        escaped.primary.increment = self.increment;
+        escaped.encode(encoder, value as usize)
+    }
+
+    #[cfg(feature="io")]
+    /// Decode a byte written by `AdaptiveByteModel::encode`
+    pub fn decode<R: Read, B: super::RangeBorder>(&mut self, decoder: &mut super::Decoder<R, B>)
+                  -> io::Result<super::Symbol> {
+        if self.escaped.is_none() {
+            return decoder.decode(self)
+        }
+        let escaped = self.escaped.as_mut().unwrap();
+        escaped.primary.increment = self.increment;
+        escaped.decode(decoder).map(|value| value as super::Symbol)
+    }
+
+    /// Adapt the model to the data without coding it,
+    /// the decoder has to be primed with the same data
+    pub fn prime(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.update(byte);
+        }
+    }
+
+    /// Add the statistics learned by another model, like the one
+    /// of a different shard, rescaling them to fit the threshold
+    /// (see `Model::merge`). The decoder has to merge the same models.
+    /// The models have to start with the same strategy kind.
+    pub fn merge(&mut self, other: &AdaptiveByteModel) {
+        match (self.escaped.as_mut(), other.escaped.as_ref()) {
+            (Some(escaped), Some(other)) => escaped.primary.merge(&other.primary),
+            (None, None) => self.table.merge(&other.table),
+            _ => panic!("Can't merge a zero-started model with a non-zero one"),
+        }
+    }
+
+    fn get_table(&self) -> &Model {
+        match self.escaped {
+            Some(ref escaped) => escaped.primary.get_table(),
+            None => &self.table,
+        }
+    }
+
+    /// Return read-only frequencies slice,
+    /// followed by the escape under `InitStrategy::Zero`
+    pub fn get_frequencies(&self) -> &[Frequency] {
+        self.get_table().get_frequencies()
+    }
+
+    /// List the interval [lo,hi) of every byte
+    pub fn dump_intervals(&self) -> Vec<(super::Symbol, Border, Border)> {
+        self.get_table().dump_intervals().into_iter()
+            .filter(|&(value, _, _)| value < super::SYMBOL_TOTAL)
+            .map(|(value, lo, hi)| (value as super::Symbol, lo, hi)).collect()
+    }
+}
+
+/// Under `InitStrategy::Zero` the unseen bytes have an empty interval,
+/// which the encoder refuses, and the escape can't be decoded as a byte:
+/// such a model has to go through `AdaptiveByteModel::encode` and `decode`.
+impl super::Model<super::Symbol> for AdaptiveByteModel {
+    fn get_range(&self, value: super::Symbol) -> (Border,Border) {
+        match self.escaped {
+            Some(ref escaped) if !escaped.primary.is_known(value as usize) => (0, 0),
+            _ => self.get_table().get_range(value as usize),
+        }
+    }
+
+    fn find_value(&self, offset: Border) -> (super::Symbol,Border,Border) {
+        let (value, lo, hi) = self.get_table().find_value(offset);
+        assert!(value < super::SYMBOL_TOTAL,
+            "The escape has to be decoded by AdaptiveByteModel::decode");
+        (value as super::Symbol, lo, hi)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.get_table().get_denominator()
+    }
+
+    fn update(&mut self, value: super::Symbol) {
+        AdaptiveByteModel::update(self, value)
+    }
+}
+
+
+/// A static model giving every value of the alphabet the same probability,
+/// which takes `log2(num_values)` bits per value. A baseline to measure
+/// the overhead of the coder itself.
+#[derive(Clone, Copy, Debug)]
+pub struct UniformModel {
+    num_values: usize,
+}
+
+impl UniformModel {
+    /// Create a new model of the values in [0,num_values)
+    pub fn new(num_values: usize) -> UniformModel {
+        assert!(num_values > 0 && num_values as u64 <= Border::max_value() as u64,
+            "Unsupported alphabet size {}", num_values);
+        UniformModel {
+            num_values: num_values,
+        }
+    }
+
+    /// Return the number of values in the alphabet
+    pub fn get_num_values(&self) -> usize {
+        self.num_values
+    }
+}
+
+impl super::Model<usize> for UniformModel {
+    fn get_range(&self, value: usize) -> (Border,Border) {
+        (value as Border, value as Border + 1)
+    }
+
+    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
+        (offset as usize, offset, offset + 1)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.num_values as Border
+    }
+}
+
+
+/// An adaptive byte model forgetting the history gradually:
+/// the frequencies are halved every `half_life` coded symbols,
+/// so that the recent ones weigh more and the model tracks a drifting source.
+/// The halving is scheduled by the number of updates, which is the same
+/// for the encoder and the decoder.
+#[derive(Clone)]
+pub struct DecayModel {
+    /// underlying frequency table
+    table: Model,
+    /// frequency to add on each update
+    pub increment: Frequency,
+    /// number of updates between the halvings
+    half_life: usize,
+    /// number of updates since the last halving
+    age: usize,
+}
+
+impl DecayModel {
+    /// Create a new flat model halving every 'half_life' symbols,
+    /// with the default increment and threshold (see `AdaptiveByteModel`)
+    pub fn new(half_life: usize) -> DecayModel {
+        assert!(half_life > 0, "The half-life has to be positive");
+        DecayModel {
+            table: Model::new_flat(super::SYMBOL_TOTAL, DEFAULT_THRESHOLD),
+            increment: DEFAULT_INCREMENT,
+            half_life: half_life,
+            age: 0,
+        }
+    }
+
+    /// Return the number of symbols between the halvings
+    pub fn get_half_life(&self) -> usize {
+        self.half_life
+    }
+
+    /// Return read-only frequencies slice
+    pub fn get_frequencies(&self) -> &[Frequency] {
+        self.table.get_frequencies()
+    }
+
+    /// List the interval [lo,hi) of every byte
+    pub fn dump_intervals(&self) -> Vec<(super::Symbol, Border, Border)> {
+        self.table.dump_intervals().into_iter()
+            .map(|(value, lo, hi)| (value as super::Symbol, lo, hi)).collect()
+    }
+}
+
+impl super::Model<super::Symbol> for DecayModel {
+    fn get_range(&self, value: super::Symbol) -> (Border,Border) {
+        self.table.get_range(value as usize)
+    }
+
+    fn find_value(&self, offset: Border) -> (super::Symbol,Border,Border) {
+        let (value, lo, hi) = self.table.find_value(offset);
+        (value as super::Symbol, lo, hi)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.table.get_denominator()
+    }
+
+    fn update(&mut self, value: super::Symbol) {
+        self.table.add_frequency(value as usize, self.increment as Border);
+        self.age += 1;
+        if self.age == self.half_life {
+            self.table.downscale();
+            self.age = 0;
+        }
+    }
+}
+
+
+/// A frequency model over an alphabet of any size, coding `u32` values.
+/// Adds the increment to the frequency of every coded value in `update`,
+/// staying static if the increment is zero.
+#[derive(Clone)]
+pub struct FreqModel {
+    /// underlying frequency table
+    table: Model,
+    /// frequency to add on each update
+    pub increment: Frequency,
+}
+
+impl FreqModel {
+    /// Create a new flat model of 'num_values' values, see `AdaptiveByteModel::new`
+    pub fn new(num_values: usize, increment: Frequency, threshold: Border) -> FreqModel {
+        FreqModel::with_frequencies(&vec![1; num_values], increment, threshold)
+    }
+
+    /// Create a new model with given initial frequencies,
+    /// downscaled to fit below the 'threshold'
+    pub fn with_frequencies(frequencies: &[Frequency], increment: Frequency, threshold: Border) -> FreqModel {
+        assert!(!frequencies.is_empty());
+        assert!((increment as Border) < threshold);
+        FreqModel {
+            table: Model::new_custom(frequencies.len(), threshold, |i| frequencies[i]),
+            increment: increment,
+        }
+    }
+
+    /// Return the number of values in the alphabet
+    pub fn get_num_values(&self) -> usize {
+        self.table.get_frequencies().len()
+    }
+
+    /// Return read-only frequencies slice
+    pub fn get_frequencies(&self) -> &[Frequency] {
+        self.table.get_frequencies()
+    }
+
+    /// List the interval [lo,hi) of every value
+    pub fn dump_intervals(&self) -> Vec<(u32, Border, Border)> {
+        self.table.dump_intervals().into_iter()
+            .map(|(value, lo, hi)| (value as u32, lo, hi)).collect()
+    }
+}
+
+impl super::Model<u32> for FreqModel {
+    fn get_range(&self, value: u32) -> (Border,Border) {
+        self.table.get_range(value as usize)
+    }
+
+    fn find_value(&self, offset: Border) -> (u32,Border,Border) {
+        let (value, lo, hi) = self.table.find_value(offset);
+        (value as u32, lo, hi)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.table.get_denominator()
+    }
+
+    fn update(&mut self, value: u32) {
+        if self.increment != 0 {
+            self.table.add_frequency(value as usize, self.increment as Border);
+        }
+    }
+}
+
+
+#[cfg(feature="io")]
+/// Number of values in a nibble
+const NIBBLE_VALUES: usize = 16;
+
+#[cfg(feature="io")]
+/// A byte model coding the high nibble, then the low nibble
+/// in the context of the high one, with separate adaptive tables
+/// of 16 values. Learns fast on the data skewed per nibble, like hex text.
+/// Takes two coded values per byte, so it works on the coder directly.
+#[derive(Clone)]
+pub struct NibbleModel {
+    /// the table of the high nibble
+    high: FreqModel,
+    /// the tables of the low nibble, indexed by the high one
+    low: Vec<FreqModel>,
+}
+
+#[cfg(feature="io")]
+impl NibbleModel {
+    /// Create a new model with flat tables, see `AdaptiveByteModel::new`
+    pub fn new(increment: Frequency, threshold: Border) -> NibbleModel {
+        NibbleModel {
+            high: FreqModel::new(NIBBLE_VALUES, increment, threshold),
+            low: (0..NIBBLE_VALUES).map(|_|
+                FreqModel::new(NIBBLE_VALUES, increment, threshold)
+                ).collect(),
+        }
+    }
+
+    /// Encode a byte as two nibbles, updating the tables
+    pub fn encode<W: Write, B: super::RangeBorder>(&mut self, encoder: &mut super::Encoder<W, B>,
+                  value: super::Symbol) -> io::Result<()> {
+        let (high, low) = ((value >> 4) as u32, (value & 0xF) as u32);
+        try!(encoder.encode(high, &mut self.high));
+        try!(encoder.encode(low, &mut self.low[high as usize]));
+        Ok(())
+    }
+
+    /// Decode a byte from its two nibbles, updating the tables
+    pub fn decode<R: Read, B: super::RangeBorder>(&mut self, decoder: &mut super::Decoder<R, B>)
+                  -> io::Result<super::Symbol> {
+        let high = try!(decoder.decode(&mut self.high));
+        let low = try!(decoder.decode(&mut self.low[high as usize]));
+        Ok(((high << 4) | low) as super::Symbol)
+    }
+}
+
+/// An order-1 context model, keeping a separate adaptive table
+/// for each value of the previous symbol.
+/// The default one uses the default adaptive tables.
+/// The compact one (see `Order1Model::compact`) keeps byte counters instead.
+#[derive(Clone)]
+pub struct Order1Model {
+    /// adaptive tables, indexed by the context
+    tables: Order1Tables,
+    /// the last coded symbol
+    context: super::Symbol,
+}
+
+#[derive(Clone)]
+enum Order1Tables {
+    Full(Vec<AdaptiveByteModel>),
+    /// counters of all contexts in a row, the frequency being the counter plus 1
+    Compact(Vec<u8>),
+}
+
+/// Counter increment of the compact order-1 model
+const COMPACT_INCREMENT: u8 = 1<<4;
+/// Largest total of a context of the compact order-1 model
+const COMPACT_TOTAL_MAX: Border = super::RANGE_DEFAULT_THRESHOLD >> 2;
+
+impl Default for Order1Model {
+    fn default() -> Order1Model {
+        Order1Model::new(DEFAULT_INCREMENT, DEFAULT_THRESHOLD)
+    }
+}
+
+impl Order1Model {
+    /// Create a new model with flat tables, see `AdaptiveByteModel::new`
+    pub fn new(increment: Frequency, threshold: Border) -> Order1Model {
+        Order1Model {
+            tables: Order1Tables::Full((0..super::SYMBOL_TOTAL).map(|_|
+                AdaptiveByteModel::new(increment, threshold)
+                ).collect()),
+            context: 0,
+        }
+    }
+
+    /// Create a new model with a byte counter per value and context,
+    /// taking 64 KB in total. The counters are halved as soon as one
+    /// of them overflows or the context total gets too large,
+    /// which trades some compression for a much smaller footprint.
+    /// The lookups scan the context linearly.
+    pub fn compact() -> Order1Model {
+        Order1Model {
+            tables: Order1Tables::Compact(vec![0; super::SYMBOL_TOTAL * super::SYMBOL_TOTAL]),
+            context: 0,
+        }
+    }
+
+    /// Return the current context symbol
+    pub fn get_context(&self) -> super::Symbol {
+        self.context
+    }
+
+    /// Return the number of bytes taken by the tables
+    pub fn memory_size(&self) -> usize {
+        match self.tables {
+            Order1Tables::Full(ref tables) => tables.iter().map(|t| t.table.memory_size()).sum(),
+            Order1Tables::Compact(ref counters) => counters.len(),
+        }
+    }
+
+    /// Return the counters of the current context in the compact mode
+    fn compact_counters(counters: &[u8], context: super::Symbol) -> &[u8] {
+        let start = context as usize * super::SYMBOL_TOTAL;
+        &counters[start .. start + super::SYMBOL_TOTAL]
+    }
+}
+
+impl super::Model<super::Symbol> for Order1Model {
+    fn get_range(&self, value: super::Symbol) -> (Border,Border) {
+        match self.tables {
+            Order1Tables::Full(ref tables) => tables[self.context as usize].get_range(value),
+            Order1Tables::Compact(ref counters) => {
+                let counters = Order1Model::compact_counters(counters, self.context);
+                let lo = counters[..value as usize].iter().fold(value as Border, |u,&c| u + c as Border);
+                (lo, lo + counters[value as usize] as Border + 1)
+            },
+        }
+    }
+
+    fn find_value(&self, offset: Border) -> (super::Symbol,Border,Border) {
+        match self.tables {
+            Order1Tables::Full(ref tables) => tables[self.context as usize].find_value(offset),
+            Order1Tables::Compact(ref counters) => {
+                let counters = Order1Model::compact_counters(counters, self.context);
+                let mut lo = 0;
+                for (value, &c) in counters.iter().enumerate() {
+                    let hi = lo + c as Border + 1;
+                    if offset < hi {
+                        return (value as super::Symbol, lo, hi)
+                    }
+                    lo = hi;
+                }
+                panic!("Offset {} exceeds the total {}", offset, lo)
+            },
+        }
+    }
+
+    fn get_denominator(&self) -> Border {
+        match self.tables {
+            Order1Tables::Full(ref tables) => tables[self.context as usize].get_denominator(),
+            Order1Tables::Compact(ref counters) => {
+                let counters = Order1Model::compact_counters(counters, self.context);
+                counters.iter().fold(super::SYMBOL_TOTAL as Border, |u,&c| u + c as Border)
+            },
+        }
+    }
+
+    fn update(&mut self, value: super::Symbol) {
+        let total = self.get_denominator();
+        match self.tables {
+            Order1Tables::Full(ref mut tables) => tables[self.context as usize].update(value),
+            Order1Tables::Compact(ref mut counters) => {
+                let start = self.context as usize * super::SYMBOL_TOTAL;
+                let counters = &mut counters[start .. start + super::SYMBOL_TOTAL];
+                if counters[value as usize] > !0 - COMPACT_INCREMENT ||
+                    total + COMPACT_INCREMENT as Border > COMPACT_TOTAL_MAX {
+                    for c in counters.iter_mut() {
+                        *c >>= 1;
+                    }
+                }
+                counters[value as usize] += COMPACT_INCREMENT;
+            },
+        }
+        self.context = value;
+    }
+}
+
+
+/// An order-N context model, hashing the last `order` symbols into
+/// a table of `1<<table_bits` adaptive tables, allocated on the first use.
+/// Colliding contexts share a table, which only costs compression.
+#[derive(Clone)]
+pub struct OrderNModel {
+    /// adaptive tables, indexed by the context hash
+    tables: Vec<Option<AdaptiveByteModel>>,
+    /// the table for the contexts seen for the first time
+    flat: AdaptiveByteModel,
+    /// the last coded symbols, the latest in the lowest byte
+    history: u32,
+    /// mask of the history bytes in the context
+    mask: u32,
+    table_bits: usize,
+    increment: Frequency,
+    threshold: Border,
+}
+
+/// Multiplier of the Fibonacci hashing
+const HASH_MULTIPLIER: u32 = 0x9E3779B1;
+
+impl OrderNModel {
+    /// Create a new model of an order from 1 to 4 with `1<<table_bits` tables,
+    /// each starting flat, see `AdaptiveByteModel::new`
+    pub fn new(order: usize, table_bits: usize, increment: Frequency, threshold: Border) -> OrderNModel {
+        assert!(order >= 1 && order <= 4, "Unsupported order {}", order);
+        assert!(table_bits >= 1 && table_bits <= 24, "Unsupported table bits {}", table_bits);
+        OrderNModel {
+            tables: (0..1<<table_bits).map(|_| None).collect(),
+            flat: AdaptiveByteModel::new(increment, threshold),
+            history: 0,
+            mask: (!0u32) >> (32 - 8*order),
+            table_bits: table_bits,
+            increment: increment,
+            threshold: threshold,
+        }
+    }
+
+    /// Return the table index of the current context
+    fn get_index(&self) -> usize {
+        ((self.history & self.mask).wrapping_mul(HASH_MULTIPLIER) >> (32 - self.table_bits)) as usize
+    }
+
+    fn get_table(&self) -> &AdaptiveByteModel {
+        match self.tables[self.get_index()] {
+            Some(ref table) => table,
+            None => &self.flat,
+        }
+    }
+}
+
+impl super::Model<super::Symbol> for OrderNModel {
+    fn get_range(&self, value: super::Symbol) -> (Border,Border) {
+        self.get_table().get_range(value)
+    }
+
+    fn find_value(&self, offset: Border) -> (super::Symbol,Border,Border) {
+        self.get_table().find_value(offset)
+    }
+
+    fn get_denominator(&self) -> Border {
+        self.get_table().get_denominator()
+    }
+
+    fn update(&mut self, value: super::Symbol) {
+        let index = self.get_index();
+        let (increment, threshold) = (self.increment, self.threshold);
+        self.tables[index].get_or_insert_with(||
+            AdaptiveByteModel::new(increment, threshold)
+            ).update(value);
+        self.history = (self.history << 8) | value as u32;
     }
+}
 
-    /// Adapt the table in favor of given 'value'
-    /// using 'add_log' and 'add_const' to produce the additive factor
-    /// the higher 'add_log' is, the more concervative is the adaptation
-    pub fn update(&mut self, value: usize, add_log: usize, add_const: Border) {
-        let add = (self.total>>add_log) + add_const;
-        assert!(add < 2*self.cut_threshold);
-        debug!("\tUpdating by adding {} to value {}", add, value);
-        self.table[value] += add as Frequency;
-        self.total += add;
-        if self.total >= self.cut_threshold {
-            self.downscale();
-            assert!(self.total < self.cut_threshold);
+
+/// Number of bits in the table index of `WindowModel`
+const WINDOW_TABLE_BITS: usize = 16;
+/// Multiplier of the rolling hash
+const ROLLING_MULTIPLIER: u32 = 0x01000193;
+/// Increment of the `WindowModel` tables: the long contexts
+/// tend to be deterministic, so they adapt fast
+const WINDOW_INCREMENT: Frequency = 1<<10;
+
+/// A high-order context model, hashing the last `window_bytes` symbols
+/// with a rolling hash into `1<<16` adaptive tables, allocated on the first use.
+/// Suits the literals of an LZ stage: the encoder and the decoder keep
+/// the same window, so long repeats get predicted from their context.
+#[derive(Clone)]
+pub struct WindowModel {
+    /// adaptive tables, indexed by the context hash
+    tables: Vec<Option<AdaptiveByteModel>>,
+    /// the table for the contexts seen for the first time
+    flat: AdaptiveByteModel,
+    /// the last coded symbols, starting as zeroes
+    window: Vec<super::Symbol>,
+    /// position of the oldest symbol in the window
+    position: usize,
+    /// rolling hash of the window
+    hash: u32,
+    /// the weight of the oldest symbol in the hash
+    power: u32,
+    /// frequency increment of the context tables, changing it affects
+    /// only the tables allocated afterwards, for the contexts not seen yet
+    pub increment: Frequency,
+    /// downscaling threshold of the context tables, changing it affects
+    /// only the tables allocated afterwards, for the contexts not seen yet
+    pub threshold: Border,
+}
+
+impl WindowModel {
+    /// Create a new model with a context of the last `window_bytes` symbols,
+    /// the tables starting flat and adapting by a large increment
+    pub fn new(window_bytes: usize) -> WindowModel {
+        assert!(window_bytes > 0, "The window can't be empty");
+        WindowModel {
+            tables: (0..1<<WINDOW_TABLE_BITS).map(|_| None).collect(),
+            flat: AdaptiveByteModel::new(WINDOW_INCREMENT, DEFAULT_THRESHOLD),
+            window: vec![0; window_bytes],
+            position: 0,
+            hash: 0,
+            power: (0..window_bytes).fold(1u32, |p, _| p.wrapping_mul(ROLLING_MULTIPLIER)),
+            increment: WINDOW_INCREMENT,
+            threshold: DEFAULT_THRESHOLD,
         }
     }
 
-    /// Reduce frequencies by 'cut_iter' bits
-    pub fn downscale(&mut self) {
-        debug!("\tDownscaling frequencies");
-        let roundup = (1<<self.cut_shift) - 1;
-        self.total = 0;
-        for freq in self.table.iter_mut() {
-            // preserve non-zero frequencies to remain positive
-            *freq = (*freq+roundup) >> self.cut_shift;
-            self.total += *freq as Border;
-        }
+    /// Return the number of symbols in the context
+    pub fn get_window_bytes(&self) -> usize {
+        self.window.len()
     }
 
-    /// Return read-only frequencies slice
-    pub fn get_frequencies<'a>(&'a self) -> &'a [Frequency] {
-        &self.table[..]
+    /// Return the table index of the current context
+    fn get_index(&self) -> usize {
+        (self.hash.wrapping_mul(HASH_MULTIPLIER) >> (32 - WINDOW_TABLE_BITS)) as usize
+    }
+
+    fn get_table(&self) -> &AdaptiveByteModel {
+        match self.tables[self.get_index()] {
+            Some(ref table) => table,
+            None => &self.flat,
+        }
     }
 }
 
-impl super::Model<usize> for Model {
-    fn get_range(&self, value: usize) -> (Border,Border) {
-        let lo = self.table[..value].iter().fold(0, |u,&f| u+(f as Border));
-        (lo, lo + (self.table[value] as Border))
+impl super::Model<super::Symbol> for WindowModel {
+    fn get_range(&self, value: super::Symbol) -> (Border,Border) {
+        self.get_table().get_range(value)
     }
 
-    fn find_value(&self, offset: Border) -> (usize,Border,Border) {
-        assert!(offset < self.total,
-            "Invalid frequency offset {} requested under total {}",
-            offset, self.total);
-        let mut value = 0;
-        let mut lo = 0 as Border;
-        let mut hi;
-        while {hi=lo+(self.table[value] as Border); hi} <= offset {
-            lo = hi;
-            value += 1;
-        }
-        (value, lo, hi)
+    fn find_value(&self, offset: Border) -> (super::Symbol,Border,Border) {
+        self.get_table().find_value(offset)
     }
 
     fn get_denominator(&self) -> Border {
-        self.total
+        self.get_table().get_denominator()
     }
-}
 
+    fn update(&mut self, value: super::Symbol) {
+        let index = self.get_index();
+        let (increment, threshold) = (self.increment, self.threshold);
+        self.tables[index].get_or_insert_with(||
+            AdaptiveByteModel::new(increment, threshold)
+            ).update(value);
+        // roll the oldest symbol out of the hash
+        let oldest = self.window[self.position];
+        self.hash = self.hash.wrapping_mul(ROLLING_MULTIPLIER)
+            .wrapping_add(value as u32)
+            .wrapping_sub((oldest as u32).wrapping_mul(self.power));
+        self.window[self.position] = value;
+        self.position = (self.position + 1) % self.window.len();
+    }
+}
 
 /// A proxy model for the sum of two frequency tables
 /// using equation: (wa * A + wb * B) >> ws
@@ -146,29 +1263,34 @@ impl<'a> SumProxy<'a> {
     }
 }
 
+impl<'a> SumProxy<'a> {
+    /// Return the mixed cumulative frequency of all values below 'value'
+    fn get_cumulative(&self, value: usize) -> Border {
+        let (wa, wb, ws) = (self.w_first, self.w_second, self.w_shift as usize);
+        (wa*self.first.get_cumulative(value) + wb*self.second.get_cumulative(value)) >> ws
+    }
+}
+
 impl<'a> super::Model<usize> for SumProxy<'a> {
     fn get_range(&self, value: usize) -> (Border,Border) {
-        let (lo0, hi0) = self.first.get_range(value);
-        let (lo1, hi1) = self.second.get_range(value);
-        let (wa, wb, ws) = (self.w_first, self.w_second, self.w_shift as usize);
-        ((wa*lo0 + wb*lo1)>>ws, (wa*hi0 + wb*hi1)>>ws)
+        (self.get_cumulative(value), self.get_cumulative(value+1))
     }
 
     fn find_value(&self, offset: Border) -> (usize,Border,Border) {
         assert!(offset < self.get_denominator(),
             "Invalid frequency offset {} requested under total {}",
             offset, self.get_denominator());
-        let mut value = 0;
-        let mut lo = 0 as Border;
-        let mut hi;
-        while {  hi = lo +
-                (self.w_first * (self.first.get_frequencies()[value] as Border) +
-                self.w_second * (self.second.get_frequencies()[value] as Border)) >>
-                (self.w_shift as usize);
-                hi <= offset } {
-            lo = hi;
-            value += 1;
+        // binary search for the last value with the cumulative not exceeding the offset
+        let (mut value, mut end) = (0, self.first.get_frequencies().len());
+        while value + 1 < end {
+            let middle = (value + end) >> 1;
+            if self.get_cumulative(middle) <= offset {
+                value = middle;
+            }else {
+                end = middle;
+            }
         }
+        let (lo, hi) = self.get_range(value);
         (value, lo, hi)
     }
 
@@ -180,15 +1302,21 @@ impl<'a> super::Model<usize> for SumProxy<'a> {
 }
 
 
+#[cfg(feature="io")]
 /// A basic byte-encoding arithmetic
 /// uses a special terminator code to end the stream
+/// Implements `Write`, so it can be plugged into any writing pipeline.
 pub struct ByteEncoder<W> {
     /// A lower level encoder
     pub encoder: super::Encoder<W>,
     /// A basic frequency table
     pub freq: Model,
+    /// Checksum of the input
+    #[cfg(feature="checksum")]
+    checksum: adler::State32,
 }
 
+#[cfg(feature="io")]
 impl<W: Write> ByteEncoder<W> {
     /// Create a new encoder on top of a given Writer
     pub fn new(w: W) -> ByteEncoder<W> {
@@ -196,36 +1324,82 @@ impl<W: Write> ByteEncoder<W> {
         ByteEncoder {
             encoder: super::Encoder::new(w),
             freq: Model::new_flat(super::SYMBOL_TOTAL+1, freq_max),
+            #[cfg(feature="checksum")]
+            checksum: adler::State32::new(),
         }
     }
 
     /// Finish encoding & write the terminator symbol
     pub fn finish(mut self) -> (W, io::Result<()>) {
-        let ret = self.encoder.encode(super::SYMBOL_TOTAL, &self.freq);
+        let ret = self.encoder.encode(super::SYMBOL_TOTAL, &mut self.freq);
         let (w,r2) = self.encoder.finish();
         (w, ret.and(r2))
     }
+
+    /// Finish encoding & write the terminator symbol with a padded tail,
+    /// allowing other data to follow the stream
+    pub fn finish_padded(mut self) -> (W, io::Result<()>) {
+        let ret = self.encoder.encode(super::SYMBOL_TOTAL, &mut self.freq);
+        let (w,r2) = self.encoder.finish_padded();
+        (w, ret.and(r2))
+    }
+
+    /// Code the whole input with a static model, built from its histogram
+    /// and written ahead of the code (see `ByteDecoder::two_pass`)
+    pub fn two_pass(mut w: W, input: &[u8]) -> (W, io::Result<()>) {
+        let mut counts = vec![0u32; super::SYMBOL_TOTAL+1];
+        for &byte in input.iter() {
+            counts[byte as usize] += 1;
+        }
+        counts[super::SYMBOL_TOTAL] = 1;
+        let mut model = StaticModel::from_histogram(&counts);
+        if let Err(e) = model.write_to(&mut w) {
+            return (w, Err(e))
+        }
+        let mut encoder = super::Encoder::new(w);
+        let values: Vec<usize> = input.iter().map(|&b| b as usize)
+            .chain(Some(super::SYMBOL_TOTAL)).collect();
+        let ret = encoder.encode_all(&values, &mut model);
+        let (w, r2) = encoder.finish();
+        (w, ret.and(r2))
+    }
+
+    /// Finish encoding & write the terminator symbol,
+    /// followed by the Adler-32 checksum of the input
+    #[cfg(feature="checksum")]
+    pub fn finish_with_checksum(self) -> (W, io::Result<()>) {
+        let checksum = self.checksum.result();
+        let (mut w, ret) = self.finish_padded();
+        let bytes = [(checksum>>24) as u8, (checksum>>16) as u8, (checksum>>8) as u8, checksum as u8];
+        let ret = ret.and_then(|_| w.write_all(&bytes)).and_then(|_| w.flush());
+        (w, ret)
+    }
 }
 
+#[cfg(feature="io")]
 impl<W: Write> Write for ByteEncoder<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         for byte in buf.iter() {
             let value = *byte as usize;
-            try!(self.encoder.encode(value, &self.freq));
+            try!(self.encoder.encode(value, &mut self.freq));
             self.freq.update(value, 10, 1);
         }
-
+        #[cfg(feature="checksum")]
+        self.checksum.feed(buf);
         Ok(buf.len())
     }
 
+    /// Flush the underlying stream, the range is kept open
     fn flush(&mut self) -> io::Result<()> {
         self.encoder.flush()
     }
 }
 
 
+#[cfg(feature="io")]
 /// A basic byte-decoding arithmetic
 /// expects a special terminator code for the end of the stream
+/// Implements `Read`, reporting the end of file after the terminator.
 pub struct ByteDecoder<R> {
     /// A lower level decoder
     pub decoder: super::Decoder<R>,
@@ -233,8 +1407,12 @@ pub struct ByteDecoder<R> {
     pub freq: Model,
     /// Remember if we found the terminator code
     is_eof: bool,
+    /// Checksum of the output
+    #[cfg(feature="checksum")]
+    checksum: adler::State32,
 }
 
+#[cfg(feature="io")]
 impl<R: Read> ByteDecoder<R> {
     /// Create a decoder on top of a given Reader
     pub fn new(r: R) -> ByteDecoder<R> {
@@ -243,6 +1421,8 @@ impl<R: Read> ByteDecoder<R> {
             decoder: super::Decoder::new(r),
             freq: Model::new_flat(super::SYMBOL_TOTAL+1, freq_max),
             is_eof: false,
+            #[cfg(feature="checksum")]
+            checksum: adler::State32::new(),
         }
     }
 
@@ -250,8 +1430,56 @@ impl<R: Read> ByteDecoder<R> {
     pub fn finish(self) -> (R, io::Result<()>) {
         self.decoder.finish()
     }
+
+    /// Decode the whole stream written by `ByteEncoder::two_pass`
+    pub fn two_pass(mut r: R) -> io::Result<Vec<u8>> {
+        let mut model = try!(StaticModel::read_from(&mut r));
+        if model.get_num_values() != super::SYMBOL_TOTAL+1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "Static model doesn't fit the byte alphabet"))
+        }
+        let mut decoder = super::Decoder::new(r);
+        let mut output = Vec::new();
+        loop {
+            let value = try!(decoder.decode(&mut model));
+            if value == super::SYMBOL_TOTAL {
+                break
+            }
+            output.push(value as u8);
+        }
+        let (_, result) = decoder.finish();
+        result.map(|_| output)
+    }
+
+    /// Return the running Adler-32 checksum of the bytes decoded so far,
+    /// to compare against a stored one without another pass over the output.
+    /// Maintained only with the `checksum` feature.
+    #[cfg(feature="checksum")]
+    pub fn checksum(&self) -> u32 {
+        self.checksum.result()
+    }
+
+    /// Finish decoding & verify the checksum written by
+    /// `ByteEncoder::finish_with_checksum` against the decoded bytes
+    #[cfg(feature="checksum")]
+    pub fn finish_with_checksum(self) -> (R, io::Result<()>) {
+        let checksum = self.checksum.result();
+        let (mut r, ret) = self.decoder.finish();
+        let mut bytes = [0u8; 4];
+        let ret = ret.and_then(|_| r.read_exact(&mut bytes)).and_then(|_| {
+            let expected = (bytes[0] as u32)<<24 | (bytes[1] as u32)<<16 |
+                (bytes[2] as u32)<<8 | (bytes[3] as u32);
+            if expected == checksum {
+                Ok(())
+            }else {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "checksum mismatch"))
+            }
+        });
+        (r, ret)
+    }
 }
 
+#[cfg(feature="io")]
 impl<R: Read> Read for ByteDecoder<R> {
     fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
         if self.is_eof {
@@ -259,7 +1487,7 @@ impl<R: Read> Read for ByteDecoder<R> {
         }
         let mut amount = 0;
         for out_byte in dst.iter_mut() {
-            let value = try!(self.decoder.decode(&self.freq));
+            let value = try!(self.decoder.decode(&mut self.freq));
             if value == super::SYMBOL_TOTAL {
                 self.is_eof = true;
                 break
@@ -268,6 +1496,368 @@ impl<R: Read> Read for ByteDecoder<R> {
             *out_byte = value as u8;
             amount += 1;
         }
+        #[cfg(feature="checksum")]
+        self.checksum.feed(&dst[..amount]);
+        Ok(amount)
+    }
+}
+
+
+#[cfg(feature="io")]
+/// A byte encoder coding against a preset dictionary: a static model
+/// of the bytes and the terminator, shared with the decoder out of band
+/// (see `StaticModel::read_from`). Nothing but the code is written,
+/// which suits many small messages of the same kind.
+/// Bytes missing from the dictionary can't be coded.
+pub struct DictByteEncoder<'a, W> {
+    encoder: super::Encoder<W>,
+    model: &'a StaticModel,
+}
+
+#[cfg(feature="io")]
+/// Check that the model covers the bytes and the terminator
+fn check_dictionary(model: &StaticModel) {
+    assert_eq!(model.get_num_values(), super::SYMBOL_TOTAL+1,
+        "The dictionary has to cover the bytes and the terminator");
+}
+
+#[cfg(feature="io")]
+impl<'a, W: Write> DictByteEncoder<'a, W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W, model: &'a StaticModel) -> DictByteEncoder<'a, W> {
+        check_dictionary(model);
+        DictByteEncoder {
+            encoder: super::Encoder::new(w),
+            model: model,
+        }
+    }
+
+    /// Finish encoding & write the terminator symbol
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let ret = self.encode(super::SYMBOL_TOTAL);
+        let (w,r2) = self.encoder.finish();
+        (w, ret.and(r2))
+    }
+
+    fn encode(&mut self, value: usize) -> io::Result<usize> {
+        let (lo, hi) = super::Model::get_range(self.model, value);
+        if lo == hi {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "byte is missing from the dictionary"))
+        }
+        self.encoder.encode(value, &mut self.model)
+    }
+}
+
+#[cfg(feature="io")]
+impl<'a, W: Write> Write for DictByteEncoder<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf.iter() {
+            try!(self.encode(*byte as usize));
+        }
+        Ok(buf.len())
+    }
+
+    /// Flush the underlying stream, the range is kept open
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+#[cfg(feature="io")]
+/// A byte decoder of the messages written by `DictByteEncoder`
+/// with the same dictionary
+pub struct DictByteDecoder<'a, R> {
+    decoder: super::Decoder<R>,
+    model: &'a StaticModel,
+    is_eof: bool,
+}
+
+#[cfg(feature="io")]
+impl<'a, R: Read> DictByteDecoder<'a, R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R, model: &'a StaticModel) -> DictByteDecoder<'a, R> {
+        check_dictionary(model);
+        DictByteDecoder {
+            decoder: super::Decoder::new(r),
+            model: model,
+            is_eof: false,
+        }
+    }
+
+    /// Finish decoding
+    pub fn finish(self) -> (R, io::Result<()>) {
+        self.decoder.finish()
+    }
+}
+
+#[cfg(feature="io")]
+impl<'a, R: Read> Read for DictByteDecoder<'a, R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        if self.is_eof {
+            return Ok(0)
+        }
+        let mut amount = 0;
+        for out_byte in dst.iter_mut() {
+            let value = try!(self.decoder.decode(&mut self.model));
+            if value == super::SYMBOL_TOTAL {
+                self.is_eof = true;
+                break
+            }
+            *out_byte = value as u8;
+            amount += 1;
+        }
+        Ok(amount)
+    }
+}
+
+
+#[cfg(feature="io")]
+/// A byte encoder resetting its frequency table every `block_size` bytes,
+/// so the statistics of old data don't linger.
+/// Nothing extra is written, the decoder resets at the same boundaries.
+pub struct BlockByteEncoder<W> {
+    /// A lower level encoder
+    pub encoder: super::Encoder<W>,
+    /// A frequency table, adapting by `BLOCK_INCREMENT`
+    pub freq: Model,
+    block_size: usize,
+    position: usize,
+}
+
+#[cfg(feature="io")]
+/// Frequency increment of the block coders, adapting fast after a reset
+const BLOCK_INCREMENT: Border = 32;
+
+#[cfg(feature="io")]
+impl<W: Write> BlockByteEncoder<W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W, block_size: usize) -> BlockByteEncoder<W> {
+        assert!(block_size > 0);
+        BlockByteEncoder {
+            encoder: super::Encoder::new(w),
+            freq: Model::new_flat(super::SYMBOL_TOTAL+1, super::RANGE_DEFAULT_THRESHOLD),
+            block_size: block_size,
+            position: 0,
+        }
+    }
+
+    /// Finish encoding & write the terminator symbol
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let ret = self.encoder.encode(super::SYMBOL_TOTAL, &mut self.freq);
+        let (w,r2) = self.encoder.finish();
+        (w, ret.and(r2))
+    }
+}
+
+#[cfg(feature="io")]
+impl<W: Write> Write for BlockByteEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf.iter() {
+            let value = *byte as usize;
+            try!(self.encoder.encode(value, &mut self.freq));
+            self.freq.add_frequency(value, BLOCK_INCREMENT);
+            self.position += 1;
+            if self.position == self.block_size {
+                self.freq.reset_flat();
+                self.position = 0;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Flush the underlying stream, the range is kept open
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+#[cfg(feature="io")]
+/// A byte decoder resetting its frequency table every `block_size` bytes,
+/// matching the `BlockByteEncoder`
+pub struct BlockByteDecoder<R> {
+    /// A lower level decoder
+    pub decoder: super::Decoder<R>,
+    /// A frequency table, adapting by `BLOCK_INCREMENT`
+    pub freq: Model,
+    block_size: usize,
+    position: usize,
+    /// Remember if we found the terminator code
+    is_eof: bool,
+}
+
+#[cfg(feature="io")]
+impl<R: Read> BlockByteDecoder<R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R, block_size: usize) -> BlockByteDecoder<R> {
+        assert!(block_size > 0);
+        BlockByteDecoder {
+            decoder: super::Decoder::new(r),
+            freq: Model::new_flat(super::SYMBOL_TOTAL+1, super::RANGE_DEFAULT_THRESHOLD),
+            block_size: block_size,
+            position: 0,
+            is_eof: false,
+        }
+    }
+
+    /// Finish decoding
+    pub fn finish(self) -> (R, io::Result<()>) {
+        self.decoder.finish()
+    }
+}
+
+#[cfg(feature="io")]
+impl<R: Read> Read for BlockByteDecoder<R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        if self.is_eof {
+            return Ok(0)
+        }
+        let mut amount = 0;
+        for out_byte in dst.iter_mut() {
+            let value = try!(self.decoder.decode(&mut self.freq));
+            if value == super::SYMBOL_TOTAL {
+                self.is_eof = true;
+                break
+            }
+            self.freq.add_frequency(value, BLOCK_INCREMENT);
+            self.position += 1;
+            if self.position == self.block_size {
+                self.freq.reset_flat();
+                self.position = 0;
+            }
+            *out_byte = value as u8;
+            amount += 1;
+        }
         Ok(amount)
     }
 }
+
+
+#[cfg(feature="io")]
+/// Apply the function to all the items, splitting them into
+/// contiguous groups processed by separate threads
+fn map_parallel<T, F>(items: &[T], num_threads: usize, fun: F) -> io::Result<Vec<Vec<u8>>>
+    where T: Sync, F: Fn(&T) -> io::Result<Vec<u8>> + Sync
+{
+    if items.is_empty() {
+        return Ok(Vec::new())
+    }
+    let group = (items.len() + num_threads - 1) / num_threads;
+    let fun = &fun;
+    let results: Vec<io::Result<Vec<Vec<u8>>>> = thread::scope(|scope| {
+        let workers: Vec<_> = items.chunks(group).map(|chunk| {
+            scope.spawn(move || chunk.iter().map(fun).collect())
+        }).collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    let mut output = Vec::with_capacity(items.len());
+    for result in results.into_iter() {
+        output.extend(try!(result));
+    }
+    Ok(output)
+}
+
+#[cfg(feature="io")]
+/// A byte encoder splitting the input into blocks of `block_size` bytes,
+/// coded by separate threads into self-contained `ByteEncoder` frames.
+/// The stream starts with the number of frames and their lengths, as varints,
+/// so that the decoder can split the work as well (see `ParallelByteDecoder`).
+/// Implements `Write`, buffering the input until `finish`.
+pub struct ParallelByteEncoder<W> {
+    w: W,
+    block_size: usize,
+    num_threads: usize,
+    input: Vec<u8>,
+}
+
+#[cfg(feature="io")]
+impl<W: Write> ParallelByteEncoder<W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W, block_size: usize, num_threads: usize) -> ParallelByteEncoder<W> {
+        assert!(block_size > 0 && num_threads > 0);
+        ParallelByteEncoder {
+            w: w,
+            block_size: block_size,
+            num_threads: num_threads,
+            input: Vec::new(),
+        }
+    }
+
+    /// Encode the buffered input and write out the frames
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let result = self.write_frames();
+        (self.w, result)
+    }
+
+    fn write_frames(&mut self) -> io::Result<()> {
+        let blocks: Vec<&[u8]> = self.input.chunks(self.block_size).collect();
+        let frames = try!(map_parallel(&blocks, self.num_threads, |block| {
+            let mut e = ByteEncoder::new(Vec::new());
+            try!(e.write_all(block));
+            let (frame, result) = e.finish();
+            result.map(|_| frame)
+        }));
+        try!(write_varint(&mut self.w, frames.len() as u64));
+        for frame in frames.iter() {
+            try!(write_varint(&mut self.w, frame.len() as u64));
+        }
+        for frame in frames.iter() {
+            try!(self.w.write_all(frame));
+        }
+        self.w.flush()
+    }
+}
+
+#[cfg(feature="io")]
+impl<W: Write> Write for ParallelByteEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Nothing is written before `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature="io")]
+/// A decoder of the frames written by `ParallelByteEncoder`,
+/// decoding them by separate threads
+pub struct ParallelByteDecoder<R> {
+    r: R,
+    num_threads: usize,
+}
+
+#[cfg(feature="io")]
+impl<R: Read> ParallelByteDecoder<R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R, num_threads: usize) -> ParallelByteDecoder<R> {
+        assert!(num_threads > 0);
+        ParallelByteDecoder {
+            r: r,
+            num_threads: num_threads,
+        }
+    }
+
+    /// Read all the frames and decode them
+    pub fn decode(mut self) -> io::Result<Vec<u8>> {
+        let num_frames = try!(read_varint(&mut self.r));
+        let mut lengths = Vec::new();
+        for _ in 0..num_frames {
+            lengths.push(try!(read_varint(&mut self.r)));
+        }
+        let mut frames = Vec::with_capacity(lengths.len());
+        for &length in lengths.iter() {
+            let mut frame = Vec::new();
+            try!(self.r.push_exactly(length, &mut frame));
+            frames.push(frame);
+        }
+        let blocks = try!(map_parallel(&frames, self.num_threads, |frame| {
+            let mut block = Vec::new();
+            try!(ByteDecoder::new(&frame[..]).read_to_end(&mut block));
+            Ok(block)
+        }));
+        Ok(blocks.concat())
+    }
+}
@@ -1,9 +1,10 @@
-use std::io::{BufReader, BufWriter, Write, Read};
+use std::io::{self, BufReader, BufWriter, Write, Read};
 use std::vec::Vec;
 #[cfg(feature="unstable")]
 use test::Bencher;
 
 static TEXT_INPUT: &'static [u8] = include_bytes!("../../data/test.txt");
+static LARGE_INPUT: &'static [u8] = include_bytes!("../../data/test.large");
 
 fn roundtrip(bytes: &[u8]) {
     info!("Roundtrip Ari of size {}", bytes.len());
@@ -41,7 +42,7 @@ fn roundtrip_binary(bytes: &[u8], factor: u32) {
     for &byte in bytes.iter() {
         let mut value = 0u8;
         for i in 0..8 {
-            let bit = decoder.decode(&bm).unwrap();
+            let bit = decoder.decode(&mut bm).unwrap();
             bm.update(bit);
             value += (bit as u8)<<i;
         }
@@ -54,7 +55,7 @@ fn roundtrip_term(bytes1: &[u8], bytes2: &[u8]) {
     let mw = {
         let mut e = super::table::ByteEncoder::new(mw);
         e.write(bytes1).unwrap();
-        let (stream, rez) = e.finish();
+        let (stream, rez) = e.finish_padded();
         rez.unwrap();
         stream
     };
@@ -88,6 +89,44 @@ fn roundtrip_term(bytes1: &[u8], bytes2: &[u8]) {
     };
 }
 
+fn roundtrip_tail(bytes: &[u8]) {
+    let encode = |padded: bool| {
+        let mut e = super::table::ByteEncoder::new(BufWriter::new(Vec::new()));
+        e.write_all(bytes).unwrap();
+        let (e, r) = if padded {e.finish_padded()} else {e.finish()};
+        r.unwrap();
+        e.into_inner().unwrap()
+    };
+    let short = encode(false);
+    let padded = encode(true);
+    assert!(short.len() < padded.len());
+    for encoded in [short, padded].iter() {
+        let mut d = super::ByteDecoder::new(BufReader::new(&encoded[..]));
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&bytes[..], &decoded[..]);
+    }
+}
+
+/// Encode the values with one model instance, decode with another,
+/// and return the encoded size. Adaptation is done by the coder.
+fn roundtrip_model<V, M>(values: &[V], mut encoder_model: M, mut decoder_model: M) -> usize
+    where V: Copy + PartialEq + ::std::fmt::Display + ::std::fmt::Debug, M: super::Model<V>
+{
+    let mut encoder = super::Encoder::new(BufWriter::new(Vec::new()));
+    for &value in values.iter() {
+        encoder.encode(value, &mut encoder_model).unwrap();
+    }
+    let (writer, err) = encoder.finish();
+    err.unwrap();
+    let output = writer.into_inner().unwrap();
+    let mut decoder = super::Decoder::new(BufReader::new(&output[..]));
+    for &value in values.iter() {
+        assert_eq!(decoder.decode(&mut decoder_model).unwrap(), value);
+    }
+    output.len()
+}
+
 fn roundtrip_proxy(bytes: &[u8]) {
     // prepare data
     let update0 = 10;
@@ -102,16 +141,16 @@ fn roundtrip_proxy(bytes: &[u8]) {
     for &byte in bytes.iter() {
         let high = (byte>>4) as usize;
         {
-            let proxy = super::table::SumProxy::new(2, &t0, 1, &t1, 0);
-            encoder.encode(high, &proxy).unwrap();
+            let mut proxy = super::table::SumProxy::new(2, &t0, 1, &t1, 0);
+            encoder.encode(high, &mut proxy).unwrap();
         }
         t0.update(high, update0, 1);
         t1.update(high, update1, 1);
         for i in 0..4 {
             let bit = (byte & (1<<i)) != 0;
             {
-                let proxy = super::bin::SumProxy::new(1, &b0, 1, &b1, 1);
-                encoder.encode(bit, &proxy).unwrap();
+                let mut proxy = super::bin::SumProxy::new(1, &b0, 1, &b1, 1);
+                encoder.encode(bit, &mut proxy).unwrap();
             }
             b0.update(bit);
             b1.update(bit);
@@ -128,16 +167,16 @@ fn roundtrip_proxy(bytes: &[u8]) {
     let mut decoder = super::Decoder::new(BufReader::new(&buffer[..]));
     for &byte in bytes.iter() {
         let high = {
-            let proxy = super::table::SumProxy::new(2, &t0, 1, &t1, 0);
-            decoder.decode(&proxy).unwrap()
+            let mut proxy = super::table::SumProxy::new(2, &t0, 1, &t1, 0);
+            decoder.decode(&mut proxy).unwrap()
         };
         t0.update(high, update0, 1);
         t1.update(high, update1, 1);
         let mut value = (high<<4) as u8;
         for i in 0..4 {
             let bit = {
-                let proxy = super::bin::SumProxy::new(1, &b0, 1, &b1, 1);
-                decoder.decode(&proxy).unwrap()
+                let mut proxy = super::bin::SumProxy::new(1, &b0, 1, &b1, 1);
+                decoder.decode(&mut proxy).unwrap()
             };
             value += (bit as u8)<<i;
             b0.update(bit);
@@ -147,6 +186,77 @@ fn roundtrip_proxy(bytes: &[u8]) {
     }
 }
 
+/// A flat byte model with a denominator exceeding the 32-bit coder precision
+struct WideModel;
+
+impl super::Model<u8> for WideModel {
+    fn get_range(&self, value: u8) -> (super::Border, super::Border) {
+        let v = value as super::Border;
+        (v<<20, (v+1)<<20)
+    }
+
+    fn find_value(&self, offset: super::Border) -> (u8, super::Border, super::Border) {
+        let v = offset>>20;
+        (v as u8, v<<20, (v+1)<<20)
+    }
+
+    fn get_denominator(&self) -> super::Border {
+        1<<28
+    }
+}
+
+fn roundtrip_wide(bytes: &[u8]) {
+    let mut model = WideModel;
+    let range = super::RangeEncoder::<u64>::new(1<<40);
+    let mut encoder = super::Encoder::with_range(BufWriter::new(Vec::new()), range);
+    for &byte in bytes.iter() {
+        encoder.encode(byte, &mut model).unwrap();
+    }
+    let (writer, err) = encoder.finish();
+    err.unwrap();
+    let output = writer.into_inner().unwrap();
+    let range = super::RangeEncoder::<u64>::new(1<<40);
+    let mut decoder = super::Decoder::with_range(BufReader::new(&output[..]), range);
+    for &byte in bytes.iter() {
+        assert_eq!(decoder.decode(&mut model).unwrap(), byte);
+    }
+    let (_, err) = decoder.finish();
+    err.unwrap();
+}
+
+fn encode_table(bytes: &[u8], range: super::RangeEncoder) -> Vec<u8> {
+    let mut freq = super::table::Model::new_flat(256, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    let mut encoder = super::Encoder::with_range(BufWriter::new(Vec::new()), range);
+    for &byte in bytes.iter() {
+        encoder.encode(byte as usize, &mut freq).unwrap();
+        freq.update(byte as usize, 10, 1);
+    }
+    let (writer, err) = encoder.finish();
+    err.unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode_table(bytes: &[u8], encoded: &[u8], range: super::RangeEncoder) {
+    let mut freq = super::table::Model::new_flat(256, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    let mut decoder = super::Decoder::with_range(BufReader::new(encoded), range);
+    for &byte in bytes.iter() {
+        let value = decoder.decode(&mut freq).unwrap();
+        assert_eq!(value, byte as usize);
+        freq.update(value, 10, 1);
+    }
+    let (_, err) = decoder.finish();
+    err.unwrap();
+}
+
+fn roundtrip_carry(bytes: &[u8], threshold: super::Border) {
+    let cut = encode_table(bytes, super::RangeEncoder::new(threshold));
+    let carry = encode_table(bytes, super::RangeEncoder::new_carryless(threshold));
+    decode_table(bytes, &cut, super::RangeEncoder::new(threshold));
+    decode_table(bytes, &carry, super::RangeEncoder::new_carryless(threshold));
+    info!("Carryless ari size {} against the cutting {}", carry.len(), cut.len());
+    assert!(carry.len() < cut.len());
+}
+
 fn roundtrip_apm(bytes: &[u8]) {
     let mut bit = super::apm::Bit::new_equal();
     let mut gate = super::apm::Gate::new();
@@ -154,8 +264,8 @@ fn roundtrip_apm(bytes: &[u8]) {
     for b8 in bytes.iter() {
         for i in 0..8 {
             let b1 = (*b8>>i) & 1 != 0;
-            let (bit_new, coords) = gate.pass(&bit);
-            encoder.encode(b1, &bit_new).unwrap();
+            let (mut bit_new, coords) = gate.pass(&bit);
+            encoder.encode(b1, &mut bit_new).unwrap();
             bit.update(b1, 10, 0);
             gate.update(b1, coords, 10, 0);
         }
@@ -169,8 +279,8 @@ fn roundtrip_apm(bytes: &[u8]) {
     for b8 in bytes.iter() {
         let mut decoded = 0u8;
         for i in 0..8 {
-            let (bit_new, coords) = gate.pass(&bit);
-            let b1 = decoder.decode(&bit_new).unwrap();
+            let (mut bit_new, coords) = gate.pass(&bit);
+            let b1 = decoder.decode(&mut bit_new).unwrap();
             if b1 {
                 decoded += 1<<i;
             }
@@ -182,6 +292,60 @@ fn roundtrip_apm(bytes: &[u8]) {
 }
 
 
+fn roundtrip_escape(warmup: &[u8], bytes: &[u8]) {
+    use super::Model;
+    use super::escape::{self, EscapeModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let new_model = || {
+        let mut model = EscapeModel::new(super::SYMBOL_TOTAL, 16, threshold);
+        for &b in warmup.iter() {
+            model.update(b as usize);
+        }
+        model
+    };
+    let mut model = new_model();
+    let mut literal = super::table::Model::new_flat(super::SYMBOL_TOTAL, threshold);
+    let mut encoder = super::Encoder::new(BufWriter::new(Vec::new()));
+    for &b in bytes.iter() {
+        escape::encode_with_fallback(&mut encoder, b as usize, &mut model, &mut literal).unwrap();
+    }
+    let (writer, err) = encoder.finish();
+    err.unwrap();
+    let output = writer.into_inner().unwrap();
+    let mut model = new_model();
+    let mut decoder = super::Decoder::new(BufReader::new(&output[..]));
+    for &b in bytes.iter() {
+        let value = escape::decode_with_fallback(&mut decoder, &mut model, &mut literal).unwrap();
+        assert_eq!(value, b as usize);
+    }
+}
+
+/// Generate pseudo-random bits with the probability of one being 1/8
+fn biased_bits(num: usize) -> Vec<bool> {
+    let mut seed = 4321u32;
+    (0..num).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) & 7 == 0
+    }).collect()
+}
+
+/// Code the bits with an adaptive binary model, return the encoded size
+fn roundtrip_bits<F: Fn() -> super::bin::Model>(bits: &[bool], new_model: F) -> usize {
+    let mut encoder_model = new_model();
+    let mut decoder_model = new_model();
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &bit in bits.iter() {
+        encoder.encode_bit(bit, &mut encoder_model).unwrap();
+    }
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::Decoder::new(&output[..]);
+    for &bit in bits.iter() {
+        assert_eq!(decoder.decode_bit(&mut decoder_model).unwrap(), bit);
+    }
+    output.len()
+}
+
 #[test]
 fn roundtrips() {
     roundtrip(b"abracadabra");
@@ -189,6 +353,53 @@ fn roundtrips() {
     roundtrip(TEXT_INPUT);
 }
 
+/// A failure of a single seed is reproduced by calling
+/// `check::roundtrip_random` with the same arguments
+#[test]
+fn roundtrips_random() {
+    use super::check::roundtrip_random;
+    for seed in 0..16 {
+        roundtrip_random(seed, 2000, 1);
+        roundtrip_random(seed, 2000, 2);
+        roundtrip_random(seed, 1000, 256);
+        roundtrip_random(seed, 1000, 4000);
+    }
+}
+
+#[test]
+fn roundtrips_short() {
+    let messages: [&[u8]; 6] = [b"", b"a", b"\x00", b"\xFF", b"ab", b"\xFF\x00"];
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    for message in messages.iter() {
+        roundtrip(message);
+        let mut e = super::ByteEncoder::new(Vec::new());
+        e.write_all(message).unwrap();
+        let (encoded, err) = e.finish_padded();
+        err.unwrap();
+        let mut d = super::ByteDecoder::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], *message);
+        let (rest, err) = d.finish();
+        err.unwrap();
+        assert!(rest.is_empty());
+        // slices with the known length
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        let encoded = super::encode_bytes(message, &mut model).unwrap();
+        assert!(!encoded.is_empty());
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        let mut decoder = super::Decoder::new(&encoded[..]);
+        assert_eq!(&decoder.decode_n(message.len(), &mut model).unwrap()[..], *message);
+        decoder.finish().1.unwrap();
+    }
+    // nothing is read for zero values
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    assert!(super::decode_bytes(&[], &mut model, 0).unwrap().is_empty());
+    // an empty stream ends right away
+    let mut decoder = super::Decoder::new(&[][..]);
+    assert!(decoder.try_decode(&mut model).unwrap().is_none());
+}
+
 #[test]
 fn roundtrips_binary() {
     roundtrip_binary(b"abracadabra", 1);
@@ -201,27 +412,2191 @@ fn roundtrips_term() {
 }
 
 #[test]
-fn roundtrips_proxy() {
-    roundtrip_proxy(b"abracadabra");
-    roundtrip_proxy(TEXT_INPUT);
+fn roundtrips_tail() {
+    roundtrip_tail(b"");
+    roundtrip_tail(b"a");
+    roundtrip_tail(b"abracadabra");
 }
 
 #[test]
-fn roundtrips_apm() {
-    roundtrip_apm(b"abracadabra");
+fn roundtrips_adaptive() {
+    let input = &LARGE_INPUT[..1<<20];
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let adaptive_size = roundtrip_model(input,
+        super::table::AdaptiveByteModel::new(16, threshold),
+        super::table::AdaptiveByteModel::new(16, threshold));
+    let values: Vec<usize> = input.iter().map(|&b| b as usize).collect();
+    let static_size = roundtrip_model(&values[..],
+        super::table::Model::new_flat(256, threshold),
+        super::table::Model::new_flat(256, threshold));
+    assert!(adaptive_size < static_size,
+        "Adaptive size {} is not smaller than static {}", adaptive_size, static_size);
 }
 
-#[cfg(feature="unstable")]
-#[bench]
-fn compress_speed(bh: &mut Bencher) {
-    use std::io::{Seek, SeekFrom, Cursor};
-    let mut storage: Vec<u8> = vec![0u8; TEXT_INPUT.len()];
-    bh.iter(|| {
-        let cursor = Cursor::new(&mut storage[..]);
-        let mut w = BufWriter::new(cursor);
-        w.seek(SeekFrom::Start(0)).unwrap();
-        let mut e = super::ByteEncoder::new(w);
-        e.write(TEXT_INPUT).unwrap();
-    });
-    bh.bytes = TEXT_INPUT.len() as u64;
+#[test]
+fn roundtrips_static() {
+    // geometrically distributed values from a simple LCG
+    let mut seed = 12345u32;
+    let input: Vec<u8> = (0..1<<16).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 8).trailing_zeros().min(23) as u8
+    }).collect();
+    let mut counts = [0u32; 256];
+    for &b in input.iter() {
+        counts[b as usize] += 1;
+    }
+    let values: Vec<usize> = input.iter().map(|&b| b as usize).collect();
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let static_size = roundtrip_model(&values[..],
+        super::table::StaticModel::from_histogram(&counts),
+        super::table::StaticModel::from_histogram(&counts));
+    let adaptive_size = roundtrip_model(&input[..],
+        super::table::AdaptiveByteModel::new(16, threshold),
+        super::table::AdaptiveByteModel::new(16, threshold));
+    assert!(static_size < adaptive_size,
+        "Static size {} is not smaller than adaptive {}", static_size, adaptive_size);
+}
+
+#[test]
+fn static_smoothed() {
+    use super::Model;
+    let mut counts = [0u32; 256];
+    for &b in TEXT_INPUT.iter() {
+        counts[b as usize] += 1;
+    }
+    assert_eq!(counts[0], 0);
+    let new_model = || super::table::StaticModel::from_histogram_smoothed(&counts, 1);
+    let model = new_model();
+    for value in 0..256 {
+        let (lo, hi) = model.get_range(value);
+        assert!(lo < hi, "Empty interval for value {}", value);
+    }
+    // the zero bytes are absent from the training text
+    let mut values: Vec<usize> = TEXT_INPUT.iter().map(|&b| b as usize).collect();
+    values.extend([0, 0, 255].iter());
+    let encoded = super::encode_bytes(&values, &mut new_model()).unwrap();
+    let decoded = super::decode_bytes(&encoded, &mut new_model(), values.len()).unwrap();
+    assert_eq!(decoded, values);
+}
+
+#[test]
+fn static_lookup() {
+    use super::Model;
+    use super::table::{StaticModel, LOOKUP_MAX_TOTAL};
+    // nucleotides, with a missing value in the middle
+    let counts = [30, 20, 0, 45, 5];
+    for &total in [64, LOOKUP_MAX_TOTAL, LOOKUP_MAX_TOTAL*2].iter() {
+        let model = StaticModel::from_histogram_exact(&counts, total);
+        assert_eq!(model.get_denominator(), total);
+        for offset in 0..total {
+            let found = model.find_value(offset);
+            assert_eq!(found, model.search_value(offset));
+            assert!(found.1 <= offset && offset < found.2);
+        }
+    }
+    let mut model = StaticModel::from_histogram_exact(&counts, 64);
+    let values: Vec<usize> = LARGE_INPUT[..1<<12].iter().map(|&b| [0, 1, 3, 4][(b & 3) as usize]).collect();
+    let encoded = super::encode_bytes(&values, &mut model).unwrap();
+    assert_eq!(super::decode_bytes(&encoded, &mut model, values.len()).unwrap(), values);
+}
+
+#[test]
+fn static_model_serialize() {
+    let mut counts = [0u32; 256];
+    for &b in TEXT_INPUT.iter() {
+        counts[b as usize] += 1;
+    }
+    let model = super::table::StaticModel::from_histogram(&counts);
+    let mut model_bytes = Vec::new();
+    model.write_to(&mut model_bytes).unwrap();
+    let loaded = super::table::StaticModel::read_from(&mut &model_bytes[..]).unwrap();
+    let values: Vec<usize> = TEXT_INPUT.iter().map(|&b| b as usize).collect();
+    let encode = |mut model: super::table::StaticModel| {
+        let mut encoder = super::Encoder::new(Vec::new());
+        encoder.encode_all(&values[..], &mut model).unwrap();
+        let (output, err) = encoder.finish();
+        err.unwrap();
+        output
+    };
+    let original = encode(model);
+    assert_eq!(encode(loaded), original);
+    let size = roundtrip_model(&values[..],
+        super::table::StaticModel::read_from(&mut &model_bytes[..]).unwrap(),
+        super::table::StaticModel::read_from(&mut &model_bytes[..]).unwrap());
+    assert_eq!(size, original.len());
+    // a single frequency above the threshold
+    let bad = [1u8, 0x80, 0x80, 0x80, 0x01];
+    let err = super::table::StaticModel::read_from(&mut &bad[..]).err().unwrap();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidData);
+    // all frequencies are zero
+    let err = super::table::StaticModel::read_from(&mut &[2u8, 0, 1][..]).err().unwrap();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn roundtrips_rescale() {
+    // frequent rescales under a small cap
+    let input = &LARGE_INPUT[..1<<18];
+    roundtrip_model(input,
+        super::table::AdaptiveByteModel::new(16, 1<<10),
+        super::table::AdaptiveByteModel::new(16, 1<<10));
+    // a long run of a single byte, pushing its frequency to the limit
+    let mut input = LARGE_INPUT[..1<<16].to_vec();
+    input.extend((0..1<<18).map(|_| b'0'));
+    input.extend_from_slice(TEXT_INPUT);
+    let threshold: super::Border = 1<<20;
+    let new_model = || super::table::AdaptiveByteModel::new(64, 1<<18);
+    let mut model = new_model();
+    let mut encoder = super::Encoder::with_range(Vec::new(), super::RangeEncoder::new(threshold));
+    encoder.encode_all(&input[..], &mut model).unwrap();
+    let total = model.get_frequencies().iter().fold(0, |u,&f| u + f as super::Border);
+    assert!(total < 1<<18);
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let mut model = new_model();
+    let mut decoder = super::Decoder::with_range(&output[..], super::RangeEncoder::new(threshold));
+    assert_eq!(decoder.decode_n(input.len(), &mut model).unwrap(), input);
+}
+
+#[test]
+fn roundtrips_bits() {
+    let bits = biased_bits(1<<16);
+    let size = roundtrip_bits(&bits, || super::bin::Model::new_flat(super::RANGE_DEFAULT_THRESHOLD, 5));
+    let ones = bits.iter().filter(|&&b| b).count() as f32;
+    let p = ones / (bits.len() as f32);
+    let optimal = -(bits.len() as f32) * (p*p.log2() + (1.0-p)*(1.0-p).log2()) / 8.0;
+    info!("Binary coded size {} against the entropy {}", size, optimal);
+    assert!((size as f32) < optimal * 1.05);
+}
+
+#[test]
+fn binary_rate() {
+    let bits = biased_bits(1<<16);
+    let fast_size = roundtrip_bits(&bits, || super::bin::Model::with_rate(2));
+    let slow_size = roundtrip_bits(&bits, || super::bin::Model::with_rate(7));
+    info!("Binary coded size {} with slow rate against {} with fast", slow_size, fast_size);
+    assert!(slow_size < fast_size);
+}
+
+#[test]
+#[should_panic(expected = "is out of")]
+fn binary_rate_zero() {
+    super::bin::Model::with_rate(0);
+}
+
+#[test]
+#[should_panic(expected = "is out of")]
+fn binary_rate_large() {
+    super::bin::Model::with_rate(40);
+}
+
+#[test]
+fn binary_apm() {
+    use super::bin::{Apm, APM_TOTAL};
+    // the probability of zero flips with the parity of the position,
+    // which the primary model can't see
+    let mut seed = 1234u32;
+    let input: Vec<(bool, usize)> = (0..1<<14).map(|i| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let rare = (seed >> 16) & 7 == 0;
+        (rare == (i & 1 == 0), i & 1)
+    }).collect();
+    let bits: Vec<bool> = input.iter().map(|&(bit, _)| bit).collect();
+    let new_model = || super::bin::Model::new_flat(APM_TOTAL, 5);
+    let raw_size = roundtrip_bits(&bits, new_model);
+    let mut model = new_model();
+    let mut apm = Apm::new(2, 5);
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &(bit, ctx) in input.iter() {
+        apm.refine(model.get_probability_zero() as u16, ctx);
+        encoder.encode(bit, &mut apm).unwrap();
+        model.update(bit);
+    }
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let (mut model, mut apm) = (new_model(), Apm::new(2, 5));
+    let mut decoder = super::Decoder::new(&output[..]);
+    for &(bit, ctx) in input.iter() {
+        apm.refine(model.get_probability_zero() as u16, ctx);
+        assert_eq!(decoder.decode(&mut apm).unwrap(), bit);
+        model.update(bit);
+    }
+    assert!(output.len() * 3 < raw_size * 2,
+        "Refined size {} is not much below the raw {}", output.len(), raw_size);
+}
+
+#[test]
+fn estimate_bits() {
+    let mut counts = [0u32; 256];
+    for &b in TEXT_INPUT.iter() {
+        counts[b as usize] += 1;
+    }
+    let mut model = super::table::StaticModel::from_histogram(&counts);
+    let values: Vec<usize> = TEXT_INPUT.iter().map(|&b| b as usize).collect();
+    let estimate = values.iter().fold(0.0, |sum, &v| sum + super::estimate_bits(v, &model));
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(&values[..], &mut model).unwrap();
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let actual = (output.len() * 8) as f64;
+    info!("Estimated {} bits, produced {}", estimate, actual);
+    assert!(estimate < actual && actual < estimate * 1.01 + 32.0);
+}
+
+#[test]
+fn core_encode() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut range = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    let mut buf = [0u8; 64];
+    let mut num = 0;
+    {
+        let mut sink = |s| { buf[num] = s; num += 1; };
+        for &b in b"abracadabra".iter() {
+            super::encode_into(b, &mut model, &mut range, &mut sink).unwrap();
+        }
+        range.get_code_tail(&mut sink);
+    }
+    let mut accum = Vec::new();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut range = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    for &b in b"abracadabra".iter() {
+        super::encode(b, &mut model, &mut range, &mut accum).unwrap();
+    }
+    range.get_code_tail(|s| accum.push(s));
+    assert_eq!(&buf[..num], &accum[..]);
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut decoder = super::Decoder::new(&buf[..num]);
+    assert_eq!(&decoder.decode_n(11, &mut model).unwrap()[..], b"abracadabra");
+}
+
+#[test]
+fn model_bounds() {
+    let max_total = 1<<16;
+    let new_model = || super::table::Model::new_custom(4096, max_total+1, |_| 16);
+    let values: Vec<usize> = LARGE_INPUT[..1<<12].chunks(2)
+        .map(|pair| ((pair[0] as usize) << 4 | (pair[1] as usize) & 0xF) & 0xFFF).collect();
+    // the default threshold is too small for the model total
+    let mut encoder = super::Encoder::new(Vec::new());
+    assert!(encoder.encode_all(&values[..], &mut new_model()).is_err());
+    let mut encoder = super::Encoder::with_model_bounds(Vec::new(), max_total);
+    encoder.encode_all(&values[..], &mut new_model()).unwrap();
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::Decoder::with_model_bounds(&output[..], max_total);
+    assert_eq!(decoder.decode_n(values.len(), &mut new_model()).unwrap(), values);
+}
+
+#[test]
+fn custom_threshold() {
+    use super::Model;
+    let threshold = 1<<20;
+    let new_model = || super::table::Model::new_flat(1<<16, threshold);
+    let values: Vec<usize> = LARGE_INPUT[..1<<12].chunks(2)
+        .map(|pair| (pair[0] as usize) << 8 | pair[1] as usize).collect();
+    assert!(new_model().get_denominator() > super::RANGE_DEFAULT_THRESHOLD);
+    let mut encoder = super::Encoder::with_threshold(Vec::new(), threshold);
+    encoder.encode_all(&values[..], &mut new_model()).unwrap();
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::Decoder::with_threshold(&output[..], threshold);
+    assert_eq!(decoder.decode_n(values.len(), &mut new_model()).unwrap(), values);
+}
+
+#[test]
+#[should_panic]
+fn custom_threshold_small() {
+    super::Encoder::with_threshold(Vec::new(), super::SYMBOL_TOTAL as super::Border);
+}
+
+/// A model with a broken denominator or interval
+#[cfg(debug_assertions)]
+struct BrokenModel {
+    total: super::Border,
+    range: (super::Border, super::Border),
+}
+
+#[cfg(debug_assertions)]
+impl super::Model<u8> for BrokenModel {
+    fn get_range(&self, _value: u8) -> (super::Border, super::Border) {
+        self.range
+    }
+    fn find_value(&self, _offset: super::Border) -> (u8, super::Border, super::Border) {
+        (0, self.range.0, self.range.1)
+    }
+    fn get_denominator(&self) -> super::Border {
+        self.total
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "exceeds the largest total")]
+fn broken_denominator() {
+    let mut model = BrokenModel { total: 1<<31, range: (0, 1) };
+    let mut encoder = super::Encoder::new(Vec::new());
+    for _ in 0..10 {
+        let _ = encoder.encode(0, &mut model);
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "is not within the denominator")]
+fn broken_interval() {
+    let mut model = BrokenModel { total: 100, range: (50, 101) };
+    let mut encoder = super::Encoder::new(Vec::new());
+    let _ = encoder.encode(0, &mut model);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn broken_empty_interval() {
+    let mut model = BrokenModel { total: 100, range: (50, 50) };
+    let mut encoder = super::Encoder::new(Vec::new());
+    let err = encoder.encode(0, &mut model).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn empty_interval() {
+    let mut re: super::RangeEncoder = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    re.process(100, 10, 20, |_| ()).unwrap();
+    let state = re.checkpoint();
+    assert_eq!(re.process(100, 50, 50, |_| ()), Err(super::Error::BadInterval(50, 50, 100)));
+    assert_eq!(re.checkpoint(), state);
+    // the rarest value has the width of 1 after the rescaling
+    let mut counts = vec![1000u32; 16];
+    counts[7] = 1;
+    let model = || super::table::StaticModel::from_histogram_exact(&counts, 1<<10);
+    {
+        use super::Model;
+        let (lo, hi) = model().get_range(7);
+        assert_eq!(hi - lo, 1);
+    }
+    let values: Vec<usize> = (0..1000).map(|i| if i % 100 == 3 {7} else {i & 15}).collect();
+    let encoded = super::encode_bytes(&values, &mut model()).unwrap();
+    assert_eq!(super::decode_bytes(&encoded, &mut model(), values.len()).unwrap(), values);
+}
+
+#[test]
+fn encoded_bytes() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    let mut sum = 0;
+    for &byte in TEXT_INPUT.iter() {
+        sum += encoder.encode(byte, &mut model).unwrap();
+        assert_eq!(sum as u64, encoder.bytes_written());
+    }
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let tail = output.len() - sum;
+    assert!(tail >= 1 && tail <= 4, "Unexpected tail of {} bytes", tail);
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    assert_eq!(encoder.encode_all(TEXT_INPUT, &mut model).unwrap(), sum);
+}
+
+#[test]
+fn encode_all() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(TEXT_INPUT, &mut model).unwrap();
+    let (all, err) = encoder.finish();
+    err.unwrap();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &byte in TEXT_INPUT.iter() {
+        encoder.encode(byte, &mut model).unwrap();
+    }
+    let (each, err) = encoder.finish();
+    err.unwrap();
+    assert_eq!(all, each);
+}
+
+#[test]
+fn encode_bytes() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::Order1Model::new(16, threshold);
+    let encoded = super::encode_bytes(TEXT_INPUT, &mut model).unwrap();
+    let mut model = super::table::Order1Model::new(16, threshold);
+    let decoded = super::decode_bytes(&encoded, &mut model, TEXT_INPUT.len()).unwrap();
+    assert_eq!(&decoded[..], TEXT_INPUT);
+}
+
+#[test]
+fn roundtrips_mix() {
+    use super::Model;
+    use super::mix::MixModel;
+    use super::table::{AdaptiveByteModel, Order1Model};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    fn roundtrip_model<M: Model<u8>, F: Fn() -> M>(new_model: F) -> usize {
+        let encoded = super::encode_bytes(TEXT_INPUT, &mut new_model()).unwrap();
+        let decoded = super::decode_bytes(&encoded, &mut new_model(), TEXT_INPUT.len()).unwrap();
+        assert_eq!(&decoded[..], TEXT_INPUT);
+        encoded.len()
+    }
+    let order0 = roundtrip_model(|| AdaptiveByteModel::new(16, threshold));
+    let order1 = roundtrip_model(|| Order1Model::new(16, threshold));
+    let mixed = roundtrip_model(|| MixModel::new(vec![
+        Box::new(AdaptiveByteModel::new(16, threshold)),
+        Box::new(Order1Model::new(16, threshold)),
+        ], vec![1, 1]));
+    assert!(mixed < order0 && mixed < order1,
+        "Mixed size {} is not below order-0 {} and order-1 {}", mixed, order0, order1);
+}
+
+#[test]
+fn prime() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let (primer, message) = TEXT_INPUT.split_at(TEXT_INPUT.len() - 200);
+    let primed = || {
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        model.prime(primer);
+        model
+    };
+    let cold = super::encode_bytes(message, &mut super::table::AdaptiveByteModel::new(16, threshold)).unwrap();
+    let warm = super::encode_bytes(message, &mut primed()).unwrap();
+    assert_eq!(&super::decode_bytes(&warm, &mut primed(), message.len()).unwrap()[..], message);
+    assert!(warm.len() < cold.len(), "Primed size {} is not below the cold {}", warm.len(), cold.len());
+}
+
+#[test]
+fn roundtrips_freq() {
+    use super::table::FreqModel;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    // tokens of a 300-symbol alphabet, mostly small
+    let values: Vec<u32> = LARGE_INPUT[..1<<12].chunks(2)
+        .map(|pair| (pair[0] as u32 + pair[1] as u32 * 3) % 300).collect();
+    assert!(values.iter().any(|&v| v >= 256));
+    let adaptive = || FreqModel::new(300, 16, threshold);
+    let mut frequencies = vec![1; 300];
+    for &v in values.iter() {
+        frequencies[v as usize] += 1;
+    }
+    let fixed = || FreqModel::with_frequencies(&frequencies, 0, threshold);
+    let encoded = super::encode_bytes(&values, &mut adaptive()).unwrap();
+    assert_eq!(super::decode_bytes(&encoded, &mut adaptive(), values.len()).unwrap(), values);
+    let mut model = fixed();
+    let encoded = super::encode_bytes(&values, &mut model).unwrap();
+    assert_eq!(model.get_frequencies(), fixed().get_frequencies());
+    assert_eq!(super::decode_bytes(&encoded, &mut model, values.len()).unwrap(), values);
+}
+
+#[test]
+fn roundtrips_order_n() {
+    use super::Model;
+    use super::table::{Order1Model, OrderNModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let text: Vec<u8> = TEXT_INPUT.iter().cycle().take(TEXT_INPUT.len() * 4).cloned().collect();
+    fn roundtrip_model<M: Model<u8>, F: Fn() -> M>(text: &[u8], new_model: F) -> usize {
+        let encoded = super::encode_bytes(text, &mut new_model()).unwrap();
+        let decoded = super::decode_bytes(&encoded, &mut new_model(), text.len()).unwrap();
+        assert_eq!(&decoded[..], text);
+        encoded.len()
+    }
+    let order1 = roundtrip_model(&text, || Order1Model::new(64, threshold));
+    let order2 = roundtrip_model(&text, || OrderNModel::new(2, 16, 64, threshold));
+    assert!(order2 < order1, "Order-2 size {} is not below order-1 {}", order2, order1);
+    // a tiny table, full of collisions
+    let collided = roundtrip_model(&text, || OrderNModel::new(3, 2, 64, threshold));
+    assert!(collided > order2);
+}
+
+#[test]
+fn roundtrips_dyn() {
+    use super::Model;
+    use super::table::{AdaptiveByteModel, Order1Model};
+    fn new_model(order: usize) -> Box<dyn Model<u8>> {
+        let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+        match order {
+            0 => Box::new(AdaptiveByteModel::new(16, threshold)),
+            _ => Box::new(Order1Model::new(16, threshold)),
+        }
+    }
+    let mut sizes = Vec::new();
+    for order in 0..2 {
+        let mut model = new_model(order);
+        let mut encoder = super::Encoder::new(Vec::new());
+        for &byte in TEXT_INPUT.iter() {
+            encoder.encode(byte, &mut model).unwrap();
+        }
+        let (encoded, err) = encoder.finish();
+        err.unwrap();
+        let mut model = new_model(order);
+        // a plain trait object reference works as well
+        let mut model_ref: &mut dyn Model<u8> = &mut *model;
+        let mut decoder = super::Decoder::new(&encoded[..]);
+        assert_eq!(&decoder.decode_n(TEXT_INPUT.len(), &mut model_ref).unwrap()[..], TEXT_INPUT);
+        sizes.push(encoded.len());
+    }
+    assert!(sizes[0] != sizes[1]);
+}
+
+#[test]
+fn byte_encoder_writer() {
+    fn write_chunks<W: Write>(w: &mut W, data: &[u8]) {
+        for chunk in data.chunks(100) {
+            w.write_all(chunk).unwrap();
+            w.flush().unwrap();
+        }
+        write!(w, "{}", "tail").unwrap();
+    }
+    let mut e = super::ByteEncoder::new(Vec::new());
+    write_chunks(&mut e, TEXT_INPUT);
+    let (encoded, err) = e.finish();
+    err.unwrap();
+    let mut d = super::ByteDecoder::new(&encoded[..]);
+    let mut decoded = Vec::new();
+    d.read_to_end(&mut decoded).unwrap();
+    assert_eq!(&decoded[..TEXT_INPUT.len()], TEXT_INPUT);
+    assert_eq!(&decoded[TEXT_INPUT.len()..], b"tail");
+}
+
+#[test]
+fn byte_decoder_reader() {
+    use std::io::BufRead;
+    let mut e = super::ByteEncoder::new(Vec::new());
+    e.write_all(TEXT_INPUT).unwrap();
+    let (encoded, err) = e.finish();
+    err.unwrap();
+    let d = super::ByteDecoder::new(&encoded[..]);
+    let mut reader = BufReader::with_capacity(16, d);
+    let mut decoded = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() != 0 {
+        decoded.extend(line.bytes());
+        line.clear();
+    }
+    assert_eq!(&decoded[..], TEXT_INPUT);
+    // stays at the end of file
+    assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+}
+
+#[test]
+#[cfg(feature="checksum")]
+fn byte_checksum() {
+    let encode = || {
+        let mut encoder = super::ByteEncoder::new(Vec::new());
+        encoder.write_all(TEXT_INPUT).unwrap();
+        let (output, err) = encoder.finish_with_checksum();
+        err.unwrap();
+        output
+    };
+    let decode = |input: &[u8]| {
+        let mut decoder = super::ByteDecoder::new(input);
+        let mut output = Vec::new();
+        try!(decoder.read_to_end(&mut output));
+        let (_, err) = decoder.finish_with_checksum();
+        err.map(|_| output)
+    };
+    let encoded = encode();
+    assert_eq!(&decode(&encoded[..]).unwrap()[..], TEXT_INPUT);
+    // some flips are caught by the coder, the rest by the checksum
+    let mut num_mismatches = 0;
+    for i in (0..encoded.len()).filter(|i| i % 7 == 0) {
+        let mut corrupted = encoded.clone();
+        corrupted[i] ^= 0x10;
+        let err = decode(&corrupted[..]).err().unwrap();
+        if err.to_string() == "checksum mismatch" {
+            num_mismatches += 1;
+        }
+    }
+    assert!(num_mismatches > 0);
+}
+
+#[test]
+fn byte_two_pass() {
+    // mostly zeros, with a sparse tail of rare bytes
+    let mut seed = 777u32;
+    let input: Vec<u8> = (0..1<<12).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        ((seed >> 8).trailing_zeros() & 7) as u8
+    }).collect();
+    let (two_pass, err) = super::ByteEncoder::two_pass(Vec::new(), &input);
+    err.unwrap();
+    assert_eq!(super::ByteDecoder::two_pass(&two_pass[..]).unwrap(), input);
+    let mut encoder = super::ByteEncoder::new(Vec::new());
+    encoder.write_all(&input).unwrap();
+    let (adaptive, err) = encoder.finish();
+    err.unwrap();
+    info!("Two-pass size {} against the adaptive {}", two_pass.len(), adaptive.len());
+    assert!(two_pass.len() < adaptive.len(),
+        "Two-pass size {} is not smaller than adaptive {}", two_pass.len(), adaptive.len());
+}
+
+#[test]
+fn byte_dictionary() {
+    use super::table::{DictByteDecoder, DictByteEncoder, StaticModel};
+    // the dictionary is trained on the first half of the text
+    let (training, rest) = TEXT_INPUT.split_at(TEXT_INPUT.len() / 2);
+    let mut counts = vec![0u32; 257];
+    for &byte in training.iter() {
+        counts[byte as usize] += 1;
+    }
+    counts[256] = 1;
+    let mut dictionary = Vec::new();
+    StaticModel::from_histogram_smoothed(&counts, 1).write_to(&mut dictionary).unwrap();
+    let model = StaticModel::read_from(&mut &dictionary[..]).unwrap();
+    let messages: Vec<&[u8]> = rest.chunks(rest.len() / 100).take(100).collect();
+    assert_eq!(messages.len(), 100);
+    let (mut dict_size, mut adaptive_size) = (0, 0);
+    for message in messages.iter() {
+        let mut e = DictByteEncoder::new(Vec::new(), &model);
+        e.write_all(message).unwrap();
+        let (encoded, err) = e.finish();
+        err.unwrap();
+        let mut d = DictByteDecoder::new(&encoded[..], &model);
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], *message);
+        dict_size += encoded.len();
+        let mut e = super::ByteEncoder::new(Vec::new());
+        e.write_all(message).unwrap();
+        adaptive_size += e.finish().0.len();
+    }
+    assert!(dict_size < adaptive_size,
+        "Dictionary coded size {} is not below the adaptive {}", dict_size, adaptive_size);
+}
+
+#[test]
+fn byte_blocks() {
+    // text, followed by binary data of a different distribution, and text again
+    let section = 1<<13;
+    let mut seed = 31337u32;
+    let mut input = LARGE_INPUT[..section].to_vec();
+    input.extend((0..section).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        0x80 | ((seed >> 8).trailing_zeros() & 15) as u8
+    }));
+    input.extend_from_slice(&LARGE_INPUT[section..2*section]);
+    let encode = |block_size| {
+        let mut encoder = super::table::BlockByteEncoder::new(Vec::new(), block_size);
+        encoder.write_all(&input).unwrap();
+        let (output, err) = encoder.finish();
+        err.unwrap();
+        output
+    };
+    let blocks = encode(section);
+    let mut decoder = super::table::BlockByteDecoder::new(&blocks[..], section);
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, input);
+    let adaptive = encode(usize::max_value());
+    assert!(blocks.len() < adaptive.len(),
+        "Block size {} is not smaller than adaptive {}", blocks.len(), adaptive.len());
+}
+
+#[test]
+fn encoder_blocks() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let blocks: Vec<&[u8]> = TEXT_INPUT.chunks(1000).collect();
+    let mut encoder = super::Encoder::new(Vec::new());
+    for block in blocks.iter() {
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        encoder.encode_all(block, &mut model).unwrap();
+        encoder.finish_block().unwrap();
+    }
+    let (encoded, err) = encoder.finish();
+    err.unwrap();
+    let mut reader = &encoded[..];
+    for block in blocks.iter() {
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        let mut decoder = super::Decoder::new(&mut reader);
+        assert_eq!(&decoder.decode_n(block.len(), &mut model).unwrap()[..], &block[..]);
+        let (_, err) = decoder.finish();
+        err.unwrap();
+    }
+}
+
+#[test]
+fn checkpoints() {
+    let values: Vec<usize> = TEXT_INPUT.iter().map(|&b| b as usize).collect();
+    let (first, second) = values.split_at(values.len() / 2);
+    let mut model = super::table::StaticModel::from_histogram(&[1; 256]);
+    let mut encoder = super::Encoder::new(Vec::new());
+    // the first checkpoint is at the block boundary, aligned by a padded tail
+    encoder.encode_all(first, &mut model).unwrap();
+    encoder.finish_block().unwrap();
+    let block_offset = encoder.bytes_written() as usize;
+    encoder.encode_all(second, &mut model).unwrap();
+    let (encoded, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::Decoder::new(&encoded[block_offset..]);
+    assert_eq!(decoder.decode_n(second.len(), &mut model).unwrap(), second);
+    // the second checkpoint is taken by the decoder in the middle of the block
+    let mut reader = &encoded[block_offset..];
+    let state = {
+        let mut decoder = super::Decoder::new(&mut reader);
+        decoder.decode_n(100, &mut model).unwrap();
+        decoder.checkpoint()
+    };
+    let offset = encoded.len() - reader.len();
+    let mut decoder = super::Decoder::new(&encoded[offset..]);
+    decoder.restore(state);
+    assert_eq!(decoder.decode_n(second.len() - 100, &mut model).unwrap(), &second[100..]);
+}
+
+#[test]
+fn bounds() {
+    let mut re: super::RangeEncoder = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    assert_eq!(re.bounds(), (0, super::Border::max_value()));
+    re.process(10, 3, 4, |_| ()).unwrap();
+    let (low, hai) = re.bounds();
+    assert!(low > 0 && hai < super::Border::max_value());
+    assert_eq!(hai - low, re.range_width());
+    re.reset();
+    assert_eq!(re.bounds(), (0, super::Border::max_value()));
+}
+
+#[test]
+fn border_max() {
+    assert_eq!(super::BORDER_MAX, !0);
+    let re: super::RangeEncoder = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    assert_eq!(re.bounds().1, super::BORDER_MAX);
+    let re: super::RangeEncoder<u64> = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD as u64);
+    assert_eq!(re.bounds().1, !0u64);
+}
+
+#[test]
+fn max_total() {
+    for &carryless in [false, true].iter() {
+        let mut re: super::RangeEncoder = if carryless {
+            super::RangeEncoder::new_carryless(super::RANGE_DEFAULT_THRESHOLD)
+        } else {
+            super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD)
+        };
+        let mut seed = 1u32;
+        for _ in 0..10000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            assert!(re.max_total() <= re.range_width());
+            // the model keeps its total as large as the coder allows
+            let total = re.max_total();
+            let from = seed % total;
+            let to = from + 1 + (seed >> 16) % (total - from);
+            re.process(total, from, to, |_| ()).unwrap();
+        }
+    }
+}
+
+#[test]
+fn into_inner() {
+    use std::ptr;
+    let mut model = super::table::StaticModel::from_histogram(&[1; 256]);
+    let values: Vec<usize> = TEXT_INPUT.iter().map(|&b| b as usize).collect();
+    let mut output = Vec::new();
+    {
+        let mut encoder = super::Encoder::new(&mut output);
+        encoder.encode_all(&values, &mut model).unwrap();
+        encoder.finish_block().unwrap();
+        encoder.encode_all(&values, &mut model).unwrap();
+        let stream = encoder.into_inner();
+        assert!(ptr::eq(&*stream, &output));
+    }
+    let mut decoder = super::Decoder::new(&output[..]);
+    assert_eq!(decoder.decode_n(values.len(), &mut model).unwrap(), values);
+    let stream = decoder.into_inner();
+    assert!(ptr::eq(stream.as_ptr(), output[output.len() - stream.len()..].as_ptr()));
+}
+
+#[test]
+fn flush_align() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let new_model = || super::table::AdaptiveByteModel::new(16, threshold);
+    let (first, second) = TEXT_INPUT.split_at(TEXT_INPUT.len() / 2);
+    for &carryless in [false, true].iter() {
+        let new_range = || if carryless {
+            super::RangeEncoder::new_carryless(super::RANGE_DEFAULT_THRESHOLD)
+        } else {
+            super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD)
+        };
+        let mut model = new_model();
+        let mut encoder = super::Encoder::with_range(Vec::new(), new_range());
+        encoder.encode_all(first, &mut model).unwrap();
+        encoder.flush_align().unwrap();
+        encoder.get_mut().write_all(b"|").unwrap();
+        encoder.encode_all(second, &mut model).unwrap();
+        let (encoded, err) = encoder.finish();
+        err.unwrap();
+        let mut model = new_model();
+        let mut decoder = super::Decoder::with_range(&encoded[..], new_range());
+        assert_eq!(&decoder.decode_n(first.len(), &mut model).unwrap()[..], first);
+        decoder.resync_align().unwrap();
+        let mut raw = [0u8];
+        decoder.get_mut().read_exact(&mut raw).unwrap();
+        assert_eq!(&raw, b"|");
+        assert_eq!(&decoder.decode_n(second.len(), &mut model).unwrap()[..], second);
+    }
+}
+
+#[test]
+fn reset_all() {
+    use std::io::Cursor;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let new_model = || super::table::AdaptiveByteModel::new(16, threshold);
+    let (first, second) = TEXT_INPUT.split_at(100);
+    let mut frames = super::encode_bytes(first, &mut new_model()).unwrap();
+    let first_len = frames.len();
+    frames.extend(super::encode_bytes(second, &mut new_model()).unwrap());
+    let mut decoder = super::Decoder::new(Cursor::new(&frames[..]));
+    assert_eq!(&decoder.decode_n(first.len(), &mut new_model()).unwrap()[..], first);
+    decoder.get_mut().set_position(first_len as u64);
+    decoder.reset_all();
+    assert_eq!(&decoder.decode_n(second.len(), &mut new_model()).unwrap()[..], second);
+}
+
+#[test]
+fn bytes_written() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    assert_eq!(encoder.bytes_written(), 0);
+    encoder.encode_all(&TEXT_INPUT[..1000], &mut model).unwrap();
+    for &byte in TEXT_INPUT[1000..].iter() {
+        encoder.encode(byte, &mut model).unwrap();
+    }
+    encoder.finish_block().unwrap();
+    let written = encoder.bytes_written();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(TEXT_INPUT, &mut model).unwrap();
+    let (encoded, err) = encoder.finish_padded();
+    err.unwrap();
+    assert_eq!(written, encoded.len() as u64);
+}
+
+#[test]
+fn bytes_lost() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let encode = |tracking| {
+        let mut range = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+        range.set_tracking(tracking);
+        let mut encoder = super::Encoder::with_range(Vec::new(), range);
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        encoder.encode_all(TEXT_INPUT, &mut model).unwrap();
+        encoder.get_bytes_lost()
+    };
+    let (cut, division) = encode(true);
+    info!("Bytes lost on cuts {}, on division {}", cut, division);
+    assert!(cut > 0.0 && division > 0.0);
+    assert_eq!(encode(false), (0.0, 0.0));
+}
+
+#[test]
+fn decode_n() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let input = &LARGE_INPUT[..1000];
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(input, &mut model).unwrap();
+    let (output, err) = encoder.finish();
+    err.unwrap();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut decoder = super::Decoder::new(&output[..]);
+    let decoded = decoder.decode_n(input.len(), &mut model).unwrap();
+    assert_eq!(&decoded[..], input);
+    // the truncated stream runs out of data
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut decoder = super::Decoder::new(&output[..output.len()/2]);
+    assert!(decoder.decode_n(input.len(), &mut model).is_err());
+}
+
+#[test]
+fn byte_parallel() {
+    use super::table::{ParallelByteDecoder, ParallelByteEncoder};
+    let input = &LARGE_INPUT[..1<<16];
+    let encode = |num_threads| {
+        let mut e = ParallelByteEncoder::new(Vec::new(), 1<<13, num_threads);
+        e.write_all(input).unwrap();
+        let (encoded, err) = e.finish();
+        err.unwrap();
+        encoded
+    };
+    let single = encode(1);
+    let parallel = encode(3);
+    assert_eq!(parallel, single);
+    for &num_threads in [1, 4].iter() {
+        let decoded = ParallelByteDecoder::new(&parallel[..], num_threads).decode().unwrap();
+        assert_eq!(&decoded[..], input);
+    }
+    let empty = ParallelByteDecoder::new(&[0u8][..], 2).decode().unwrap();
+    assert!(empty.is_empty());
+    // a frame is cut short
+    let cut = ParallelByteDecoder::new(&parallel[..parallel.len()-1], 2).decode();
+    assert!(cut.is_err());
+}
+
+#[test]
+fn symbols() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(TEXT_INPUT, &mut model).unwrap();
+    let (encoded, err) = encoder.finish();
+    err.unwrap();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut decoder = super::Decoder::new(&encoded[..]);
+    let decoded: Vec<u8> = decoder.symbols(&mut model).map(|v| v.unwrap()).collect();
+    assert!(decoded.len() >= TEXT_INPUT.len());
+    assert_eq!(&decoded[..TEXT_INPUT.len()], TEXT_INPUT);
+    // a broken stream surfaces the error once
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let broken = [0xFFu8; 16];
+    let mut decoder = super::Decoder::new(&broken[..]);
+    let items: Vec<_> = decoder.symbols(&mut model).collect();
+    assert_eq!(items.len(), 1);
+    assert!(items[0].is_err());
+}
+
+#[test]
+fn try_decode() {
+    let mut model = super::table::Model::new_flat(256, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    let mut decoder = super::Decoder::new(&b""[..]);
+    assert_eq!(decoder.try_decode(&mut model).unwrap(), None);
+    let values: Vec<usize> = TEXT_INPUT[..100].iter().map(|&b| b as usize).collect();
+    let mut encoder = super::Encoder::new(Vec::new());
+    encoder.encode_all(&values[..], &mut model).unwrap();
+    let (encoded, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::Decoder::new(&encoded[..50]);
+    let mut decoded = Vec::new();
+    while let Some(value) = decoder.try_decode(&mut model).unwrap() {
+        decoded.push(value);
+    }
+    // the values within the last code word can't be trusted
+    assert!(decoded.len() < 50 + 4);
+    assert_eq!(&decoded[..46], &values[..46]);
+}
+
+#[test]
+fn roundtrips_order1() {
+    let input = &LARGE_INPUT[..1<<18];
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let order0_size = roundtrip_model(input,
+        super::table::AdaptiveByteModel::new(16, threshold),
+        super::table::AdaptiveByteModel::new(16, threshold));
+    let order1_size = roundtrip_model(input,
+        super::table::Order1Model::new(16, threshold),
+        super::table::Order1Model::new(16, threshold));
+    info!("Order-1 size {} against the order-0 {}", order1_size, order0_size);
+    assert!(order1_size < order0_size);
+}
+
+#[test]
+fn roundtrips_escape() {
+    roundtrip_escape(b"", b"abracadabra");
+    roundtrip_escape(b"abracadabra", TEXT_INPUT);
+    let (warmup, rest) = TEXT_INPUT.split_at(TEXT_INPUT.len() / 2);
+    let mut input = rest.to_vec();
+    input.extend((0..256).map(|i| i as u8));
+    roundtrip_escape(warmup, &input);
+    // the escape itself is not a value
+    use std::io;
+    use super::escape::{self, EscapeModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = EscapeModel::new(super::SYMBOL_TOTAL, 16, threshold);
+    let mut literal = super::table::Model::new_flat(super::SYMBOL_TOTAL, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    let escape = model.get_escape();
+    let err = escape::encode_with_fallback(&mut encoder, escape, &mut model, &mut literal).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn roundtrips_proxy() {
+    roundtrip_proxy(b"abracadabra");
+    roundtrip_proxy(TEXT_INPUT);
+}
+
+#[test]
+fn roundtrips_wide() {
+    roundtrip_wide(b"abracadabra");
+    roundtrip_wide(TEXT_INPUT);
+}
+
+#[test]
+fn roundtrips_carry() {
+    // a wide threshold makes the range cuts frequent
+    let skewed: Vec<u8> = (0..10000u32).map(|i| if i%13 == 0 {b'b'} else {b'a'}).collect();
+    roundtrip_carry(&skewed, 1<<22);
+    roundtrip_carry(TEXT_INPUT, 1<<22);
+}
+
+#[test]
+fn roundtrips_apm() {
+    roundtrip_apm(b"abracadabra");
+}
+
+#[test]
+fn range_errors() {
+    use super::Error;
+    let mut re = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    assert_eq!(re.process(10, 5, 5, |_| ()), Err(Error::BadInterval(5, 5, 10)));
+    assert_eq!(re.process(10, 5, 11, |_| ()), Err(Error::BadInterval(5, 11, 10)));
+    re.process(2, 1, 2, |_| ()).unwrap();
+    assert_eq!(re.query(10, 0), Err(Error::CodeOutOfRange));
+    re.process(1<<20, 0, 1, |_| ()).unwrap();
+    let width = re.range_width();
+    assert!(width < 1<<30);
+    assert_eq!(re.process(1<<30, 0, 1, |_| ()), Err(Error::RangeTooNarrow(1<<30, width)));
+    let err: ::std::io::Error = Error::CodeOutOfRange.into();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn table_cumulative() {
+    use super::Model;
+    let mut table = super::table::Model::new_custom(300, 1<<12, |i| (i%7) as u16);
+    for i in 0..1000usize {
+        table.update((i*i) % 300, 5, 1);
+    }
+    let mut lo = 0;
+    for (value, &freq) in table.get_frequencies().iter().enumerate() {
+        let hi = lo + freq as super::Border;
+        assert_eq!(table.get_range(value), (lo, hi));
+        for offset in lo..hi {
+            assert_eq!(table.find_value(offset), (value, lo, hi));
+        }
+        lo = hi;
+    }
+    assert_eq!(lo, table.get_denominator());
+}
+
+#[test]
+fn roundtrips_large_alphabet() {
+    let num_values = 4096;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 1;
+    let values: Vec<usize> = (0..10000usize).map(|i| (i*i*31 + i) % num_values).collect();
+    let mut t0 = super::table::Model::new_flat(num_values, threshold);
+    let t1 = super::table::Model::new_custom(num_values, threshold, |i| (i&1) as u16 + 1);
+    let mut encoder = super::Encoder::new(BufWriter::new(Vec::new()));
+    for &value in values.iter() {
+        encoder.encode(value, &mut t0).unwrap();
+        let mut proxy = super::table::SumProxy::new(1, &t0, 1, &t1, 1);
+        encoder.encode(value, &mut proxy).unwrap();
+        t0.update(value, 10, 1);
+    }
+    let (writer, err) = encoder.finish();
+    err.unwrap();
+    let output = writer.into_inner().unwrap();
+    t0.reset_flat();
+    let mut decoder = super::Decoder::new(BufReader::new(&output[..]));
+    for &value in values.iter() {
+        assert_eq!(decoder.decode(&mut t0).unwrap(), value);
+        let mut proxy = super::table::SumProxy::new(1, &t0, 1, &t1, 1);
+        assert_eq!(decoder.decode(&mut proxy).unwrap(), value);
+        t0.update(value, 10, 1);
+    }
+}
+
+#[cfg(feature="unstable")]
+#[bench]
+fn table_update_speed(bh: &mut Bencher) {
+    use super::Model;
+    let mut table = super::table::Model::new_flat(256, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    bh.iter(|| {
+        for &byte in TEXT_INPUT.iter() {
+            let (lo, _) = table.get_range(byte as usize);
+            table.find_value(lo);
+            table.update(byte as usize, 10, 1);
+        }
+    });
+    bh.bytes = TEXT_INPUT.len() as u64;
+}
+
+#[cfg(feature="unstable")]
+#[bench]
+fn compress_speed(bh: &mut Bencher) {
+    use std::io::{Seek, SeekFrom, Cursor};
+    let mut storage: Vec<u8> = vec![0u8; TEXT_INPUT.len()];
+    bh.iter(|| {
+        let cursor = Cursor::new(&mut storage[..]);
+        let mut w = BufWriter::new(cursor);
+        w.seek(SeekFrom::Start(0)).unwrap();
+        let mut e = super::ByteEncoder::new(w);
+        e.write(TEXT_INPUT).unwrap();
+    });
+    bh.bytes = TEXT_INPUT.len() as u64;
+}
+
+#[cfg(feature="unstable")]
+#[bench]
+fn compress_order1_speed(bh: &mut Bencher) {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    bh.iter(|| {
+        let mut model = super::table::Order1Model::new(16, threshold);
+        let mut e = super::Encoder::new(Vec::new());
+        for &byte in TEXT_INPUT.iter() {
+            e.encode(byte, &mut model).unwrap();
+        }
+        e.finish()
+    });
+    bh.bytes = TEXT_INPUT.len() as u64;
+}
+
+#[cfg(feature="bench")]
+#[test]
+fn bench_harness() {
+    use super::bench::{self, Corpus};
+    for &kind in [Corpus::Random, Corpus::Text, Corpus::Zeros].iter() {
+        let input = bench::corpus(kind, 1<<12);
+        assert_eq!(input, bench::corpus(kind, 1<<12));
+        for measure in [bench::byte_encode, bench::byte_decode, bench::range_encode].iter() {
+            let (bytes, nanos) = measure(&input);
+            assert_eq!(bytes, input.len() as u64);
+            assert!(bench::mb_per_sec(bytes, nanos) > 0.0);
+        }
+    }
+}
+
+#[test]
+fn coded_len() {
+    let input = &LARGE_INPUT[..1<<14];
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    for &rate in [1, 8, 32].iter() {
+        let mut model = super::table::AdaptiveByteModel::new(rate, threshold);
+        let mut encoder = super::Encoder::new(Vec::new());
+        encoder.encode_all(input, &mut model).unwrap();
+        let (encoded, result) = encoder.finish();
+        result.unwrap();
+        let mut model = super::table::AdaptiveByteModel::new(rate, threshold);
+        assert_eq!(super::coded_len(input, &mut model).unwrap(), encoded.len());
+    }
+    let mut model = super::table::AdaptiveByteModel::new(1, threshold);
+    assert_eq!(super::coded_len(&[], &mut model).unwrap(), super::encode_bytes(&[0u8; 0], &mut model).unwrap().len());
+}
+
+#[test]
+fn checked_boundary() {
+    // the total at the threshold and the intervals at its ends
+    // give the largest products the coder has to handle
+    fn check<B: super::RangeBorder>(mut range: super::RangeEncoder<B>, total: super::Border) {
+        let mut output = Vec::new();
+        for i in 0..1000 {
+            let (lo, hi) = match i % 3 {
+                0 => (total - 1, total),
+                1 => (0, 1),
+                _ => (0, total),
+            };
+            range.process(total, lo, hi, |s| output.push(s)).unwrap();
+        }
+        range.get_code_tail(|s| output.push(s));
+        assert!(!output.is_empty());
+    }
+    let threshold = super::RANGE_DEFAULT_THRESHOLD;
+    check(super::RangeEncoder::<u32>::new(threshold), threshold);
+    check(super::RangeEncoder::<u32>::new_carryless(threshold), threshold);
+    check(super::RangeEncoder::<u64>::new(threshold as u64), threshold);
+    check(super::RangeEncoder::<u64>::new_carryless(1<<48), super::BORDER_MAX);
+}
+
+#[test]
+fn encode_iter() {
+    let input = &LARGE_INPUT[..1<<16];
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let expected = super::encode_bytes(input, &mut model).unwrap();
+    let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    let written = encoder.encode_iter(input.iter().cloned(), &mut model).unwrap();
+    assert_eq!(written as u64, encoder.bytes_written());
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    assert!(encoded == expected);
+}
+
+#[test]
+fn efficiency_report() {
+    // skewed: mostly small values
+    let mut seed = 1u32;
+    let input: Vec<u8> = (0..1<<16).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16).trailing_zeros() as u8
+    }).collect();
+    let mut range = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    range.set_tracking(true);
+    let mut encoder = super::Encoder::with_range(Vec::new(), range);
+    let mut model = super::table::AdaptiveByteModel::new(16, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    encoder.encode_all(&input, &mut model).unwrap();
+    let report = encoder.efficiency_report();
+    info!("{:?}", report);
+    let lost = report.bits_lost_on_threshold_cut + report.bits_lost_on_division;
+    assert!(report.ideal_bits > 0.0 && lost < 0.01 * report.ideal_bits,
+        "Lost {} bits of the ideal {}", lost, report.ideal_bits);
+    assert!((report.actual_bits as f32) < report.ideal_bits + lost + 32.0,
+        "Wrote {} bits, ideally {} with {} lost", report.actual_bits, report.ideal_bits, lost);
+}
+
+#[test]
+fn nibble_tail() {
+    let tail_bits = |bits| {
+        let mut range = super::RangeEncoder::<u32>::new(super::RANGE_DEFAULT_THRESHOLD);
+        range.set_symbol_bits(bits);
+        let mut symbols = Vec::new();
+        range.process(4, 1, 3, |s| symbols.push(s)).unwrap();
+        range.get_code_tail(|s| symbols.push(s));
+        assert!(symbols.iter().all(|&s| (s as usize) < 1<<bits));
+        symbols.len() * bits
+    };
+    let (nibble, byte) = (tail_bits(4), tail_bits(8));
+    assert!(nibble < byte, "Nibble tail of {} bits, byte tail of {}", nibble, byte);
+}
+
+#[test]
+fn roundtrips_nibble() {
+    fn roundtrip<B: super::RangeBorder>(new_range: &dyn Fn() -> super::RangeEncoder<B>, input: &[u8]) {
+        let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+        let with_nibbles = || {
+            let mut range = new_range();
+            range.set_symbol_bits(4);
+            range
+        };
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        let mut encoder = super::Encoder::with_range(Vec::new(), with_nibbles());
+        encoder.encode_all(input, &mut model).unwrap();
+        encoder.flush_align().unwrap();
+        encoder.encode_all(input, &mut model).unwrap();
+        let (encoded, result) = encoder.finish();
+        result.unwrap();
+        let mut model = super::table::AdaptiveByteModel::new(16, threshold);
+        let mut decoder = super::Decoder::with_range(&encoded[..], with_nibbles());
+        let first = decoder.decode_n(input.len(), &mut model).unwrap();
+        decoder.resync_align().unwrap();
+        let second = decoder.decode_n(input.len(), &mut model).unwrap();
+        assert!(first == input && second == input);
+    }
+    let threshold = super::RANGE_DEFAULT_THRESHOLD;
+    for &input in [&b""[..], &b"a"[..], &b"abracadabra"[..], &LARGE_INPUT[..1<<14]].iter() {
+        roundtrip(&|| super::RangeEncoder::<u32>::new(threshold), input);
+        roundtrip(&|| super::RangeEncoder::<u32>::new_carryless(threshold), input);
+        roundtrip(&|| super::RangeEncoder::<u64>::new(threshold as u64), input);
+        roundtrip(&|| super::RangeEncoder::<u64>::new_carryless(1<<40), input);
+    }
+}
+
+#[cfg(feature="checksum")]
+fn encode_frame(input: &[u8]) -> Vec<u8> {
+    let mut encoder = super::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(input).unwrap();
+    let (output, result) = encoder.finish();
+    result.unwrap();
+    output
+}
+
+#[cfg(feature="checksum")]
+fn decode_frame(encoded: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = super::frame::FrameReader::new(encoded);
+    let mut output = Vec::new();
+    try!(reader.read_to_end(&mut output));
+    assert_eq!(reader.decoded_len(), output.len() as u64);
+    Ok(output)
+}
+
+#[test]
+#[cfg(feature="checksum")]
+fn frame_valid() {
+    for &input in [&b""[..], &b"a"[..], TEXT_INPUT].iter() {
+        let encoded = encode_frame(input);
+        assert_eq!(&encoded[..4], &super::frame::MAGIC[..]);
+        assert_eq!(&decode_frame(&encoded).unwrap()[..], input);
+    }
+    // concatenated frames
+    let mut encoded = encode_frame(b"abracadabra");
+    encoded.extend(encode_frame(TEXT_INPUT));
+    let mut reader = super::frame::FrameReader::new(&encoded[..]);
+    let mut first = Vec::new();
+    reader.read_to_end(&mut first).unwrap();
+    let rest = reader.into_inner();
+    assert_eq!(&first[..], b"abracadabra");
+    assert_eq!(&decode_frame(rest).unwrap()[..], TEXT_INPUT);
+}
+
+#[test]
+#[cfg(feature="checksum")]
+fn frame_truncated() {
+    let encoded = encode_frame(TEXT_INPUT);
+    for &size in [0, 3, 5, encoded.len() / 2, encoded.len() - 13, encoded.len() - 1].iter() {
+        assert!(decode_frame(&encoded[..size]).is_err(), "Truncated to {} bytes", size);
+    }
+}
+
+#[test]
+#[cfg(feature="checksum")]
+fn frame_corrupted() {
+    let mut encoded = encode_frame(TEXT_INPUT);
+    let last = encoded.len() - 1;
+    encoded[last] ^= 1;
+    let err = decode_frame(&encoded).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "checksum mismatch");
+    let mut encoded = encode_frame(TEXT_INPUT);
+    encoded[0] ^= 1;
+    assert_eq!(decode_frame(&encoded).unwrap_err().to_string(), "invalid frame magic");
+}
+
+#[test]
+fn clone_model() {
+    use super::table::{AdaptiveByteModel, Order1Model, StaticModel};
+    fn speculate<M: super::Model<u8> + Clone + Default>() {
+        let head = &LARGE_INPUT[..1<<12];
+        let tails = [&TEXT_INPUT[..1000], &LARGE_INPUT[1<<14..1<<15]];
+        let mut model = M::default();
+        let prefix = super::encode_bytes(head, &mut model).unwrap();
+        let mut decoder_model = M::default();
+        assert_eq!(&super::decode_bytes(&prefix, &mut decoder_model, head.len()).unwrap()[..], head);
+        // each continuation is coded from its own snapshot of the model
+        for tail in tails.iter() {
+            let encoded = super::encode_bytes(tail, &mut model.clone()).unwrap();
+            let decoded = super::decode_bytes(&encoded, &mut decoder_model.clone(), tail.len()).unwrap();
+            assert_eq!(&decoded[..], *tail);
+        }
+    }
+    speculate::<AdaptiveByteModel>();
+    speculate::<Order1Model>();
+    let model = StaticModel::default();
+    assert_eq!(model.get_num_values(), 256);
+    assert_eq!(super::Model::get_range(&model.clone(), 0x41), super::Model::get_range(&model, 0x41));
+}
+
+#[test]
+fn probability() {
+    use super::Model;
+    let mut model = super::table::AdaptiveByteModel::default();
+    let flat = model.probability(b'a');
+    assert_eq!(flat, 1.0 / 256.0);
+    model.prime(&[b'a'; 100]);
+    let p = model.probability(b'a');
+    assert!((0..256).all(|b| b == b'a' as usize || model.probability(b as u8) < p / 10.0),
+        "Probability of the primed value is {}", p);
+    let total: f64 = (0..256).map(|b| model.probability(b as u8)).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn oversized_total() {
+    use super::Error;
+    let mut re = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    re.process(1<<20, 0, 1, |_| ()).unwrap();
+    let (low, width) = (re.bounds().0, re.range_width());
+    let total = width + 1;
+    // encoding and decoding fail with the same error
+    assert_eq!(re.process(total, 0, 1, |_| ()), Err(Error::RangeTooNarrow(total, width)));
+    assert_eq!(re.query(total, low), Err(Error::RangeTooNarrow(total, width)));
+    assert_eq!(Error::RangeTooNarrow(total, width).to_string(),
+        format!("total {} exceeds the current range width {}", total, width));
+    assert_eq!(re.query(0, low), Err(Error::BadInterval(0, 0, 0)));
+}
+
+#[test]
+fn order1_compact() {
+    use super::table::{AdaptiveByteModel, Order1Model};
+    let input = &LARGE_INPUT[..1<<16];
+    let compact = Order1Model::compact();
+    assert!(compact.memory_size() <= 64 << 10, "Compact model takes {} bytes", compact.memory_size());
+    assert!(compact.memory_size() < Order1Model::default().memory_size() / 4);
+    let encoded = super::encode_bytes(input, &mut compact.clone()).unwrap();
+    let decoded = super::decode_bytes(&encoded, &mut compact.clone(), input.len()).unwrap();
+    assert!(&decoded[..] == input);
+    let order0 = super::encode_bytes(input, &mut AdaptiveByteModel::default()).unwrap();
+    assert!(encoded.len() < order0.len(),
+        "Compact order-1 size {}, order-0 size {}", encoded.len(), order0.len());
+}
+
+#[test]
+fn literal_fallback() {
+    use super::escape::LiteralFallbackModel;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    // the low half of the bytes first, then the unseen high half
+    let mut input: Vec<u8> = LARGE_INPUT[..1<<12].iter().map(|&b| b & 0x7F).collect();
+    input.extend((0..1<<12).map(|i| 0x80 | (i * 37) as u8));
+    let mut model = LiteralFallbackModel::new(256, 16, threshold);
+    assert_eq!(model.get_num_values(), 256);
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &b in input.iter() {
+        model.encode(&mut encoder, b as usize).unwrap();
+    }
+    assert!(model.encode(&mut encoder, 256).is_err());
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    let mut model = LiteralFallbackModel::new(256, 16, threshold);
+    let mut decoder = super::Decoder::new(&encoded[..]);
+    let decoded: Vec<u8> = input.iter().map(|_| model.decode(&mut decoder).unwrap() as u8).collect();
+    assert!(decoded == input);
+    assert!(input.iter().all(|&b| model.primary.is_known(b as usize)));
+}
+
+#[test]
+fn preset_select() {
+    use super::preset::{self, PresetRegistry};
+    let registry = PresetRegistry::new();
+    let mut seed = 7u32;
+    let random: Vec<u8> = (0..1<<12).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    }).collect();
+    let sparse: Vec<u8> = random.iter().map(|&b| if b < 0xE0 {0} else {b & 7}).collect();
+    let inputs = [(TEXT_INPUT, preset::PRESET_TEXT), (&random[..], preset::PRESET_BINARY),
+        (&sparse[..], preset::PRESET_SPARSE)];
+    for &(input, expected) in inputs.iter() {
+        let (encoded, selector) = registry.encode_best(Vec::new(), input);
+        let selector = selector.unwrap();
+        assert_eq!(selector, expected);
+        let sizes: Vec<usize> = (0..registry.get_num_presets()).map(|i| {
+            let (encoded, result) = registry.encode_with(Vec::new(), i as u8, input);
+            result.unwrap();
+            encoded.len()
+        }).collect();
+        assert_eq!(encoded.len(), *sizes.iter().min().unwrap(), "Sizes {:?}", sizes);
+        assert_eq!(encoded[0], selector);
+        assert!(&registry.decode(&encoded[..]).unwrap()[..] == input);
+    }
+    assert!(registry.decode(&[3u8, 0, 0][..]).is_err());
+}
+
+#[test]
+fn finalize() {
+    struct Owner {
+        encoder: super::Encoder<Vec<u8>>,
+        model: super::table::AdaptiveByteModel,
+    }
+    let mut owner = Owner {
+        encoder: super::Encoder::new(Vec::new()),
+        model: super::table::AdaptiveByteModel::default(),
+    };
+    owner.encoder.encode_all(TEXT_INPUT, &mut owner.model).unwrap();
+    owner.encoder.finalize().unwrap();
+    let err = owner.encoder.encode(0u8, &mut owner.model).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    owner.encoder.finalize().unwrap();
+    let encoded = owner.encoder.get_mut().clone();
+    let decoded = super::decode_bytes(&encoded, &mut super::table::AdaptiveByteModel::default(),
+        TEXT_INPUT.len()).unwrap();
+    assert!(&decoded[..] == TEXT_INPUT);
+    let (output, result) = owner.encoder.finish();
+    result.unwrap();
+    assert!(output == encoded);
+}
+
+#[test]
+fn raw_intervals() {
+    // a value v of the alphabet [0,n) takes [v*v, (v+1)*(v+1)) of n*n
+    let values: Vec<u32> = LARGE_INPUT[..1<<12].iter().map(|&b| (b % 23) as u32).collect();
+    let n = 23;
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &v in values.iter() {
+        encoder.encode_freq(n*n, v*v, (v+1)*(v+1)).unwrap();
+    }
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    let mut decoder = super::Decoder::new(&encoded[..]);
+    for &v in values.iter() {
+        let offset = decoder.decode_freq(n*n).unwrap();
+        let value = (offset as f64).sqrt() as u32;
+        assert_eq!(value, v);
+        if value > 0 {
+            assert!(decoder.consume_freq(n*n, 0, value*value).is_err());
+        }
+        decoder.consume_freq(n*n, value*value, (value+1)*(value+1)).unwrap();
+    }
+}
+
+#[test]
+fn expected_len() {
+    let input = b"abracadabra";
+    let encoded = super::encode_bytes(input, &mut super::table::AdaptiveByteModel::default()).unwrap();
+    let mut model = super::table::AdaptiveByteModel::default();
+    let mut decoder = super::Decoder::with_expected_len(&encoded[..], input.len() as u64);
+    let decoded = decoder.decode_n(input.len(), &mut model).unwrap();
+    assert_eq!(&decoded[..], &input[..]);
+    assert_eq!(decoder.get_remaining(), Some(0));
+    assert_eq!(decoder.try_decode(&mut model).unwrap(), None);
+    let err = decoder.decode(&mut model).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(decoder.decode_freq(10).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+/// Significance bits and values of some residuals, zero being insignificant
+fn residuals() -> Vec<(bool, u8)> {
+    LARGE_INPUT[..1<<12].iter().map(|&b| {
+        let value = if b % 3 == 0 {0} else {b};
+        (value != 0, value)
+    }).collect()
+}
+
+#[test]
+fn interleaved_models() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let input = residuals();
+    let mut significance = super::bin::Model::new_flat(threshold, 4);
+    let mut values = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut encoder = super::Encoder::new(Vec::new());
+    for &(bit, value) in input.iter() {
+        encoder.encode(bit, &mut significance).unwrap();
+        if bit {
+            encoder.encode(value, &mut values).unwrap();
+        }
+    }
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    let mut significance = super::bin::Model::new_flat(threshold, 4);
+    let mut values = super::table::AdaptiveByteModel::new(16, threshold);
+    let mut decoder = super::Decoder::new(&encoded[..]);
+    for &(bit, value) in input.iter() {
+        assert_eq!(decoder.decode(&mut significance).unwrap(), bit);
+        if bit {
+            assert_eq!(decoder.decode(&mut values).unwrap(), value);
+        }
+    }
+}
+
+fn encode_dual(input: &[(bool, u8)]) -> Vec<u8> {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut encoder = super::dual::DualModelEncoder::new(Vec::new(),
+        super::bin::Model::new_flat(threshold, 4), super::table::AdaptiveByteModel::new(16, threshold));
+    for &(bit, value) in input.iter() {
+        encoder.encode_pair(bit, value).unwrap();
+    }
+    let (encoded, result) = encoder.finish();
+    result.unwrap();
+    encoded
+}
+
+#[test]
+fn roundtrips_dual() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let input = residuals();
+    let encoded = encode_dual(&input);
+    let mut decoder = super::dual::DualModelDecoder::new(&encoded[..],
+        super::bin::Model::new_flat(threshold, 4), super::table::AdaptiveByteModel::new(16, threshold));
+    for &(bit, value) in input.iter() {
+        assert_eq!(decoder.decode_first::<bool>().unwrap(), bit);
+        assert_eq!(decoder.decode_second::<u8>().unwrap(), value);
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "out of order")]
+fn dual_misordered() {
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let encoded = encode_dual(&residuals());
+    let mut decoder = super::dual::DualModelDecoder::new(&encoded[..],
+        super::bin::Model::new_flat(threshold, 4), super::table::AdaptiveByteModel::new(16, threshold));
+    let _ = decoder.decode_second::<u8>();
+}
+
+#[test]
+fn decay_model() {
+    use super::Model;
+    use super::table::{AdaptiveByteModel, DecayModel};
+    // skewed towards one end of the alphabet, then the other
+    let mut seed = 3u32;
+    let input: Vec<u8> = (0..1<<13).map(|i| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let x = ((seed >> 16).trailing_zeros() * 4) as u8;
+        if i < 1<<12 {x} else {!x}
+    }).collect();
+    // the cost of adapting to the flipped distribution
+    fn cost<M: Model<u8>>(input: &[u8], mut model: M) -> f64 {
+        let mut bits = 0.0;
+        for (i, &b) in input.iter().enumerate() {
+            if i >= input.len() / 2 && i < input.len() / 2 + 1024 {
+                bits += super::estimate_bits(b, &model);
+            }
+            model.update(b);
+        }
+        bits
+    }
+    let decay = cost(&input, DecayModel::new(64));
+    let steady = cost(&input, AdaptiveByteModel::new(1, super::RANGE_DEFAULT_THRESHOLD));
+    assert!(decay < steady, "Decay costs {} bits, the steady model {}", decay, steady);
+    let encoded = super::encode_bytes(&input, &mut DecayModel::new(64)).unwrap();
+    let decoded = super::decode_bytes(&encoded, &mut DecayModel::new(64), input.len()).unwrap();
+    assert!(decoded == input);
+}
+
+#[test]
+fn validate() {
+    let input = &LARGE_INPUT[..1<<12];
+    let encoded = super::encode_bytes(input, &mut super::table::AdaptiveByteModel::default()).unwrap();
+    let check = |data: &[u8], len| {
+        let mut decoder = super::Decoder::new(data);
+        decoder.validate(&mut super::table::AdaptiveByteModel::default(), len)
+    };
+    check(&encoded, input.len()).unwrap();
+    assert!(check(&encoded[..encoded.len() / 2], input.len()).is_err());
+    let mut extended = encoded.clone();
+    extended.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(check(&extended, input.len()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn dump_intervals() {
+    use super::Model;
+    fn check<V, M>(model: &M, intervals: Vec<(V, super::Border, super::Border)>)
+        where V: Copy + ::std::fmt::Display + ::std::fmt::Debug + PartialEq, M: Model<V>
+    {
+        let mut next = 0;
+        for &(value, lo, hi) in intervals.iter() {
+            assert_eq!(lo, next, "Gap before the value {}", value);
+            assert!(lo <= hi);
+            if lo < hi {
+                assert_eq!(model.get_range(value), (lo, hi));
+            }
+            next = hi;
+        }
+        assert_eq!(next, model.get_denominator());
+    }
+    let mut model = super::table::AdaptiveByteModel::default();
+    model.prime(TEXT_INPUT);
+    check(&model, model.dump_intervals());
+    let mut model = super::table::DecayModel::new(100);
+    for &b in TEXT_INPUT.iter() {
+        model.update(b);
+    }
+    check(&model, model.dump_intervals());
+    let mut model = super::table::FreqModel::new(1000, 8, 1<<12);
+    for v in 0..300 {
+        model.update(v * 3);
+    }
+    check(&model, model.dump_intervals());
+    let mut counts = vec![0u32; 300];
+    counts[5] = 10;
+    counts[200] = 3;
+    let model = super::table::StaticModel::from_histogram(&counts);
+    check(&model, model.dump_intervals());
+    let table = super::table::Model::new_custom(10, 1<<10, |i| (i % 3) as super::table::Frequency);
+    let intervals = table.dump_intervals();
+    assert_eq!(intervals.len(), 10);
+    assert_eq!(intervals[9].2, table.get_denominator());
+}
+
+#[test]
+fn uniform_model() {
+    use super::table::UniformModel;
+    let mut seed = 11u32;
+    let input: Vec<usize> = (0..1<<14).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8 as usize
+    }).collect();
+    let mut model = UniformModel::new(256);
+    let encoded = super::encode_bytes(&input, &mut model).unwrap();
+    assert!(encoded.len() >= input.len() && encoded.len() <= input.len() + 8,
+        "Coded {} bytes into {}", input.len(), encoded.len());
+    assert!(super::decode_bytes(&encoded, &mut model, input.len()).unwrap() == input);
+    // 3 values take log2(3) bits each
+    let values: Vec<usize> = input.iter().map(|&b| (b % 3) as usize).collect();
+    let mut model = UniformModel::new(3);
+    let encoded = super::encode_bytes(&values, &mut model).unwrap();
+    let ideal = values.len() as f64 * 3f64.log2() / 8.0;
+    assert!((encoded.len() as f64) < ideal * 1.01 + 8.0, "Coded into {} bytes, ideally {}", encoded.len(), ideal);
+    assert!(super::decode_bytes(&encoded, &mut model, values.len()).unwrap() == values);
+}
+
+#[test]
+fn output_limit() {
+    use std::io;
+    use super::table::UniformModel;
+    let limit = 100;
+    let mut seed = 3u32;
+    let input: Vec<usize> = (0..1000).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8 as usize
+    }).collect();
+    let mut model = UniformModel::new(256);
+    // find the value pushing the output over the limit
+    let mut e = super::Encoder::new(Vec::new());
+    let mut total = 0;
+    let stop = input.iter().position(|&v| {
+        total += e.encode(v, &mut model).unwrap();
+        total > limit
+    }).unwrap();
+    let mut e = super::Encoder::with_output_limit(Vec::new(), limit as u64);
+    for (i, &v) in input.iter().enumerate() {
+        match e.encode(v, &mut model) {
+            Ok(_) => assert!(i < stop, "Value {} passed the limit", i),
+            Err(err) => {
+                assert_eq!(i, stop);
+                assert_eq!(err.kind(), io::ErrorKind::Other);
+                break
+            },
+        }
+    }
+    let written = e.bytes_written();
+    assert!(written <= limit as u64, "Wrote {} bytes over the limit {}", written, limit);
+    assert_eq!(e.into_inner().len() as u64, written);
+    // any range takes the limit
+    let mut e = super::Encoder::with_range(Vec::new(), super::RangeEncoder::<u64>::new(1<<40));
+    e.set_output_limit(limit as u64);
+    let result = input.iter().map(|&v| e.encode(v, &mut model)).find(|r| r.is_err());
+    assert_eq!(result.unwrap().unwrap_err().kind(), io::ErrorKind::Other);
+    assert!(e.bytes_written() <= limit as u64);
+}
+
+#[test]
+fn window_model() {
+    use super::table::{Order1Model, WindowModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let text: Vec<u8> = TEXT_INPUT.iter().cycle().take(TEXT_INPUT.len() * 8).cloned().collect();
+    let order1 = super::encode_bytes(&text, &mut Order1Model::new(16, threshold)).unwrap();
+    let encoded = super::encode_bytes(&text, &mut WindowModel::new(6)).unwrap();
+    // most of the output is the first copy, coded without any context
+    assert!(encoded.len() * 2 < order1.len(),
+        "Window size {} is not well below order-1 {}", encoded.len(), order1.len());
+    let decoded = super::decode_bytes(&encoded, &mut WindowModel::new(6), text.len()).unwrap();
+    assert_eq!(decoded, text);
+}
+
+#[test]
+fn try_finish() {
+    let mut range = super::RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD);
+    range.set_tracking(true);
+    let mut encoder = super::Encoder::with_range(Vec::new(), range);
+    let mut model = super::table::AdaptiveByteModel::new(16, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    let payload = encoder.encode_all(TEXT_INPUT, &mut model).unwrap();
+    let (encoded, report) = encoder.try_finish().unwrap();
+    assert!(report.tail_bytes > 0);
+    assert_eq!(payload + report.tail_bytes, encoded.len());
+    let lost = report.bits_lost_on_threshold_cut + report.bits_lost_on_division;
+    assert!(lost > 0.0 && lost < 8.0 * encoded.len() as f32, "Lost {} bits", lost);
+    let mut model = super::table::AdaptiveByteModel::new(16, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    assert_eq!(&super::decode_bytes(&encoded, &mut model, TEXT_INPUT.len()).unwrap()[..], TEXT_INPUT);
+}
+
+#[test]
+fn stalled_reader() {
+    use std::io::{self, Read};
+    struct Empty;
+    impl Read for Empty {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+    struct Interrupted;
+    impl Read for Interrupted {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Interrupted, "no progress"))
+        }
+    }
+    let mut model = super::table::AdaptiveByteModel::new(16, super::RANGE_DEFAULT_THRESHOLD >> 2);
+    let err = super::Decoder::new(Empty).decode(&mut model).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let err = super::Decoder::new(Interrupted).decode(&mut model).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    let mut d = super::ByteDecoder::new(Interrupted);
+    assert!(d.read(&mut [0u8; 4]).is_err());
+}
+
+#[test]
+fn nibble_model() {
+    use super::table::{AdaptiveByteModel, NibbleModel, UniformModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut seed = 5u32;
+    let hex: Vec<u8> = (0..1<<12).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        b"0123456789abcdef"[(seed >> 16) as usize & 0xF]
+    }).collect();
+    let mut model = NibbleModel::new(16, threshold);
+    let mut e = super::Encoder::new(Vec::new());
+    for &byte in hex.iter() {
+        model.encode(&mut e, byte).unwrap();
+    }
+    let (encoded, err) = e.finish();
+    err.unwrap();
+    let flat: Vec<usize> = hex.iter().map(|&b| b as usize).collect();
+    let flat = super::encode_bytes(&flat, &mut UniformModel::new(256)).unwrap();
+    let adaptive = super::encode_bytes(&hex, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    assert!(encoded.len() < flat.len() && encoded.len() < adaptive.len(),
+        "Nibble size {} is not below the flat {} and adaptive {}", encoded.len(), flat.len(), adaptive.len());
+    let mut model = NibbleModel::new(16, threshold);
+    let mut d = super::Decoder::new(&encoded[..]);
+    for (i, &byte) in hex.iter().enumerate() {
+        assert_eq!(model.decode(&mut d).unwrap(), byte, "Byte {} differs", i);
+    }
+}
+
+#[test]
+fn vec_encoder() {
+    use super::table::UniformModel;
+    let mut seed = 9u32;
+    let input: Vec<usize> = (0..100<<10).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8 as usize
+    }).collect();
+    let capacity = input.len() + 16;
+    let mut model = UniformModel::new(256);
+    let mut e = super::VecEncoder::with_capacity(capacity);
+    for &value in input.iter() {
+        e.encode(value, &mut model).unwrap();
+        assert_eq!(e.get_mut().capacity(), capacity);
+    }
+    let (encoded, err) = e.finish();
+    err.unwrap();
+    assert_eq!(encoded.capacity(), capacity);
+    assert!(super::decode_bytes(&encoded, &mut model, input.len()).unwrap() == input);
+}
+
+#[test]
+fn merge_models() {
+    use super::table::AdaptiveByteModel;
+    use super::Model;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let (corpus, holdout) = LARGE_INPUT[..1<<16].split_at(3<<14);
+    let (first, second) = corpus.split_at(corpus.len() / 2);
+    let mut full = AdaptiveByteModel::new(16, threshold);
+    full.prime(corpus);
+    let mut merged = AdaptiveByteModel::new(16, threshold);
+    merged.prime(first);
+    let mut other = AdaptiveByteModel::new(16, threshold);
+    other.prime(second);
+    let half = merged.clone();
+    merged.merge(&other);
+    assert!(merged.get_denominator() < threshold);
+    let mut model = merged.clone();
+    let encoded = super::encode_bytes(holdout, &mut model).unwrap();
+    let mut model = merged.clone();
+    assert_eq!(&super::decode_bytes(&encoded, &mut model, holdout.len()).unwrap()[..], holdout);
+    // evaluate the learned statistics alone
+    let holdout_len = |model: &AdaptiveByteModel| {
+        let mut model = model.clone();
+        model.increment = 0;
+        super::coded_len(holdout, &mut model).unwrap()
+    };
+    let (len_full, len_merged, len_half) = (holdout_len(&full), holdout_len(&merged), holdout_len(&half));
+    assert!(len_merged as f32 <= len_full as f32 * 1.02,
+        "Merged size {} is not close to the full {}, half {}", len_merged, len_full, len_half);
+    assert!(len_merged <= len_half);
+}
+
+#[test]
+fn endianness() {
+    use std::io;
+    use super::{Decoder, Encoder, Endianness};
+    use super::table::AdaptiveByteModel;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let message = &TEXT_INPUT[..100];
+    let mut e = Encoder::with_endianness(Vec::new(), Endianness::Little);
+    e.encode_all(message, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    let (little, err) = e.finish();
+    err.unwrap();
+    // the little-endian tail is the reversed padded big-endian one
+    let mut e = Encoder::new(Vec::new());
+    e.encode_all(message, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    let (big, err) = e.finish_padded();
+    err.unwrap();
+    assert_eq!(big.len(), little.len());
+    let body = big.len() - 4;
+    assert_eq!(&big[..body], &little[..body]);
+    let tail: Vec<u8> = big[body..].iter().rev().cloned().collect();
+    assert_eq!(&little[body..], &tail[..]);
+    let mut d = Decoder::with_endianness(&little[..], Endianness::Little);
+    let mut model = AdaptiveByteModel::new(16, threshold);
+    assert_eq!(&d.decode_n(message.len(), &mut model).unwrap()[..], message);
+    let mut d = Decoder::with_endianness(&little[..], Endianness::Little);
+    d.validate(&mut AdaptiveByteModel::new(16, threshold), message.len()).unwrap();
+    // the little-endian tail can't be followed by other data
+    let mut e = Encoder::with_endianness(Vec::new(), Endianness::Little);
+    assert_eq!(e.flush_align().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    // any range takes the byte order
+    let new_range = || super::RangeEncoder::<u64>::new(1<<40);
+    let mut e = Encoder::with_range(Vec::new(), new_range());
+    e.set_endianness(Endianness::Little);
+    e.encode_all(message, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    let (little, err) = e.finish();
+    err.unwrap();
+    let mut d = Decoder::with_range(&little[..], new_range());
+    d.set_endianness(Endianness::Little);
+    let mut model = AdaptiveByteModel::new(16, threshold);
+    assert_eq!(&d.decode_n(message.len(), &mut model).unwrap()[..], message);
+}
+
+#[test]
+fn alphabet_map() {
+    use super::alphabet::AlphabetMap;
+    use super::table::{AdaptiveByteModel, FreqModel};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    // 30 distinct bytes, spread over the whole range
+    let mut seed = 13u32;
+    let input: Vec<u8> = (0..1<<12).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (((seed >> 16) % 30) * 8 + 3) as u8
+    }).collect();
+    let map = AlphabetMap::from_data(&input);
+    assert_eq!(map.get_num_symbols(), 30);
+    let mut output = Vec::new();
+    map.write_to(&mut output).unwrap();
+    let indices: Vec<u32> = input.iter().map(|&b| map.map(b).unwrap() as u32).collect();
+    let mut model = FreqModel::new(map.get_num_symbols(), 16, threshold);
+    output.extend(super::encode_bytes(&indices, &mut model).unwrap());
+    let plain = super::encode_bytes(&input, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    assert!(output.len() < plain.len(), "Mapped size {} is not below the plain {}", output.len(), plain.len());
+    let mut reader = &output[..];
+    let map = AlphabetMap::read_from(&mut reader).unwrap();
+    let mut model = FreqModel::new(map.get_num_symbols(), 16, threshold);
+    let decoded: Vec<u8> = super::decode_bytes(reader, &mut model, input.len()).unwrap()
+        .into_iter().map(|i| map.unmap(i as usize)).collect();
+    assert_eq!(decoded, input);
+}
+
+#[test]
+#[cfg(feature="checksum")]
+fn running_checksum() {
+    use checksum::adler;
+    let mut encoder = super::ByteEncoder::new(Vec::new());
+    encoder.write_all(TEXT_INPUT).unwrap();
+    let (encoded, err) = encoder.finish();
+    err.unwrap();
+    let mut decoder = super::ByteDecoder::new(&encoded[..]);
+    assert_eq!(decoder.checksum(), adler::State32::new().result());
+    let mut first = [0u8; 100];
+    decoder.read_exact(&mut first).unwrap();
+    let mut state = adler::State32::new();
+    state.feed(&first);
+    assert_eq!(decoder.checksum(), state.result());
+    let mut rest = Vec::new();
+    decoder.read_to_end(&mut rest).unwrap();
+    state.feed(&rest);
+    assert_eq!(decoder.checksum(), state.result());
+    assert_eq!(state.result(), {
+        let mut full = adler::State32::new();
+        full.feed(TEXT_INPUT);
+        full.result()
+    });
+}
+
+#[test]
+fn process_chunked() {
+    let mut seed = 17u32;
+    let intervals: Vec<(super::Border, super::Border, super::Border)> = (0..1000).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let total = (seed >> 20) + 2;
+        let from = (seed >> 8) % (total - 1);
+        (total, from, from + 1 + (seed % (total - from)))
+    }).collect();
+    let threshold = super::RANGE_DEFAULT_THRESHOLD;
+    let mut expected = Vec::new();
+    let mut range = super::RangeEncoder::<u32>::new(threshold);
+    for &(total, from, to) in intervals.iter() {
+        range.process(total, from, to, |s| expected.push(s)).unwrap();
+    }
+    range.get_code_tail(|s| expected.push(s));
+    // a sink of two symbols, drained once it's full
+    let mut output = Vec::new();
+    let mut sink = Vec::with_capacity(2);
+    let mut num_pauses = 0;
+    let mut range = super::RangeEncoder::<u32>::new(threshold);
+    for &(total, from, to) in intervals.iter() {
+        let mut num_shift = {
+            let mut push = |s| sink.len() < 2 && { sink.push(s); true };
+            range.process_chunked(total, from, to, &mut push).unwrap()
+        };
+        while range.has_pending_shifts() {
+            num_pauses += 1;
+            output.extend(sink.drain(..));
+            num_shift += range.resume_chunked(|s| sink.len() < 2 && { sink.push(s); true });
+        }
+        assert!(num_shift <= range.word_symbols());
+    }
+    output.extend(sink.drain(..));
+    range.get_code_tail(|s| output.push(s));
+    assert!(num_pauses > 0);
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn batch_ranges() {
+    use super::Model;
+    use super::table;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let mut seed = 19u32;
+    let mut fenwick = table::Model::new_flat(256, threshold);
+    let values: Vec<usize> = (0..1000).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as usize & 0xFF
+    }).collect();
+    for &value in values.iter() {
+        fenwick.update(value, 5, 1);
+    }
+    let counts: Vec<u32> = fenwick.get_frequencies().iter().map(|&f| f as u32).collect();
+    let fixed = table::StaticModel::from_histogram(&counts);
+    fn check<M: Model<usize>>(model: &M, values: &[usize]) {
+        // both the short and the long batches
+        for len in [1, 10, values.len()].iter() {
+            let values = &values[..*len];
+            let mut ranges = vec![(0, 0); values.len()];
+            model.get_ranges(values, &mut ranges);
+            for (&value, &range) in values.iter().zip(ranges.iter()) {
+                assert_eq!(range, model.get_range(value), "Value {} differs", value);
+            }
+        }
+    }
+    check(&fenwick, &values);
+    check(&fixed, &values);
+    check(&table::UniformModel::new(256), &values);
+    let boxed: Box<dyn Model<usize>> = Box::new(fixed.clone());
+    check(&boxed, &values);
+}
+
+#[test]
+fn export_state() {
+    use super::{Encoder, EncoderState, RangeEncoder};
+    use super::table::AdaptiveByteModel;
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let message = &LARGE_INPUT[..1<<14];
+    let new_range = |carryless, bits| {
+        let mut range = if carryless {
+            RangeEncoder::new_carryless(super::RANGE_DEFAULT_THRESHOLD)
+        }else {
+            RangeEncoder::new(super::RANGE_DEFAULT_THRESHOLD)
+        };
+        range.set_symbol_bits(bits);
+        range
+    };
+    for &(carryless, bits) in [(false, 8), (true, 8), (false, 4)].iter() {
+        let mut e = Encoder::with_range(Vec::new(), new_range(carryless, bits));
+        e.encode_all(message, &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+        let (expected, err) = e.finish();
+        err.unwrap();
+        // three chunks, each by a fresh encoder over a separate buffer
+        let mut model = AdaptiveByteModel::new(16, threshold);
+        let mut output = Vec::new();
+        let mut state = Encoder::with_range(Vec::new(), new_range(carryless, bits)).export_state();
+        for (i, chunk) in message.chunks(message.len() / 3 + 1).enumerate() {
+            let mut e = Encoder::from_state(Vec::new(), EncoderState::from_bytes(&state.to_bytes()).unwrap());
+            e.encode_all(chunk, &mut model).unwrap();
+            state = e.export_state();
+            if i == 2 {
+                let (last, err) = e.finish();
+                err.unwrap();
+                output.extend(last);
+            }else {
+                output.extend(e.into_inner());
+            }
+        }
+        assert!(output == expected, "Chunked output differs, carryless {}, bits {}", carryless, bits);
+    }
+    // every intermediate state survives serialization, including the wrapped carryless ones
+    let mut e = Encoder::with_range(Vec::new(), new_range(true, 8));
+    let mut model = AdaptiveByteModel::new(16, threshold);
+    let mut seed = 1u32;
+    for _ in 0..5000 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        e.encode((seed >> 16) as u8, &mut model).unwrap();
+        let state = e.export_state();
+        assert_eq!(EncoderState::from_bytes(&state.to_bytes()).unwrap(), state);
+    }
+    assert!(EncoderState::<u32>::from_bytes(&[0; 5]).is_err());
+}
+
+#[test]
+fn init_strategy() {
+    use super::table::{AdaptiveByteModel, InitStrategy};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let coded_size = |message: &[u8], init: InitStrategy| {
+        let mut model = AdaptiveByteModel::with_init(init, 16, threshold);
+        let mut e = super::Encoder::new(Vec::new());
+        for &byte in message.iter() {
+            model.encode(&mut e, byte).unwrap();
+        }
+        let (encoded, err) = e.finish();
+        err.unwrap();
+        let mut model = AdaptiveByteModel::with_init(init, 16, threshold);
+        let mut d = super::Decoder::new(&encoded[..]);
+        let decoded: Vec<u8> = message.iter().map(|_| model.decode(&mut d).unwrap()).collect();
+        assert_eq!(&decoded[..], message);
+        encoded.len()
+    };
+    let message = &TEXT_INPUT[..200];
+    let mut counts = [0u32; 256];
+    for &byte in TEXT_INPUT[200..].iter() {
+        counts[byte as usize] += 1;
+    }
+    let sizes: Vec<usize> = [InitStrategy::Uniform, InitStrategy::Zero, InitStrategy::Histogram(&counts)]
+        .iter().map(|&init| coded_size(message, init)).collect();
+    info!("Sizes of the uniform, zero and histogram starts: {:?}", sizes);
+    assert_eq!(sizes[0], super::encode_bytes(message, &mut AdaptiveByteModel::new(16, threshold)).unwrap().len());
+    assert!(sizes[2] < sizes[0], "Histogram start size {} is not below the uniform {}", sizes[2], sizes[0]);
+    // the zero start doesn't spend the probability on the bytes never seen
+    let small: Vec<u8> = message.iter().map(|&b| b'a' + (b & 3)).collect();
+    let (uniform, zero) = (coded_size(&small, InitStrategy::Uniform), coded_size(&small, InitStrategy::Zero));
+    info!("Sizes of the uniform and zero starts on 4 bytes: {} {}", uniform, zero);
+    assert!(zero < uniform, "Zero start size {} is not below the uniform {}", zero, uniform);
+    // the unseen bytes of the zero start have no interval of their own
+    let mut model = AdaptiveByteModel::with_init(InitStrategy::Zero, 16, threshold);
+    assert!(super::encode_bytes(b"a", &mut model).is_err());
+}
+
+#[test]
+fn self_check() {
+    use super::table::{AdaptiveByteModel, Order1Model};
+    let threshold = super::RANGE_DEFAULT_THRESHOLD >> 2;
+    let input = &LARGE_INPUT[..1<<14];
+    let mut e = super::Encoder::new(Vec::new());
+    e.encode_all(input, &mut Order1Model::new(16, threshold)).unwrap();
+    e.flush_align().unwrap();
+    e.encode_iter(input.iter().cloned(), &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    let (expected, err) = e.finish();
+    err.unwrap();
+    let mut e = super::Encoder::with_self_check(Vec::new());
+    e.encode_all(input, &mut Order1Model::new(16, threshold)).unwrap();
+    e.flush_align().unwrap();
+    e.encode_iter(input.iter().cloned(), &mut AdaptiveByteModel::new(16, threshold)).unwrap();
+    let (encoded, err) = e.finish();
+    err.unwrap();
+    assert!(encoded == expected);
+    // combined with the other settings of any range
+    let new_ranges: [&dyn Fn() -> super::RangeEncoder<u64>; 2] = [
+        &|| super::RangeEncoder::new(1<<40),
+        &|| super::RangeEncoder::new_carryless(1<<40),
+    ];
+    for new_range in new_ranges.iter() {
+        let encode = |check: bool| {
+            let mut e = super::Encoder::with_range(Vec::new(), new_range());
+            e.set_endianness(super::Endianness::Little);
+            e.set_output_limit(1<<20);
+            if check {
+                e.set_self_check();
+            }
+            e.encode_all(input, &mut Order1Model::new(16, threshold)).unwrap();
+            let (encoded, err) = e.finish();
+            err.unwrap();
+            encoded
+        };
+        assert!(encode(true) == encode(false));
+    }
+}
+
+#[test]
+#[should_panic(expected = "has to be set before coding")]
+fn self_check_late() {
+    let mut e = super::Encoder::new(Vec::new());
+    e.encode(5u8, &mut super::table::AdaptiveByteModel::default()).unwrap();
+    e.set_self_check();
+}
+
+/// A model giving the values overlapping intervals
+struct OverlappingModel;
+
+impl super::Model<super::Border> for OverlappingModel {
+    fn get_range(&self, value: super::Border) -> (super::Border, super::Border) {
+        (value, value + 2)
+    }
+    fn find_value(&self, offset: super::Border) -> (super::Border, super::Border, super::Border) {
+        (offset, offset, offset + 2)
+    }
+    fn get_denominator(&self) -> super::Border {
+        258
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Self-check failed")]
+fn self_check_broken_model() {
+    let mut e = super::Encoder::with_self_check(Vec::new());
+    for value in 0..100 {
+        e.encode(value, &mut OverlappingModel).unwrap();
+    }
 }
@@ -0,0 +1,461 @@
+/*!
+
+Huffman encoder/decoder built from a byte histogram. Requires `entropy` feature, enabled by default
+Trades some ratio against the arithmetic coder for speed and simplicity.
+
+The stream starts with a header of code lengths, 4 bits per symbol,
+followed by the canonical codes of the data and the end-of-block code.
+Bits are packed LSB first, like in DEFLATE.
+
+# Links
+
+http://en.wikipedia.org/wiki/Huffman_coding
+http://en.wikipedia.org/wiki/Canonical_Huffman_code
+
+# Example
+
+```rust
+use std::io::{Read, Write};
+use compress::entropy::huffman;
+
+let text = b"some text";
+let mut counts = [0u32; 256];
+for &b in text.iter() {
+    counts[b as usize] += 1;
+}
+let mut e = huffman::HuffmanEncoder::new(Vec::new(), &counts);
+e.write_all(text).unwrap();
+let (encoded, _) = e.finish();
+
+let mut d = huffman::HuffmanDecoder::new(&encoded[..]);
+let mut decoded = Vec::new();
+d.read_to_end(&mut decoded).unwrap();
+assert_eq!(&decoded[..], &text[..]);
+```
+
+# Credit
+
+This is an original implementation.
+
+*/
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read, Write};
+
+/// Maximum length of a code in bits
+pub const MAX_BITS: usize = 15;
+/// Number of bits used to store a code length in the header
+const LENGTH_BITS: usize = 4;
+/// End of block symbol, which follows all the bytes
+const END_OF_BLOCK: usize = 256;
+/// Number of symbols, including the end of block
+const NUM_SYMBOLS: usize = 257;
+
+/// Compute the code lengths for the symbol counts, limited to `MAX_BITS`.
+/// Every symbol with a positive count gets a non-zero length.
+pub fn code_lengths(counts: &[u32]) -> Vec<u8> {
+    let mut counts: Vec<u64> = counts.iter().map(|&c| c as u64).collect();
+    loop {
+        let lengths = build_lengths(&counts);
+        if lengths.iter().all(|&len| (len as usize) <= MAX_BITS) {
+            return lengths
+        }
+        // flatten the distribution until the tree is shallow enough
+        for c in counts.iter_mut() {
+            if *c != 0 {
+                *c = (*c + 1) >> 1;
+            }
+        }
+    }
+}
+
+/// Build the unlimited Huffman tree and return the leaf depths
+fn build_lengths(counts: &[u64]) -> Vec<u8> {
+    let mut lengths = vec![0u8; counts.len()];
+    // node -> parent, leaves go first
+    let mut parents: Vec<usize> = Vec::with_capacity(2*counts.len());
+    let mut heap = BinaryHeap::new();
+    for (i, &c) in counts.iter().enumerate() {
+        parents.push(0);
+        if c != 0 {
+            heap.push(Reverse((c, i)));
+        }
+    }
+    if heap.len() == 1 {
+        // a single symbol still needs a bit
+        let Reverse((_, i)) = heap.pop().unwrap();
+        lengths[i] = 1;
+        return lengths
+    }
+    while heap.len() > 1 {
+        let Reverse((c0, n0)) = heap.pop().unwrap();
+        let Reverse((c1, n1)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(0);
+        parents[n0] = node;
+        parents[n1] = node;
+        heap.push(Reverse((c0+c1, node)));
+    }
+    let root = parents.len() - 1;
+    for (i, len) in lengths.iter_mut().enumerate() {
+        if counts[i] != 0 {
+            let mut node = i;
+            while node != root {
+                node = parents[node];
+                *len += 1;
+            }
+        }
+    }
+    lengths
+}
+
+/// Assign canonical codes to the given lengths:
+/// shorter codes go first, equal lengths are ordered by the symbol
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut count = [0u16; MAX_BITS+1];
+    for &len in lengths.iter() {
+        count[len as usize] += 1;
+    }
+    count[0] = 0;
+    let mut next = [0u16; MAX_BITS+1];
+    let mut code = 0;
+    for bits in 1..MAX_BITS+1 {
+        code = (code + count[bits-1]) << 1;
+        next[bits] = code;
+    }
+    lengths.iter().map(|&len| {
+        if len == 0 {
+            0
+        }else {
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            code
+        }
+    }).collect()
+}
+
+fn error<T>(msg: &'static str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+
+/// A Huffman encoder of bytes.
+/// Implements `Write`, so it can be plugged into any writing pipeline.
+pub struct HuffmanEncoder<W> {
+    w: W,
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+    wrote_header: bool,
+    bitbuf: u32,
+    bitcnt: usize,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> HuffmanEncoder<W> {
+    /// Create a new encoder on top of a given Writer, with the codes
+    /// built from the byte counts. Bytes with zero count can't be written.
+    pub fn new(w: W, counts: &[u32; 256]) -> HuffmanEncoder<W> {
+        let mut all_counts = counts.to_vec();
+        all_counts.push(1); // end of block
+        let lengths = code_lengths(&all_counts);
+        let codes = canonical_codes(&lengths);
+        HuffmanEncoder {
+            w: w,
+            lengths: lengths,
+            codes: codes,
+            wrote_header: false,
+            bitbuf: 0,
+            bitcnt: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Put the lower 'cnt' bits of 'value' into the buffer, LSB first
+    fn put_bits(&mut self, value: u32, cnt: usize) {
+        self.bitbuf |= value << self.bitcnt;
+        self.bitcnt += cnt;
+        while self.bitcnt >= 8 {
+            self.buf.push(self.bitbuf as u8);
+            self.bitbuf >>= 8;
+            self.bitcnt -= 8;
+        }
+    }
+
+    /// Put the code of a symbol into the buffer, MSB first
+    fn put_symbol(&mut self, symbol: usize) -> io::Result<()> {
+        let len = self.lengths[symbol] as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "byte is missing from the histogram"))
+        }
+        let code = self.codes[symbol] as u32;
+        for i in (0..len).rev() {
+            self.put_bits((code >> i) & 1, 1);
+        }
+        Ok(())
+    }
+
+    fn put_header(&mut self) {
+        if !self.wrote_header {
+            self.wrote_header = true;
+            for i in 0..NUM_SYMBOLS {
+                let len = self.lengths[i] as u32;
+                self.put_bits(len, LENGTH_BITS);
+            }
+        }
+    }
+
+    /// Write out the buffered whole bytes
+    fn write_buffer(&mut self) -> io::Result<()> {
+        try!(self.w.write_all(&self.buf[..]));
+        self.buf.truncate(0);
+        Ok(())
+    }
+
+    /// Finish encoding: write the end of block code and pad the last byte
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        self.put_header();
+        let mut result = self.put_symbol(END_OF_BLOCK);
+        if self.bitcnt != 0 {
+            let pad = 8 - self.bitcnt;
+            self.put_bits(0, pad);
+        }
+        result = result.and_then(|_| self.write_buffer()).and_then(|_| self.w.flush());
+        (self.w, result)
+    }
+}
+
+impl<W: Write> Write for HuffmanEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.put_header();
+        for &byte in buf.iter() {
+            try!(self.put_symbol(byte as usize));
+        }
+        try!(self.write_buffer());
+        Ok(buf.len())
+    }
+
+    /// Flush the underlying stream, the partial byte is kept
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+
+/// A Huffman decoder of bytes.
+/// Implements `Read`, reporting the end of file after the end of block code.
+pub struct HuffmanDecoder<R> {
+    r: R,
+    /// number of codes of each length
+    count: [u16; MAX_BITS+1],
+    /// symbols in the canonical order
+    symbol: Vec<u16>,
+    read_header: bool,
+    is_eof: bool,
+    bitbuf: u32,
+    bitcnt: usize,
+}
+
+impl<R: Read> HuffmanDecoder<R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R) -> HuffmanDecoder<R> {
+        HuffmanDecoder {
+            r: r,
+            count: [0; MAX_BITS+1],
+            symbol: Vec::with_capacity(NUM_SYMBOLS),
+            read_header: false,
+            is_eof: false,
+            bitbuf: 0,
+            bitcnt: 0,
+        }
+    }
+
+    /// Return the underlying Reader
+    pub fn finish(self) -> (R, io::Result<()>) {
+        (self.r, Ok(()))
+    }
+
+    fn bits(&mut self, cnt: usize) -> io::Result<u32> {
+        while self.bitcnt < cnt {
+            let mut byte = [0u8];
+            try!(self.r.read_exact(&mut byte));
+            self.bitbuf |= (byte[0] as u32) << self.bitcnt;
+            self.bitcnt += 8;
+        }
+        let ret = self.bitbuf & ((1 << cnt) - 1);
+        self.bitbuf >>= cnt;
+        self.bitcnt -= cnt;
+        Ok(ret)
+    }
+
+    fn get_header(&mut self) -> io::Result<()> {
+        let mut lengths = [0u8; NUM_SYMBOLS];
+        for len in lengths.iter_mut() {
+            *len = try!(self.bits(LENGTH_BITS)) as u8;
+            self.count[*len as usize] += 1;
+        }
+        self.count[0] = 0;
+        // check that the lengths don't oversubscribe the code space
+        let mut left = 1isize;
+        for bits in 1..MAX_BITS+1 {
+            left = 2*left - self.count[bits] as isize;
+            if left < 0 {
+                return error("invalid huffman code lengths")
+            }
+        }
+        for bits in 1..MAX_BITS+1 {
+            for (sym, &len) in lengths.iter().enumerate() {
+                if len as usize == bits {
+                    self.symbol.push(sym as u16);
+                }
+            }
+        }
+        if lengths[END_OF_BLOCK] == 0 {
+            return error("missing huffman end of block code")
+        }
+        self.read_header = true;
+        Ok(())
+    }
+
+    /// Decode a symbol, reading the code bits one by one
+    fn get_symbol(&mut self) -> io::Result<usize> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for bits in 1..MAX_BITS+1 {
+            code |= try!(self.bits(1));
+            let count = self.count[bits] as u32;
+            if code < first + count {
+                return Ok(self.symbol[(index + code - first) as usize] as usize)
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        error("invalid huffman code")
+    }
+}
+
+impl<R: Read> Read for HuffmanDecoder<R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        if !self.read_header {
+            try!(self.get_header());
+        }
+        if self.is_eof {
+            return Ok(0)
+        }
+        let mut amount = 0;
+        for out_byte in dst.iter_mut() {
+            let symbol = try!(self.get_symbol());
+            if symbol == END_OF_BLOCK {
+                self.is_eof = true;
+                break
+            }
+            *out_byte = symbol as u8;
+            amount += 1;
+        }
+        Ok(amount)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, BufWriter, Read, Write};
+    use super::{code_lengths, HuffmanDecoder, HuffmanEncoder, MAX_BITS};
+    #[cfg(feature="unstable")]
+    use test::Bencher;
+
+    static TEXT_INPUT: &'static [u8] = include_bytes!("../data/test.txt");
+    static LARGE_INPUT: &'static [u8] = include_bytes!("../data/test.large");
+
+    fn histogram(bytes: &[u8]) -> [u32; 256] {
+        let mut counts = [0u32; 256];
+        for &b in bytes.iter() {
+            counts[b as usize] += 1;
+        }
+        counts
+    }
+
+    fn roundtrip(bytes: &[u8]) -> usize {
+        let mut e = HuffmanEncoder::new(BufWriter::new(Vec::new()), &histogram(bytes));
+        e.write_all(bytes).unwrap();
+        let (e, r) = e.finish();
+        r.unwrap();
+        let encoded = e.into_inner().unwrap();
+        let mut d = HuffmanDecoder::new(BufReader::new(&encoded[..]));
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], bytes);
+        encoded.len()
+    }
+
+    #[test]
+    fn some_roundtrips() {
+        roundtrip(b"");
+        roundtrip(b"a");
+        roundtrip(b"aaaaaaaa");
+        roundtrip(b"abracadabra");
+        roundtrip(TEXT_INPUT);
+        roundtrip(&LARGE_INPUT[..1<<16]);
+    }
+
+    #[test]
+    fn limited_lengths() {
+        // Fibonacci counts produce the deepest possible tree
+        let mut counts = vec![1u32, 1];
+        while counts.len() < 30 {
+            let n = counts.len();
+            let next = counts[n-1] + counts[n-2];
+            counts.push(next);
+        }
+        let lengths = code_lengths(&counts);
+        assert!(lengths.iter().all(|&len| len > 0 && len as usize <= MAX_BITS));
+        let kraft = lengths.iter().fold(0.0, |sum, &len| sum + (0.5f64).powi(len as i32));
+        assert!(kraft <= 1.0);
+    }
+
+    #[test]
+    fn missing_byte() {
+        let mut e = HuffmanEncoder::new(Vec::new(), &histogram(b"abc"));
+        assert!(e.write_all(b"abcd").is_err());
+    }
+
+    #[test]
+    #[cfg(feature="io")]
+    fn compare_ari() {
+        let huffman_size = roundtrip(TEXT_INPUT);
+        let mut e = super::super::ari::ByteEncoder::new(Vec::new());
+        e.write_all(TEXT_INPUT).unwrap();
+        let (ari_encoded, r) = e.finish();
+        r.unwrap();
+        info!("Huffman size {} against the arithmetic {}", huffman_size, ari_encoded.len());
+        assert!(huffman_size < TEXT_INPUT.len());
+    }
+
+    #[cfg(feature="unstable")]
+    #[bench]
+    fn compress_speed(bh: &mut Bencher) {
+        let counts = histogram(TEXT_INPUT);
+        let mut storage = Vec::with_capacity(TEXT_INPUT.len());
+        bh.iter(|| {
+            storage.truncate(0);
+            let mut e = HuffmanEncoder::new(&mut storage, &counts);
+            e.write_all(TEXT_INPUT).unwrap();
+        });
+        bh.bytes = TEXT_INPUT.len() as u64;
+    }
+
+    #[cfg(all(feature="unstable", feature="io"))]
+    #[bench]
+    fn compress_ari_speed(bh: &mut Bencher) {
+        let mut storage = Vec::with_capacity(TEXT_INPUT.len());
+        bh.iter(|| {
+            storage.truncate(0);
+            let mut e = super::super::ari::ByteEncoder::new(&mut storage);
+            e.write_all(TEXT_INPUT).unwrap();
+        });
+        bh.bytes = TEXT_INPUT.len() as u64;
+    }
+}
@@ -0,0 +1,280 @@
+/*!
+
+Universal codes of integers with unknown magnitude, written bit by bit.
+Useful to code run lengths and distances in front of the arithmetic coder.
+Both codes take positive numbers, so the values are offset by one,
+covering the whole `u32` range including zero. Requires `io` feature.
+
+Elias gamma code of `n` is `floor(log2(n))` zero bits followed by `n` itself.
+Elias delta code of `n` is the gamma code of `floor(log2(n))+1`
+followed by `n` without its leading bit.
+The gamma code can also go bit by bit to a custom sink, like the adaptive
+binary models of the arithmetic coder (see `write_gamma_with`).
+
+# Links
+
+http://en.wikipedia.org/wiki/Elias_gamma_coding
+http://en.wikipedia.org/wiki/Elias_delta_coding
+
+# Example
+
+```rust
+use compress::entropy::integer;
+use compress::io::bits::{BitReader, BitWriter};
+
+let mut w = BitWriter::new(Vec::new());
+integer::write_gamma(&mut w, 5).unwrap();
+integer::write_delta(&mut w, 1000).unwrap();
+let (encoded, _) = w.finish();
+
+let mut r = BitReader::new(&encoded[..]);
+assert_eq!(integer::read_gamma(&mut r).unwrap(), 5);
+assert_eq!(integer::read_delta(&mut r).unwrap(), 1000);
+```
+
+# Credit
+
+This is an original implementation.
+
+*/
+
+use std::io::{self, Read, Write};
+use io::bits::{BitReader, BitWriter, MAX_BITS};
+
+/// Return the position of the leading bit of a positive number
+fn log2(n: u64) -> usize {
+    63 - n.leading_zeros() as usize
+}
+
+/// Write the lowest 'count' bits of a number, possibly longer than `MAX_BITS`
+fn write_long<W: Write>(w: &mut BitWriter<W>, n: u64, count: usize) -> io::Result<()> {
+    if count > MAX_BITS {
+        try!(w.write_bits((n >> MAX_BITS) as u32, count - MAX_BITS));
+        w.write_bits(n as u32, MAX_BITS)
+    }else {
+        w.write_bits(n as u32, count)
+    }
+}
+
+/// Read a number of 'count' bits, possibly longer than `MAX_BITS`
+fn read_long<R: Read>(r: &mut BitReader<R>, count: usize) -> io::Result<u64> {
+    if count > MAX_BITS {
+        let high = try!(r.read_bits(count - MAX_BITS)) as u64;
+        let low = try!(r.read_bits(MAX_BITS)) as u64;
+        Ok(high << MAX_BITS | low)
+    }else {
+        r.read_bits(count).map(|n| n as u64)
+    }
+}
+
+/// Convert a decoded positive number back into the value
+fn to_value(n: u64) -> io::Result<u32> {
+    if n == 0 || n - 1 > u32::max_value() as u64 {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "integer code is out of range"))
+    }else {
+        Ok((n - 1) as u32)
+    }
+}
+
+/// Position of a gamma code bit, letting the bit sinks
+/// pick a separate model for every bit, like the arithmetic coder does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GammaBit {
+    /// The bit of the unary prefix at the given position: zero bits,
+    /// followed by the leading one of the number
+    Prefix(usize),
+    /// The bit of the number below its leading one, at the given position
+    Suffix(usize),
+}
+
+fn put_gamma_raw<F>(n: u64, mut put: F) -> io::Result<()>
+where F: FnMut(GammaBit, bool) -> io::Result<()> {
+    let num_zeros = log2(n);
+    for i in 0..num_zeros {
+        try!(put(GammaBit::Prefix(i), false));
+    }
+    try!(put(GammaBit::Prefix(num_zeros), true));
+    for i in (0..num_zeros).rev() {
+        try!(put(GammaBit::Suffix(i), (n >> i) & 1 != 0));
+    }
+    Ok(())
+}
+
+fn get_gamma_raw<F>(mut get: F) -> io::Result<u64>
+where F: FnMut(GammaBit) -> io::Result<bool> {
+    let mut num_zeros = 0;
+    while !try!(get(GammaBit::Prefix(num_zeros))) {
+        num_zeros += 1;
+        if num_zeros > MAX_BITS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "integer code is too long"))
+        }
+    }
+    let mut n = 1u64;
+    for i in (0..num_zeros).rev() {
+        n = n << 1 | try!(get(GammaBit::Suffix(i))) as u64;
+    }
+    Ok(n)
+}
+
+fn write_gamma_raw<W: Write>(w: &mut BitWriter<W>, n: u64) -> io::Result<()> {
+    put_gamma_raw(n, |_, bit| w.write_bits(bit as u32, 1))
+}
+
+fn read_gamma_raw<R: Read>(r: &mut BitReader<R>) -> io::Result<u64> {
+    get_gamma_raw(|_| r.read_bits(1).map(|bit| bit != 0))
+}
+
+/// Return the length of the gamma code of the value in bits
+pub fn gamma_bits(value: u32) -> usize {
+    2 * log2(value as u64 + 1) + 1
+}
+
+/// Return the length of the delta code of the value in bits
+pub fn delta_bits(value: u32) -> usize {
+    let num_bits = log2(value as u64 + 1);
+    2 * log2(num_bits as u64 + 1) + 1 + num_bits
+}
+
+/// Write the value with the Elias gamma code
+pub fn write_gamma<W: Write>(w: &mut BitWriter<W>, value: u32) -> io::Result<()> {
+    write_gamma_raw(w, value as u64 + 1)
+}
+
+/// Read a value coded with the Elias gamma code
+pub fn read_gamma<R: Read>(r: &mut BitReader<R>) -> io::Result<u32> {
+    read_gamma_raw(r).and_then(to_value)
+}
+
+/// Pass the bits of the Elias gamma code of the value to a bit sink,
+/// along with their positions
+pub fn write_gamma_with<F>(value: u32, put: F) -> io::Result<()>
+where F: FnMut(GammaBit, bool) -> io::Result<()> {
+    put_gamma_raw(value as u64 + 1, put)
+}
+
+/// Read a value coded with the Elias gamma code from a bit source,
+/// asked for the bits by their positions
+pub fn read_gamma_with<F>(get: F) -> io::Result<u32>
+where F: FnMut(GammaBit) -> io::Result<bool> {
+    get_gamma_raw(get).and_then(to_value)
+}
+
+/// Write the value with the Elias delta code
+pub fn write_delta<W: Write>(w: &mut BitWriter<W>, value: u32) -> io::Result<()> {
+    let n = value as u64 + 1;
+    let num_bits = log2(n);
+    try!(write_gamma_raw(w, num_bits as u64 + 1));
+    write_long(w, n, num_bits)
+}
+
+/// Read a value coded with the Elias delta code
+pub fn read_delta<R: Read>(r: &mut BitReader<R>) -> io::Result<u32> {
+    let num_bits = try!(read_gamma_raw(r)) as usize - 1;
+    if num_bits > MAX_BITS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "integer code is too long"))
+    }
+    let tail = try!(read_long(r, num_bits));
+    to_value(1 << num_bits | tail)
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use io::bits::{BitReader, BitWriter};
+    use super::{delta_bits, gamma_bits, read_delta, read_gamma, write_delta, write_gamma};
+
+    static VALUES: [u32; 9] = [0, 1, 2, 3, 7, 1000, 1<<16, u32::max_value() - 1, u32::max_value()];
+
+    type WriteFn = fn(&mut BitWriter<Vec<u8>>, u32) -> io::Result<()>;
+    type ReadFn = fn(&mut BitReader<io::Cursor<Vec<u8>>>) -> io::Result<u32>;
+
+    fn roundtrip(write: WriteFn, read: ReadFn) {
+        let mut w = BitWriter::new(Vec::new());
+        for &value in VALUES.iter() {
+            write(&mut w, value).unwrap();
+        }
+        let (encoded, result) = w.finish();
+        result.unwrap();
+        let mut r = BitReader::new(io::Cursor::new(encoded));
+        for &value in VALUES.iter() {
+            assert_eq!(read(&mut r).unwrap(), value);
+        }
+    }
+
+    /// Return the code length in bits, by writing it 8 times to fill whole bytes
+    fn code_bits(write: WriteFn, value: u32) -> usize {
+        let mut w = BitWriter::new(Vec::new());
+        for _ in 0..8 {
+            write(&mut w, value).unwrap();
+        }
+        w.bytes_written() as usize
+    }
+
+    #[test]
+    fn roundtrips() {
+        roundtrip(write_gamma, read_gamma);
+        roundtrip(write_delta, read_delta);
+    }
+
+    #[test]
+    fn code_lengths() {
+        for &value in VALUES.iter() {
+            let n = value as u64 + 1;
+            let log = 63 - n.leading_zeros() as usize;
+            assert_eq!(gamma_bits(value), 2*log + 1);
+            assert_eq!(code_bits(write_gamma, value), gamma_bits(value));
+            assert_eq!(code_bits(write_delta, value), delta_bits(value));
+        }
+        assert_eq!(gamma_bits(0), 1);
+        assert_eq!(delta_bits(0), 1);
+    }
+
+    #[test]
+    fn bit_callbacks() {
+        use super::{read_gamma_with, write_gamma_with, GammaBit};
+        let mut w = BitWriter::new(Vec::new());
+        let mut bits = Vec::new();
+        for &value in VALUES.iter() {
+            write_gamma(&mut w, value).unwrap();
+            write_gamma_with(value, |position, bit| {
+                bits.push((position, bit));
+                Ok(())
+            }).unwrap();
+        }
+        let (encoded, _) = w.finish();
+        let mut r = BitReader::new(&encoded[..]);
+        for &(_, bit) in bits.iter() {
+            assert_eq!(r.read_bits(1).unwrap() != 0, bit);
+        }
+        // 5 + 1 = 0b110
+        let mut positions = Vec::new();
+        write_gamma_with(5, |position, _| {
+            positions.push(position);
+            Ok(())
+        }).unwrap();
+        assert_eq!(positions, [GammaBit::Prefix(0), GammaBit::Prefix(1), GammaBit::Prefix(2),
+            GammaBit::Suffix(1), GammaBit::Suffix(0)]);
+        let mut iter = bits.into_iter();
+        for &value in VALUES.iter() {
+            assert_eq!(read_gamma_with(|position| {
+                let (expected, bit) = iter.next().unwrap();
+                assert_eq!(position, expected);
+                Ok(bit)
+            }).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn bad_input() {
+        // too many leading zeros
+        let mut r = BitReader::new(&[0u8; 8][..]);
+        assert_eq!(read_gamma(&mut r).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // the code of u32::MAX+2 fits the length limit, but not the type
+        let mut w = BitWriter::new(Vec::new());
+        super::write_gamma_raw(&mut w, (1<<32) + 1).unwrap();
+        let (encoded, _) = w.finish();
+        let mut r = BitReader::new(&encoded[..]);
+        assert_eq!(read_gamma(&mut r).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,355 @@
+/*!
+
+Static rANS encoder/decoder of bytes. Requires `entropy` feature, enabled by default
+Decodes faster than the range coder by looking the symbols up in a table,
+at the cost of encoding the whole block in reverse.
+
+The frequencies come from `ari::table::StaticModel`, which has to sum up to
+a power of two (see `StaticModel::from_histogram_exact`). Two interleaved
+states alternate between the symbols to break the decoding dependency chain.
+The stream is the number of symbols (8 bytes), the initial decoder states
+(4 bytes each) and the renormalization bytes, all little endian.
+
+# Links
+
+http://arxiv.org/abs/1311.2540
+https://github.com/rygorous/ryg_rans
+
+# Example
+
+```rust
+use std::io::{Read, Write};
+use compress::entropy::ari::table::StaticModel;
+use compress::entropy::rans;
+
+let text = b"some text";
+let mut counts = [0u32; 256];
+for &b in text.iter() {
+    counts[b as usize] += 1;
+}
+let model = StaticModel::from_histogram_exact(&counts, 1<<12);
+let mut e = rans::RansEncoder::new(Vec::new(), &model);
+e.write_all(text).unwrap();
+let (encoded, _) = e.finish();
+
+let mut d = rans::RansDecoder::new(&encoded[..], &model);
+let mut decoded = Vec::new();
+d.read_to_end(&mut decoded).unwrap();
+assert_eq!(&decoded[..], &text[..]);
+```
+
+# Credit
+
+Based on the byte-wise rANS by Fabian Giesen.
+
+*/
+
+use std::io::{self, Read, Write};
+use super::ari::Model;
+use super::ari::table::StaticModel;
+
+/// Lower bound of the normalized state
+const RANS_LOW: u32 = 1<<23;
+/// Maximum number of bits in the total frequency
+pub const MAX_SCALE_BITS: usize = 16;
+/// Number of interleaved states
+const NUM_STATES: usize = 2;
+
+/// Per-symbol frequencies extracted from the model
+struct Table {
+    start: [u32; 256],
+    freq: [u32; 256],
+    scale_bits: usize,
+}
+
+impl Table {
+    fn new(model: &StaticModel) -> Table {
+        // every slot of the total has to map to a byte
+        assert!(model.get_num_values() == 256,
+            "The model has {} values instead of 256", model.get_num_values());
+        let total = model.get_denominator();
+        assert!(total.is_power_of_two() && total <= 1<<MAX_SCALE_BITS,
+            "The total {} is not a power of two up to 1<<{}", total, MAX_SCALE_BITS);
+        let mut table = Table {
+            start: [0; 256],
+            freq: [0; 256],
+            scale_bits: total.trailing_zeros() as usize,
+        };
+        for i in 0..256 {
+            let (lo, hi) = model.get_range(i);
+            table.start[i] = lo;
+            table.freq[i] = hi - lo;
+        }
+        table
+    }
+}
+
+
+/// A rANS encoder of bytes.
+/// Implements `Write`, buffering the input until `finish`.
+pub struct RansEncoder<W> {
+    w: W,
+    table: Table,
+    input: Vec<u8>,
+}
+
+impl<W: Write> RansEncoder<W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W, model: &StaticModel) -> RansEncoder<W> {
+        RansEncoder {
+            w: w,
+            table: Table::new(model),
+            input: Vec::new(),
+        }
+    }
+
+    /// Encode the buffered input in reverse and write it out
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let result = self.encode().and_then(|output| {
+            let n = self.input.len() as u64;
+            let header: Vec<u8> = (0..8).map(|i| (n >> (8*i)) as u8).collect();
+            try!(self.w.write_all(&header));
+            try!(self.w.write_all(&output));
+            self.w.flush()
+        });
+        (self.w, result)
+    }
+
+    /// Produce the code bytes, in the decoding order
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let table = &self.table;
+        // the bytes are gathered backwards and reversed at the end
+        let mut output = Vec::with_capacity(self.input.len());
+        let mut states = [RANS_LOW; NUM_STATES];
+        for (i, &byte) in self.input.iter().enumerate().rev() {
+            let (start, freq) = (table.start[byte as usize], table.freq[byte as usize]);
+            if freq == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    "byte is missing from the model"))
+            }
+            let x = &mut states[i % NUM_STATES];
+            let x_max = ((RANS_LOW >> table.scale_bits) << 8) * freq;
+            while *x >= x_max {
+                output.push(*x as u8);
+                *x >>= 8;
+            }
+            *x = ((*x / freq) << table.scale_bits) + (*x % freq) + start;
+        }
+        for x in states.iter().rev() {
+            for shift in [24, 16, 8, 0].iter() {
+                output.push((*x >> *shift) as u8);
+            }
+        }
+        output.reverse();
+        Ok(output)
+    }
+}
+
+impl<W: Write> Write for RansEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Nothing is written before `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// A rANS decoder of bytes.
+/// Implements `Read`, reporting the end of file after the encoded number of bytes.
+pub struct RansDecoder<R> {
+    r: R,
+    table: Table,
+    /// slot -> symbol lookup
+    lookup: Vec<u8>,
+    states: [u32; NUM_STATES],
+    /// index of the next symbol
+    position: u64,
+    /// number of symbols, known after the header is read
+    num_symbols: Option<u64>,
+}
+
+impl<R: Read> RansDecoder<R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R, model: &StaticModel) -> RansDecoder<R> {
+        let table = Table::new(model);
+        let mut lookup = Vec::with_capacity(1 << table.scale_bits);
+        for (symbol, &freq) in table.freq.iter().enumerate() {
+            lookup.extend((0..freq).map(|_| symbol as u8));
+        }
+        RansDecoder {
+            r: r,
+            table: table,
+            lookup: lookup,
+            states: [0; NUM_STATES],
+            position: 0,
+            num_symbols: None,
+        }
+    }
+
+    /// Return the underlying Reader
+    pub fn finish(self) -> (R, io::Result<()>) {
+        (self.r, Ok(()))
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8];
+        try!(self.r.read_exact(&mut byte));
+        Ok(byte[0])
+    }
+
+    fn read_header(&mut self) -> io::Result<u64> {
+        let mut n = 0u64;
+        for i in 0..8 {
+            n |= (try!(self.read_u8()) as u64) << (8*i);
+        }
+        for k in 0..NUM_STATES {
+            let mut x = 0u32;
+            for i in 0..4 {
+                x |= (try!(self.read_u8()) as u32) << (8*i);
+            }
+            self.states[k] = x;
+        }
+        self.num_symbols = Some(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> Read for RansDecoder<R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let num_symbols = match self.num_symbols {
+            Some(n) => n,
+            None => try!(self.read_header()),
+        };
+        let mask = (1 << self.table.scale_bits) - 1;
+        let mut amount = 0;
+        for out_byte in dst.iter_mut() {
+            if self.position == num_symbols {
+                break
+            }
+            let k = (self.position % NUM_STATES as u64) as usize;
+            let mut x = self.states[k];
+            let slot = x & mask;
+            let symbol = self.lookup[slot as usize];
+            let (start, freq) = (self.table.start[symbol as usize], self.table.freq[symbol as usize]);
+            x = freq * (x >> self.table.scale_bits) + slot - start;
+            while x < RANS_LOW {
+                x = (x << 8) | (try!(self.read_u8()) as u32);
+            }
+            self.states[k] = x;
+            *out_byte = symbol;
+            amount += 1;
+            self.position += 1;
+        }
+        Ok(amount)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, BufWriter, Read, Write};
+    use rand::{Rng, StdRng, SeedableRng};
+    use super::super::ari::table::StaticModel;
+    use super::{RansDecoder, RansEncoder};
+    #[cfg(feature="unstable")]
+    use test::Bencher;
+
+    static TEXT_INPUT: &'static [u8] = include_bytes!("../data/test.txt");
+    static LARGE_INPUT: &'static [u8] = include_bytes!("../data/test.large");
+
+    fn new_model(bytes: &[u8]) -> StaticModel {
+        let mut counts = [0u32; 256];
+        for &b in bytes.iter() {
+            counts[b as usize] += 1;
+        }
+        if bytes.is_empty() {
+            counts[0] = 1;
+        }
+        StaticModel::from_histogram_exact(&counts, 1<<14)
+    }
+
+    fn encode(bytes: &[u8], model: &StaticModel) -> Vec<u8> {
+        let mut e = RansEncoder::new(BufWriter::new(Vec::new()), model);
+        e.write_all(bytes).unwrap();
+        let (e, r) = e.finish();
+        r.unwrap();
+        e.into_inner().unwrap()
+    }
+
+    fn roundtrip(bytes: &[u8]) -> usize {
+        let model = new_model(bytes);
+        let encoded = encode(bytes, &model);
+        let mut d = RansDecoder::new(BufReader::new(&encoded[..]), &model);
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], bytes);
+        encoded.len()
+    }
+
+    #[test]
+    fn some_roundtrips() {
+        roundtrip(b"");
+        roundtrip(b"a");
+        roundtrip(b"abracadabra");
+        roundtrip(TEXT_INPUT);
+        roundtrip(&LARGE_INPUT[..1<<16]);
+    }
+
+    #[test]
+    fn random_roundtrips() {
+        let mut rng = StdRng::from_seed(&[1, 2, 3, 4][..]);
+        let input: Vec<u8> = (0..1<<16).map(|_| rng.gen()).collect();
+        let size = roundtrip(&input);
+        assert!(size < input.len() + 100);
+        // skewed: mostly small values
+        let input: Vec<u8> = (0..1<<16).map(|_| {
+            let x: u32 = rng.gen();
+            (x.trailing_zeros() * 10) as u8
+        }).collect();
+        let size = roundtrip(&input);
+        assert!(size < input.len() / 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "values instead of 256")]
+    fn extra_values() {
+        let mut counts = [1u32; 257];
+        counts[256] = 1<<8;
+        let model = StaticModel::from_histogram_exact(&counts, 1<<10);
+        RansDecoder::new(&[][..], &model);
+    }
+
+    #[cfg(feature="unstable")]
+    #[bench]
+    fn decompress_speed(bh: &mut Bencher) {
+        let input = &LARGE_INPUT[..1<<16];
+        let model = new_model(input);
+        let encoded = encode(input, &model);
+        let mut output = vec![0u8; input.len()];
+        bh.iter(|| {
+            let mut d = RansDecoder::new(&encoded[..], &model);
+            d.read_exact(&mut output).unwrap();
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[cfg(all(feature="unstable", feature="io"))]
+    #[bench]
+    fn decompress_ari_speed(bh: &mut Bencher) {
+        use super::super::ari;
+        let input = &LARGE_INPUT[..1<<16];
+        let mut e = ari::ByteEncoder::new(Vec::new());
+        e.write_all(input).unwrap();
+        let (encoded, _) = e.finish();
+        let mut output = vec![0u8; input.len()];
+        bh.iter(|| {
+            let mut d = ari::ByteDecoder::new(&encoded[..]);
+            d.read_exact(&mut output).unwrap();
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
@@ -0,0 +1,278 @@
+/*!
+
+Run-length front-end of the arithmetic coder, for the data dominated
+by long repeats of the same byte. Requires `io` feature.
+
+Every run of a byte becomes a pair: the byte, coded by an adaptive
+frequency model, and the run length, coded by the Elias gamma code
+(see `integer::write_gamma_with`) with every bit going through
+an adaptive binary model, picked by the bit position.
+A long run costs a few dozens of bits instead of a fraction of a bit
+per byte. The stream ends with a special terminator in place of the byte.
+
+Unlike the crate-level `rle` module (the `rle` feature), which writes
+the runs as plain bytes for any later stage, this one is entropy coded
+and produces the final output.
+
+# Example
+
+```rust
+use std::io::{Read, Write};
+use compress::entropy::rle;
+
+let input = [0u8; 1000];
+let mut e = rle::RleEncoder::new(Vec::new());
+e.write_all(&input).unwrap();
+let (encoded, _) = e.finish();
+assert!(encoded.len() < 10);
+
+let mut d = rle::RleDecoder::new(&encoded[..]);
+let mut decoded = Vec::new();
+d.read_to_end(&mut decoded).unwrap();
+assert_eq!(&decoded[..], &input[..]);
+```
+
+# Credit
+
+This is an original implementation.
+
+*/
+
+use std::io::{self, Read, Write};
+use io::bits::MAX_BITS;
+use super::ari::{self, bin, table};
+use super::integer::{self, GammaBit};
+
+/// Number of the gamma code prefix bits of the largest run length
+const PREFIX_BITS: usize = MAX_BITS + 1;
+/// Adaptation shift of the binary models of the run length
+const LENGTH_RATE: usize = 4;
+/// The byte value reserved for the end of the stream
+const TERMINATOR: usize = 256;
+
+/// Adaptive models of the gamma code bits of the run length
+struct LengthModel {
+    /// the unary prefix, by the bit position
+    prefix: Vec<bin::Model>,
+    /// the bits following the leading one, by the bit position
+    suffix: Vec<bin::Model>,
+}
+
+impl LengthModel {
+    fn new() -> LengthModel {
+        LengthModel {
+            prefix: (0..PREFIX_BITS).map(|_| bin::Model::with_rate(LENGTH_RATE)).collect(),
+            suffix: (0..MAX_BITS).map(|_| bin::Model::with_rate(LENGTH_RATE)).collect(),
+        }
+    }
+
+    fn get_model(&mut self, position: GammaBit) -> &mut bin::Model {
+        match position {
+            GammaBit::Prefix(i) => &mut self.prefix[i],
+            GammaBit::Suffix(i) => &mut self.suffix[i],
+        }
+    }
+
+    /// Encode a positive length
+    fn encode<W: Write>(&mut self, encoder: &mut ari::Encoder<W>, n: u32) -> io::Result<()> {
+        debug_assert!(n != 0);
+        integer::write_gamma_with(n - 1, |position, bit| {
+            encoder.encode_bit(bit, self.get_model(position))
+        })
+    }
+
+    /// Decode a length written by `encode`
+    fn decode<R: Read>(&mut self, decoder: &mut ari::Decoder<R>) -> io::Result<u32> {
+        let value = try!(integer::read_gamma_with(|position| {
+            decoder.decode_bit(self.get_model(position))
+        }));
+        if value == u32::max_value() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "run is too long"))
+        }
+        Ok(value + 1)
+    }
+}
+
+/// Create the adaptive model of the run bytes and the terminator
+fn new_byte_model() -> table::Model {
+    table::Model::new_flat(TERMINATOR + 1, table::DEFAULT_THRESHOLD)
+}
+
+
+/// A run-length encoder on top of the arithmetic coder.
+/// Implements `Write`, coding every run once it's over.
+pub struct RleEncoder<W> {
+    /// A lower level encoder
+    pub encoder: ari::Encoder<W>,
+    /// The model of the run bytes
+    freq: table::Model,
+    length: LengthModel,
+    /// The byte and the length of the current run
+    run: Option<(u8, u32)>,
+}
+
+impl<W: Write> RleEncoder<W> {
+    /// Create a new encoder on top of a given Writer
+    pub fn new(w: W) -> RleEncoder<W> {
+        RleEncoder {
+            encoder: ari::Encoder::new(w),
+            freq: new_byte_model(),
+            length: LengthModel::new(),
+            run: None,
+        }
+    }
+
+    fn encode_run(&mut self, byte: u8, n: u32) -> io::Result<()> {
+        let value = byte as usize;
+        try!(self.encoder.encode(value, &mut self.freq));
+        self.freq.update(value, 10, 1);
+        self.length.encode(&mut self.encoder, n)
+    }
+
+    /// Finish encoding the last run & write the terminator symbol
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let mut ret = match self.run.take() {
+            Some((byte, n)) => self.encode_run(byte, n),
+            None => Ok(()),
+        };
+        if ret.is_ok() {
+            ret = self.encoder.encode(TERMINATOR, &mut self.freq).map(|_| ());
+        }
+        let (w, r2) = self.encoder.finish();
+        (w, ret.and(r2))
+    }
+}
+
+impl<W: Write> Write for RleEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf.iter() {
+            match self.run {
+                Some((b, ref mut n)) if b == byte && *n != !0 => {
+                    *n += 1;
+                    continue
+                },
+                Some((b, n)) => try!(self.encode_run(b, n)),
+                None => (),
+            }
+            self.run = Some((byte, 1));
+        }
+        Ok(buf.len())
+    }
+
+    /// Flush the underlying stream, the current run is kept open
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+
+/// A run-length decoder on top of the arithmetic coder.
+/// Implements `Read`, reporting the end of file after the terminator.
+pub struct RleDecoder<R> {
+    /// A lower level decoder
+    pub decoder: ari::Decoder<R>,
+    freq: table::Model,
+    length: LengthModel,
+    /// The byte and the remaining length of the current run
+    run: (u8, u32),
+    /// Remember if we found the terminator code
+    is_eof: bool,
+}
+
+impl<R: Read> RleDecoder<R> {
+    /// Create a decoder on top of a given Reader
+    pub fn new(r: R) -> RleDecoder<R> {
+        RleDecoder {
+            decoder: ari::Decoder::new(r),
+            freq: new_byte_model(),
+            length: LengthModel::new(),
+            run: (0, 0),
+            is_eof: false,
+        }
+    }
+
+    /// Finish decoding
+    pub fn finish(self) -> (R, io::Result<()>) {
+        self.decoder.finish()
+    }
+}
+
+impl<R: Read> Read for RleDecoder<R> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let mut amount = 0;
+        while amount < dst.len() {
+            if self.run.1 == 0 {
+                if self.is_eof {
+                    break
+                }
+                let value = try!(self.decoder.decode(&mut self.freq));
+                if value == TERMINATOR {
+                    self.is_eof = true;
+                    break
+                }
+                self.freq.update(value, 10, 1);
+                let n = try!(self.length.decode(&mut self.decoder));
+                self.run = (value as u8, n);
+            }
+            let count = (dst.len() - amount).min(self.run.1 as usize);
+            for out_byte in dst[amount..amount + count].iter_mut() {
+                *out_byte = self.run.0;
+            }
+            amount += count;
+            self.run.1 -= count as u32;
+        }
+        Ok(amount)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, BufWriter, Read, Write};
+    use super::{RleDecoder, RleEncoder};
+
+    static TEXT_INPUT: &'static [u8] = include_bytes!("../data/test.txt");
+
+    fn roundtrip(bytes: &[u8]) -> usize {
+        let mut e = RleEncoder::new(BufWriter::new(Vec::new()));
+        e.write_all(bytes).unwrap();
+        let (e, r) = e.finish();
+        r.unwrap();
+        let encoded = e.into_inner().unwrap();
+        let mut d = RleDecoder::new(BufReader::new(&encoded[..]));
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], bytes);
+        encoded.len()
+    }
+
+    #[test]
+    fn some_roundtrips() {
+        roundtrip(b"");
+        roundtrip(b"a");
+        roundtrip(b"aaabbbbbbbbbbbbbbbbcdd");
+        roundtrip(TEXT_INPUT);
+        let binary: Vec<u8> = (0..1<<12).map(|i| (i * i >> 3) as u8).collect();
+        roundtrip(&binary);
+    }
+
+    #[test]
+    fn zero_runs() {
+        let mut input = Vec::new();
+        let mut seed = 1u32;
+        for _ in 0..100 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let n = 1000 + (seed >> 16) as usize % 10000;
+            input.extend((0..n).map(|_| 0u8));
+            input.push((seed >> 8) as u8 | 1);
+        }
+        let size = roundtrip(&input);
+        assert!(size * 200 < input.len(), "Encoded {} bytes into {}", input.len(), size);
+        // the plain byte coder spends a fraction of a bit on every zero
+        let mut e = super::super::ari::ByteEncoder::new(Vec::new());
+        e.write_all(&input).unwrap();
+        let (encoded, _) = e.finish();
+        assert!(size * 10 < encoded.len(), "Encoded into {} bytes, {} for the byte coder",
+            size, encoded.len());
+    }
+}
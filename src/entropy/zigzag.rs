@@ -0,0 +1,72 @@
+/*!
+
+Zigzag mapping of signed integers to unsigned ones, interleaving
+the negative values with the positive: 0, -1, 1, -2, 2... map to 0, 1, 2, 3, 4...
+Small magnitudes get small codes, so signed deltas can index
+a frequency model or a universal code. Requires `entropy` feature, enabled by default.
+
+# Links
+
+https://developers.google.com/protocol-buffers/docs/encoding#signed-integers
+
+# Example
+
+```rust
+use compress::entropy::zigzag;
+
+assert_eq!(zigzag::encode_i32(-2), 3);
+assert_eq!(zigzag::decode_i32(3), -2);
+```
+
+*/
+
+/// Map a signed value to the unsigned code
+#[inline]
+pub fn encode_i32(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Map the unsigned code back to the signed value
+#[inline]
+pub fn decode_i32(code: u32) -> i32 {
+    ((code >> 1) as i32) ^ -((code & 1) as i32)
+}
+
+/// Map a signed value to the unsigned code
+#[inline]
+pub fn encode_i64(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Map the unsigned code back to the signed value
+#[inline]
+pub fn decode_i64(code: u64) -> i64 {
+    ((code >> 1) as i64) ^ -((code & 1) as i64)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::{decode_i32, decode_i64, encode_i32, encode_i64};
+
+    #[test]
+    fn roundtrips() {
+        for &value in [-1, 0, 1, -1000, 1000, i32::min_value(), i32::max_value()].iter() {
+            assert_eq!(decode_i32(encode_i32(value)), value);
+            assert_eq!(decode_i64(encode_i64(value as i64)), value as i64);
+        }
+        for &value in [i64::min_value(), i64::max_value()].iter() {
+            assert_eq!(decode_i64(encode_i64(value)), value);
+        }
+    }
+
+    #[test]
+    fn small_codes() {
+        let codes: Vec<u32> = [0, -1, 1, -2, 2, -3].iter().map(|&v| encode_i32(v)).collect();
+        assert_eq!(codes, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(encode_i32(i32::max_value()), u32::max_value() - 1);
+        assert_eq!(encode_i32(i32::min_value()), u32::max_value());
+        assert_eq!(encode_i64(-3), 5);
+        assert_eq!(encode_i64(i64::min_value()), u64::max_value());
+    }
+}
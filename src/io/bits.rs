@@ -0,0 +1,192 @@
+/*!
+
+Bit-level IO on top of byte streams.
+Bits are packed MSB first: the first bit written becomes the highest bit
+of the first byte. The last byte is padded with zero bits on flush.
+
+# Example
+
+```rust
+use compress::io::bits::{BitReader, BitWriter};
+
+let mut w = BitWriter::new(Vec::new());
+w.write_bits(0b101, 3).unwrap();
+w.write_bits(0x1234, 16).unwrap();
+let (encoded, result) = w.finish();
+result.unwrap();
+assert_eq!(encoded.len(), 3);
+
+let mut r = BitReader::new(&encoded[..]);
+assert_eq!(r.read_bits(3).unwrap(), 0b101);
+assert_eq!(r.read_bits(16).unwrap(), 0x1234);
+```
+
+*/
+
+use std::io::{self, Read, Write};
+
+/// Maximum number of bits in a single call
+pub const MAX_BITS: usize = 32;
+
+/// A writer of bits, MSB first
+pub struct BitWriter<W> {
+    w: W,
+    /// pending bits, in the lowest positions
+    buffer: u64,
+    /// number of pending bits, always below 8 between the calls
+    num_bits: usize,
+    /// number of bytes written so far
+    bytes_written: u64,
+}
+
+impl<W: Write> BitWriter<W> {
+    /// Create a new bit writer on top of a given Writer
+    pub fn new(w: W) -> BitWriter<W> {
+        BitWriter {
+            w: w,
+            buffer: 0,
+            num_bits: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Write the lowest 'count' bits of the value, starting with the highest of them
+    pub fn write_bits(&mut self, value: u32, count: usize) -> io::Result<()> {
+        assert!(count <= MAX_BITS, "Unable to write {} bits at once", count);
+        let mask = (1u64 << count) - 1;
+        self.buffer = (self.buffer << count) | (value as u64 & mask);
+        self.num_bits += count;
+        let mut bytes = [0u8; 5];
+        let mut num_bytes = 0;
+        while self.num_bits >= 8 {
+            self.num_bits -= 8;
+            bytes[num_bytes] = (self.buffer >> self.num_bits) as u8;
+            num_bytes += 1;
+        }
+        self.buffer &= (1 << self.num_bits) - 1;
+        self.bytes_written += num_bytes as u64;
+        self.w.write_all(&bytes[..num_bytes])
+    }
+
+    /// Write out the pending bits, padding the last byte with zeros,
+    /// and flush the underlying Writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.num_bits != 0 {
+            let pad = 8 - self.num_bits;
+            try!(self.write_bits(0, pad));
+        }
+        self.w.flush()
+    }
+
+    /// Return the number of bytes written so far, not counting the pending bits
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Flush the pending bits and return the underlying Writer
+    pub fn finish(mut self) -> (W, io::Result<()>) {
+        let result = self.flush();
+        (self.w, result)
+    }
+}
+
+
+/// A reader of bits, MSB first
+pub struct BitReader<R> {
+    r: R,
+    /// remaining bits of the last bytes, in the lowest positions
+    buffer: u64,
+    /// number of remaining bits
+    num_bits: usize,
+}
+
+impl<R: Read> BitReader<R> {
+    /// Create a new bit reader on top of a given Reader
+    pub fn new(r: R) -> BitReader<R> {
+        BitReader {
+            r: r,
+            buffer: 0,
+            num_bits: 0,
+        }
+    }
+
+    /// Read 'count' bits into the lowest positions of the result
+    pub fn read_bits(&mut self, count: usize) -> io::Result<u32> {
+        assert!(count <= MAX_BITS, "Unable to read {} bits at once", count);
+        while self.num_bits < count {
+            let mut byte = [0u8];
+            try!(self.r.read_exact(&mut byte));
+            self.buffer = (self.buffer << 8) | byte[0] as u64;
+            self.num_bits += 8;
+        }
+        self.num_bits -= count;
+        let value = (self.buffer >> self.num_bits) & ((1u64 << count) - 1);
+        self.buffer &= (1 << self.num_bits) - 1;
+        Ok(value as u32)
+    }
+
+    /// Skip the remaining bits of the current byte
+    pub fn align(&mut self) {
+        let skip = self.num_bits & 7;
+        self.num_bits -= skip;
+        self.buffer &= (1 << self.num_bits) - 1;
+    }
+
+    /// Return the underlying Reader, dropping the remaining bits
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use super::{BitReader, BitWriter, MAX_BITS};
+
+    #[test]
+    fn roundtrip_patterns() {
+        let mut seed = 1u32;
+        let fields: Vec<(u32, usize)> = (0..1000).map(|i| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let count = i % (MAX_BITS + 1);
+            let mask = if count == MAX_BITS {!0} else {(1u32 << count) - 1};
+            (seed & mask, count)
+        }).collect();
+        let mut w = BitWriter::new(Vec::new());
+        for &(value, count) in fields.iter() {
+            w.write_bits(value, count).unwrap();
+        }
+        let total_bits = fields.iter().fold(0, |s, &(_, count)| s + count);
+        let (encoded, result) = w.finish();
+        result.unwrap();
+        assert_eq!(encoded.len(), (total_bits + 7) / 8);
+        let mut r = BitReader::new(&encoded[..]);
+        for &(value, count) in fields.iter() {
+            assert_eq!(r.read_bits(count).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn flush_padding() {
+        let mut w = BitWriter::new(Vec::new());
+        w.write_bits(0b1, 1).unwrap();
+        assert_eq!(w.bytes_written(), 0);
+        w.flush().unwrap();
+        assert_eq!(w.bytes_written(), 1);
+        // a flush on the byte boundary adds nothing
+        w.flush().unwrap();
+        w.write_bits(0b1011, 4).unwrap();
+        w.write_bits(0xFF, 8).unwrap();
+        let (encoded, result) = w.finish();
+        result.unwrap();
+        assert_eq!(encoded, vec![0b1000_0000, 0b1011_1111, 0b1111_0000]);
+        let mut r = BitReader::new(&encoded[..]);
+        assert_eq!(r.read_bits(1).unwrap(), 1);
+        r.align();
+        assert_eq!(r.read_bits(12).unwrap(), 0b1011_1111_1111);
+        // padding reads as zeros, then the stream ends
+        assert_eq!(r.read_bits(4).unwrap(), 0);
+        assert_eq!(r.read_bits(1).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
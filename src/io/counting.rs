@@ -0,0 +1,151 @@
+/*!
+
+Stream adapters counting the bytes passed through them.
+
+# Example
+
+```rust
+use std::io::Write;
+use compress::entropy::ari;
+use compress::io::CountingWriter;
+
+let mut e = ari::ByteEncoder::new(CountingWriter::new(Vec::new()));
+e.write_all(b"some text").unwrap();
+let (counter, result) = e.finish();
+result.unwrap();
+assert_eq!(counter.count() as usize, counter.get_ref().len());
+```
+
+*/
+
+use std::io::{self, Read, Write};
+
+/// A writer counting the bytes written into the underlying stream
+pub struct CountingWriter<W> {
+    w: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    /// Create a new counter on top of a given Writer
+    pub fn new(w: W) -> CountingWriter<W> {
+        CountingWriter {
+            w: w,
+            count: 0,
+        }
+    }
+
+    /// Return the number of bytes written so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return a reference to the underlying Writer
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Return the underlying Writer
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amount = try!(self.w.write(buf));
+        self.count += amount as u64;
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+
+/// A reader counting the bytes read from the underlying stream
+pub struct CountingReader<R> {
+    r: R,
+    count: u64,
+}
+
+impl<R: Read> CountingReader<R> {
+    /// Create a new counter on top of a given Reader
+    pub fn new(r: R) -> CountingReader<R> {
+        CountingReader {
+            r: r,
+            count: 0,
+        }
+    }
+
+    /// Return the number of bytes read so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return a reference to the underlying Reader
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Return the underlying Reader
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amount = try!(self.r.read(buf));
+        self.count += amount as u64;
+        Ok(amount)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use super::{CountingReader, CountingWriter};
+
+    #[test]
+    fn count_written() {
+        let mut w = CountingWriter::new(Vec::new());
+        w.write_all(b"some").unwrap();
+        write!(w, " text {}", 1).unwrap();
+        assert_eq!(w.count(), 11);
+        assert_eq!(w.into_inner(), b"some text 1".to_vec());
+    }
+
+    #[test]
+    fn count_read() {
+        let data = [7u8; 1000];
+        let mut r = CountingReader::new(&data[..]);
+        let mut chunk = [0u8; 10];
+        r.read_exact(&mut chunk).unwrap();
+        assert_eq!(r.count(), 10);
+        let mut rest = Vec::new();
+        r.read_to_end(&mut rest).unwrap();
+        assert_eq!(r.count() as usize, data.len());
+    }
+
+    #[cfg(feature="entropy")]
+    #[test]
+    fn count_coded() {
+        use entropy::ari;
+        let text = include_bytes!("../data/test.txt");
+        let mut e = ari::ByteEncoder::new(CountingWriter::new(Vec::new()));
+        e.write_all(text).unwrap();
+        let (w, err) = e.finish();
+        err.unwrap();
+        let encoded = w.get_ref().clone();
+        assert_eq!(w.count() as usize, encoded.len());
+        let mut d = ari::ByteDecoder::new(CountingReader::new(&encoded[..]));
+        let mut decoded = Vec::new();
+        d.read_to_end(&mut decoded).unwrap();
+        assert_eq!(&decoded[..], &text[..]);
+        let (r, _) = d.finish();
+        assert_eq!(r.count() as usize, encoded.len());
+    }
+}
@@ -13,17 +13,30 @@ extern crate log;
 #[cfg(feature="unstable")]
 extern crate test;
 
-use std::io::{self, Read};
+use std::io::Read;
 
 /// Public exports
 #[cfg(feature="checksum")]
 pub use self::checksum::adler::State32 as Adler32;
+#[cfg(feature="checksum")]
+pub use self::checksum::crc32::State32 as Crc32;
+#[cfg(all(feature="entropy", feature="io", feature="checksum"))]
+pub use self::simple::{compress, decompress};
 
 #[cfg(feature="checksum")]
 /// Checksum algorithms. Requires `checksum` feature, enabled by default
 // http://en.wikipedia.org/wiki/Checksum
 pub mod checksum {
     pub mod adler;
+    pub mod crc32;
+
+    /// A running checksum of the data fed into it
+    pub trait Checksum {
+        /// Mutate the state for given data
+        fn update(&mut self, buf: &[u8]);
+        /// Get the checksum of the data so far
+        fn value(&self) -> u32;
+    }
 }
 
 #[cfg(feature="bwt")]
@@ -43,18 +56,37 @@ pub mod zlib;
 #[cfg(feature="entropy")]
 pub mod entropy {
     pub mod ari;
+    pub mod huffman;
+    #[cfg(feature="io")]
+    pub mod integer;
+    pub mod rans;
+    #[cfg(feature="io")]
+    pub mod rle;
+    pub mod zigzag;
 }
 
 #[cfg(feature="rle")]
 pub mod rle;
 
-#[cfg(any(feature = "lz4", feature = "entropy", feature = "bwt"))]
-fn byteorder_err_to_io(err: byteorder::Error) -> io::Error {
+#[cfg(all(feature="entropy", feature="io", feature="checksum"))]
+mod simple;
+
+/// Shared IO primitives
+#[cfg(feature="io")]
+pub mod io {
+    pub mod bits;
+    pub mod counting;
+
+    pub use self::counting::{CountingReader, CountingWriter};
+}
+
+#[cfg(any(feature = "lz4", feature = "bwt"))]
+fn byteorder_err_to_io(err: byteorder::Error) -> std::io::Error {
     match err {
         byteorder::Error::Io(e) => e,
         byteorder::Error::UnexpectedEOF =>
-            io::Error::new(
-                io::ErrorKind::Other,
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
                 "unexpected end of file"
             )
     }
@@ -64,12 +96,12 @@ fn byteorder_err_to_io(err: byteorder::Error) -> io::Error {
 /// to push_at_least in the late Reader trait
 pub trait ReadExact: Read + Sized {
     /// Appends exact number of bytes to a buffer
-    fn push_exactly(&mut self, bytes: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+    fn push_exactly(&mut self, bytes: u64, buf: &mut Vec<u8>) -> std::io::Result<()> {
         let n = try!(self.by_ref().take(bytes).read_to_end(buf)) as u64;
 
         if n < bytes {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
                 "unexpected end of file"
             ));
         }
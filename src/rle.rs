@@ -11,6 +11,9 @@ The most significat bit of each byte in this string indicates if the byte is
 the last byte in the string. The rest of the bits are concatenated using 
 the Little Endian convention.
 
+The output is plain bytes, meant for a later compression stage. For runs
+coded straight by the arithmetic coder, see `entropy::rle`.
+
 # Example
 
 ```rust
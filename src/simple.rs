@@ -0,0 +1,133 @@
+/*!
+
+One-call compression of byte slices, for the cases where picking
+a coder and its model is not worth the trouble. Requires `entropy`,
+`io` and `checksum` features, enabled by default.
+
+The input is coded by the arithmetic coder under an adaptive order-0
+or order-1 model, whichever makes it smaller. The output starts with
+a header: the format version, the model id, the input length (8 bytes)
+and its CRC-32 (4 bytes), both little endian, followed by the code.
+
+# Example
+
+```rust
+let text = b"some text, some more text";
+let encoded = compress::compress(text);
+let decoded = compress::decompress(&encoded).unwrap();
+assert_eq!(&decoded[..], &text[..]);
+```
+
+*/
+
+use std::io;
+use checksum::crc32;
+use entropy::ari::{self, Model};
+use entropy::ari::table::{AdaptiveByteModel, Order1Model};
+
+/// Version of the format written by `compress`
+const VERSION: u8 = 1;
+/// Model id of the adaptive order-0 byte model
+const MODEL_ORDER0: u8 = 0;
+/// Model id of the adaptive order-1 byte model
+const MODEL_ORDER1: u8 = 1;
+/// Size of the header: version, model id, length and checksum
+const HEADER_SIZE: usize = 14;
+
+/// Create the adaptive model of the given id
+fn new_model(id: u8) -> Option<Box<dyn Model<u8>>> {
+    match id {
+        MODEL_ORDER0 => Some(Box::new(AdaptiveByteModel::default())),
+        MODEL_ORDER1 => Some(Box::new(Order1Model::default())),
+        _ => None,
+    }
+}
+
+/// Compress the bytes with the model making them smaller
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let len_order0 = ari::coded_len(data, &mut AdaptiveByteModel::default()).unwrap();
+    let len_order1 = ari::coded_len(data, &mut Order1Model::default()).unwrap();
+    let id = if len_order1 < len_order0 {MODEL_ORDER1} else {MODEL_ORDER0};
+    let mut checksum = crc32::State32::new();
+    checksum.feed(data);
+    let mut output = Vec::with_capacity(HEADER_SIZE + len_order0.min(len_order1));
+    output.push(VERSION);
+    output.push(id);
+    let length = data.len() as u64;
+    output.extend((0..8).map(|i| (length >> (8*i)) as u8));
+    let checksum = checksum.result();
+    output.extend((0..4).map(|i| (checksum >> (8*i)) as u8));
+    let mut model = new_model(id).unwrap();
+    let mut encoder = ari::Encoder::new(output);
+    encoder.encode_all(data, &mut model).unwrap();
+    let (output, result) = encoder.finish();
+    result.unwrap();
+    output
+}
+
+/// Decompress the bytes produced by `compress`, checking the header
+/// and the checksum of the decoded bytes
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.len() < HEADER_SIZE {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated header"))
+    }
+    if data[0] != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported format version"))
+    }
+    let mut model = match new_model(data[1]) {
+        Some(model) => model,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown model")),
+    };
+    let length = data[2..10].iter().rev().fold(0u64, |u, &b| (u << 8) | b as u64);
+    let expected = data[10..14].iter().rev().fold(0u32, |u, &b| (u << 8) | b as u32);
+    // a corrupted length runs out of the code instead of allocating everything upfront
+    let mut decoder = ari::Decoder::new(&data[HEADER_SIZE..]);
+    let mut output = Vec::new();
+    for _ in 0..length {
+        output.push(try!(decoder.decode(&mut model)));
+    }
+    let mut checksum = crc32::State32::new();
+    checksum.feed(&output);
+    if checksum.result() != expected {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "checksum mismatch"))
+    }
+    Ok(output)
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use super::{compress, decompress};
+
+    static TEXT_INPUT: &'static [u8] = include_bytes!("data/test.txt");
+    static LARGE_INPUT: &'static [u8] = include_bytes!("data/test.large");
+
+    fn roundtrip(bytes: &[u8]) -> usize {
+        let encoded = compress(bytes);
+        assert_eq!(&decompress(&encoded).unwrap()[..], bytes);
+        encoded.len()
+    }
+
+    #[test]
+    fn some_roundtrips() {
+        roundtrip(b"");
+        roundtrip(b"a");
+        assert!(roundtrip(TEXT_INPUT) < TEXT_INPUT.len());
+        let binary: Vec<u8> = (0..1<<12).map(|i| (i * i >> 3) as u8).collect();
+        roundtrip(&binary);
+        let large = &LARGE_INPUT[..1<<16];
+        assert!(roundtrip(large) < large.len());
+    }
+
+    #[test]
+    fn corrupted_header() {
+        let encoded = compress(TEXT_INPUT);
+        assert_eq!(decompress(&encoded[..10]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        for i in 0..super::HEADER_SIZE {
+            let mut corrupted = encoded.clone();
+            corrupted[i] ^= 0x40;
+            assert!(decompress(&corrupted).is_err(), "Corrupted byte {} is accepted", i);
+        }
+    }
+}